@@ -1,5 +1,6 @@
 use crate::workloads::file::remove_duplicates_array;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use validator::Validate;
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -34,6 +35,74 @@ pub struct Container {
     registry: Registry,
     #[validate(length(min = 1))]
     image: String,
+    // labels the node must have to run the container
+    #[serde(default, rename = "nodeSelector")]
+    node_selector: HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    affinity: Option<Affinity>,
+    #[serde(default)]
+    tolerations: Vec<Toleration>,
+    // higher priority containers may evict lower priority ones when the cluster is full
+    #[serde(default)]
+    priority: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum LabelOperator {
+    In,
+    NotIn,
+    Exists,
+    DoesNotExist,
+}
+
+// requirement on a label of the nodes, `values` are only used by In / NotIn
+#[derive(Serialize, Deserialize, Debug)]
+struct LabelRequirement {
+    key: String,
+    operator: LabelOperator,
+    #[serde(default)]
+    values: Vec<String>,
+}
+
+// nodes matching all the requirements are favored by the weight (1 to 100)
+#[derive(Serialize, Deserialize, Debug)]
+struct Preference {
+    weight: u32,
+    requirements: Vec<LabelRequirement>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Affinity {
+    #[serde(default)]
+    required: Vec<LabelRequirement>,
+    #[serde(default)]
+    preferred: Vec<Preference>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+enum TolerationOperator {
+    #[default]
+    Equal,
+    Exists,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum TaintEffect {
+    NoSchedule,
+    PreferNoSchedule,
+}
+
+// allows the container on the nodes with a matching taint, every effect if none is given
+#[derive(Serialize, Deserialize, Debug)]
+struct Toleration {
+    #[serde(default)]
+    key: String,
+    #[serde(default)]
+    operator: TolerationOperator,
+    #[serde(default)]
+    value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    effect: Option<TaintEffect>,
 }
 
 // transform port from u32 to string
@@ -60,6 +60,15 @@ pub async fn post_workload(body: String) -> anyhow::Result<Json<Value>, ApiError
         image: json_body.workload.image,
         environment,
         resource_limits: Some(workload::Resources::default()),
+        node_selector: json_body.workload.node_selector,
+        affinity: json_body.workload.affinity.map(Into::into),
+        tolerations: json_body
+            .workload
+            .tolerations
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        priority: json_body.workload.priority,
     };
 
     let request = SchedulingRequest {
@@ -1,9 +1,14 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 use validator::{Validate, ValidationError};
 
+use crate::client::scheduler::workload as grpc;
+
 #[derive(Debug, Validate, Deserialize)]
 pub struct WorkloadRequest {
     pub version: String,
+    #[validate]
     pub workload: Workload,
 }
 
@@ -37,6 +42,90 @@ pub struct Workload {
     pub port: String,
 
     pub network: Vec<String>,
+
+    #[serde(default, rename = "nodeSelector")]
+    pub node_selector: HashMap<String, String>,
+
+    #[serde(default)]
+    #[validate]
+    pub affinity: Option<Affinity>,
+
+    #[serde(default)]
+    #[validate]
+    pub tolerations: Vec<Toleration>,
+
+    #[serde(default)]
+    pub priority: i32,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum LabelOperator {
+    In,
+    NotIn,
+    Exists,
+    DoesNotExist,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[validate(schema(function = "validate_label_requirement"))]
+pub struct LabelRequirement {
+    #[validate(length(min = 1))]
+    pub key: String,
+
+    pub operator: LabelOperator,
+
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct Preference {
+    #[validate(range(min = 1, max = 100))]
+    pub weight: u32,
+
+    #[validate]
+    pub requirements: Vec<LabelRequirement>,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct Affinity {
+    #[serde(default)]
+    #[validate]
+    pub required: Vec<LabelRequirement>,
+
+    #[serde(default)]
+    #[validate]
+    pub preferred: Vec<Preference>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub enum TolerationOperator {
+    #[default]
+    Equal,
+    Exists,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum TaintEffect {
+    NoSchedule,
+    PreferNoSchedule,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[validate(schema(function = "validate_toleration"))]
+pub struct Toleration {
+    #[serde(default)]
+    pub key: String,
+
+    #[serde(default)]
+    pub operator: TolerationOperator,
+
+    #[serde(default)]
+    pub value: String,
+
+    // Tolerates every effect when missing
+    #[serde(default)]
+    pub effect: Option<TaintEffect>,
 }
 
 fn validate_workload_kind(kind: &WorkloadKind) -> Result<(), ValidationError> {
@@ -51,4 +140,88 @@ fn validate_workload_registry(registry: &WorkloadRegistry) -> Result<(), Validat
         WorkloadRegistry::Podman => Ok(()),
         WorkloadRegistry::Ghcr => Ok(()),
     }
-}
\ No newline at end of file
+}
+
+// In and NotIn compare the label with the values, Exists and DoesNotExist do not take any
+fn validate_label_requirement(requirement: &LabelRequirement) -> Result<(), ValidationError> {
+    let compares_values = matches!(
+        requirement.operator,
+        LabelOperator::In | LabelOperator::NotIn
+    );
+
+    if compares_values == requirement.values.is_empty() {
+        return Err(ValidationError::new("invalid_label_requirement_values"));
+    }
+    Ok(())
+}
+
+// Equal needs a key to compare the value with, Exists does not take any value
+fn validate_toleration(toleration: &Toleration) -> Result<(), ValidationError> {
+    match toleration.operator {
+        TolerationOperator::Equal if toleration.key.is_empty() => {
+            Err(ValidationError::new("missing_toleration_key"))
+        }
+        TolerationOperator::Exists if !toleration.value.is_empty() => {
+            Err(ValidationError::new("unexpected_toleration_value"))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl From<LabelRequirement> for grpc::LabelRequirement {
+    fn from(requirement: LabelRequirement) -> Self {
+        let operator = match requirement.operator {
+            LabelOperator::In => grpc::label_requirement::Operator::In,
+            LabelOperator::NotIn => grpc::label_requirement::Operator::NotIn,
+            LabelOperator::Exists => grpc::label_requirement::Operator::Exists,
+            LabelOperator::DoesNotExist => grpc::label_requirement::Operator::DoesNotExist,
+        };
+
+        Self {
+            key: requirement.key,
+            operator: operator.into(),
+            values: requirement.values,
+        }
+    }
+}
+
+impl From<Affinity> for grpc::Affinity {
+    fn from(affinity: Affinity) -> Self {
+        Self {
+            required: affinity.required.into_iter().map(Into::into).collect(),
+            preferred: affinity
+                .preferred
+                .into_iter()
+                .map(|preference| grpc::affinity::Preference {
+                    weight: preference.weight,
+                    requirements: preference
+                        .requirements
+                        .into_iter()
+                        .map(Into::into)
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<Toleration> for grpc::Toleration {
+    fn from(toleration: Toleration) -> Self {
+        let operator = match toleration.operator {
+            TolerationOperator::Equal => grpc::toleration::Operator::Equal,
+            TolerationOperator::Exists => grpc::toleration::Operator::Exists,
+        };
+        let effect = match toleration.effect {
+            None => grpc::toleration::Effect::All,
+            Some(TaintEffect::NoSchedule) => grpc::toleration::Effect::NoSchedule,
+            Some(TaintEffect::PreferNoSchedule) => grpc::toleration::Effect::PreferNoSchedule,
+        };
+
+        Self {
+            key: toleration.key,
+            operator: operator.into(),
+            value: toleration.value,
+            effect: effect.into(),
+        }
+    }
+}
@@ -3,6 +3,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     tonic_build::configure().compile(
         &[
+            "src/scheduler/admin.proto",
             "src/scheduler/agent.proto",
             "src/scheduler/controller.proto",
         ],
@@ -2,6 +2,10 @@ pub mod node_agent {
     tonic::include_proto!("node_agent");
 }
 
+pub mod scheduler_admin {
+    tonic::include_proto!("scheduler.admin");
+}
+
 pub mod scheduler_agent {
     tonic::include_proto!("scheduler.agent");
 }
@@ -0,0 +1,227 @@
+//! Benchmark of the node statuses streamed by many node agents at the same time.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use orka_proto::scheduler_agent::{
+    node_status::{CpuLoad, Disk, LoadAverage, Memory, Network},
+    status_update_service_client::StatusUpdateServiceClient,
+    status_update_service_server::StatusUpdateServiceServer,
+    NodeStatus,
+};
+use orka_scheduler::grpc::agent_status_update_service::AgentStatusUpdateSvc;
+use orka_scheduler::grpc::controller_scheduling_service::StatusSenders;
+use orka_scheduler::grpc::conversions::to_node_metrics;
+use orka_scheduler::managers::events::broadcaster::ClusterEvents;
+use orka_scheduler::managers::instance::manager::InstanceManager;
+use orka_scheduler::managers::node_agent::manager::NodeAgentManager;
+use orka_scheduler::managers::node_agent::properties::NodeProperties;
+use orka_scheduler::managers::pending::queue::{PendingQueue, QueueOptions};
+use orka_scheduler::metrics::registry::SchedulerMetrics;
+use orka_scheduler::state::cluster::ClusterState;
+use orka_scheduler::state::log::StateLog;
+use tokio::net::TcpListener;
+use tokio::runtime::Runtime;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Endpoint, Server};
+
+/// Number of node agents streaming their status concurrently.
+const AGENTS: usize = 1_000;
+
+/// Number of statuses sent by each agent during an iteration.
+const UPDATES_PER_AGENT: usize = 10;
+
+/// Create a manager holding the benchmarked agents, returning it with their IDs.
+///
+/// # Arguments
+///
+/// * `state_log` - The log recording the changes of the agents, as in production, if any.
+fn manager_with_agents(state_log: Option<StateLog>) -> (Arc<NodeAgentManager>, Vec<String>) {
+    let manager = Arc::new(match state_log {
+        Some(state_log) => NodeAgentManager::restore(&ClusterState::default(), Arc::new(state_log)),
+        None => NodeAgentManager::new(),
+    });
+    let ids: Vec<String> = (0..AGENTS).map(|i| format!("agent-{}", i)).collect();
+
+    for id in &ids {
+        manager
+            .add_agent(id, "127.0.0.1:50052", NodeProperties::default())
+            .unwrap();
+    }
+
+    (manager, ids)
+}
+
+/// Create the status sent by an agent.
+fn node_status(id: &str, update: usize) -> NodeStatus {
+    NodeStatus {
+        id: id.to_string(),
+        memory: Some(Memory {
+            total: 8 << 30,
+            free: (4 << 30) + update as u64,
+        }),
+        cpu_load: Some(CpuLoad {
+            load: (update % 100) as f64,
+            cores: vec![(update % 100) as f64; 4],
+        }),
+        disks: vec![Disk {
+            mount_point: "/".to_string(),
+            total: 100 << 30,
+            free: 50 << 30,
+        }],
+        network: Some(Network {
+            received: 1 << 20,
+            transmitted: 1 << 20,
+        }),
+        load_average: Some(LoadAverage {
+            one: 0.5,
+            five: 0.4,
+            fifteen: 0.3,
+        }),
+        running_instances: Some(2),
+        instances: Vec::new(),
+        lists_instances: false,
+    }
+}
+
+/// Open a state log in an empty temporary directory.
+fn state_log() -> StateLog {
+    let dir = std::env::temp_dir().join(format!("orka-scheduler-bench-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let (state_log, _) = StateLog::open(&dir).unwrap();
+    state_log
+}
+
+/// Update the agents directly in the manager, one task per agent.
+///
+/// # Arguments
+///
+/// * `manager` - The manager holding the agents.
+/// * `ids` - The IDs of the agents.
+async fn update_agents(manager: &Arc<NodeAgentManager>, ids: &[String]) {
+    let tasks: Vec<_> = ids
+        .iter()
+        .map(|id| {
+            let manager = Arc::clone(manager);
+            let id = id.clone();
+
+            tokio::spawn(async move {
+                for update in 0..UPDATES_PER_AGENT {
+                    let metrics = to_node_metrics(node_status(&id, update));
+
+                    manager.update_node_status(&id, metrics).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        task.await.unwrap();
+    }
+}
+
+/// Update the agents directly in the manager, with and without recording the changes in a state
+/// log as the scheduler does.
+fn registry_updates(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("registry");
+    group.throughput(Throughput::Elements((AGENTS * UPDATES_PER_AGENT) as u64));
+
+    let (manager, ids) = manager_with_agents(None);
+    group.bench_function("1000_agents", |b| {
+        b.to_async(&runtime).iter(|| update_agents(&manager, &ids))
+    });
+
+    let (manager, ids) = manager_with_agents(Some(state_log()));
+    group.bench_function("1000_agents_with_state_log", |b| {
+        b.to_async(&runtime).iter(|| update_agents(&manager, &ids))
+    });
+
+    group.finish();
+}
+
+/// Start a status update server on a local port, returning its address.
+async fn start_server(manager: Arc<NodeAgentManager>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+
+    tokio::spawn(
+        Server::builder()
+            .add_service(StatusUpdateServiceServer::new(AgentStatusUpdateSvc::new(
+                manager,
+                Arc::new(InstanceManager::new()),
+                Arc::new(StatusSenders::new()),
+                Arc::new(PendingQueue::new(QueueOptions::default())),
+                false,
+                Arc::new(SchedulerMetrics::new().unwrap()),
+                Arc::new(ClusterEvents::new()),
+                None,
+            )))
+            .serve_with_incoming(TcpListenerStream::new(listener)),
+    );
+
+    address
+}
+
+/// Stream the statuses of the agents through the gRPC service, with one connection and one
+/// stream per agent.
+fn grpc_streams(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let (manager, ids) = manager_with_agents(Some(state_log()));
+
+    let clients: Vec<StatusUpdateServiceClient<Channel>> = runtime.block_on(async {
+        let address = start_server(manager).await;
+        let mut clients = Vec::with_capacity(AGENTS);
+
+        for _ in 0..AGENTS {
+            let channel = Endpoint::from_shared(format!("http://{}", address))
+                .unwrap()
+                .connect()
+                .await
+                .unwrap();
+
+            clients.push(StatusUpdateServiceClient::new(channel));
+        }
+
+        clients
+    });
+
+    let mut group = c.benchmark_group("grpc");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements((AGENTS * UPDATES_PER_AGENT) as u64));
+
+    group.bench_function("1000_streams", |b| {
+        b.to_async(&runtime).iter(|| async {
+            let tasks: Vec<_> = clients
+                .iter()
+                .zip(&ids)
+                .map(|(client, id)| {
+                    let mut client = client.clone();
+                    let statuses: Vec<_> = (0..UPDATES_PER_AGENT)
+                        .map(|update| node_status(id, update))
+                        .collect();
+
+                    tokio::spawn(async move {
+                        client
+                            .update_node_status(tokio_stream::iter(statuses))
+                            .await
+                            .unwrap();
+                    })
+                })
+                .collect();
+
+            for task in tasks {
+                task.await.unwrap();
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, registry_updates, grpc_streams);
+criterion_main!(benches);
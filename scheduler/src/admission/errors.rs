@@ -0,0 +1,15 @@
+//! Admission errors.
+
+use thiserror::Error;
+
+/// Admission error enum to have self-explanatory and compact errors.
+#[derive(Error, Debug)]
+pub enum AdmissionError {
+    /// The agent ID is not accepted by the admission policy.
+    #[error("Agent ID not allowed: `{0}`")]
+    IdNotAllowed(String),
+
+    /// The agent sends messages faster than allowed.
+    #[error("Node agent `{0}` exceeded its rate limit of {1} messages per second")]
+    RateLimited(String, f64),
+}
@@ -0,0 +1,5 @@
+//! Admission control of the node agents, protecting the scheduler from misbehaving agents.
+
+pub mod errors;
+pub mod policy;
+pub mod rate_limit;
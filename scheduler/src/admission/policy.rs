@@ -0,0 +1,100 @@
+//! Policy deciding which node agents may join the cluster.
+
+use std::collections::HashSet;
+
+use regex::Regex;
+
+use super::errors::AdmissionError;
+
+/// The admission policy of the node agents, restricting their IDs and their number.
+#[derive(Debug, Clone, Default)]
+pub struct AdmissionPolicy {
+    /// The IDs always accepted.
+    allowed_ids: HashSet<String>,
+
+    /// The pattern the other IDs must match entirely, if any.
+    id_pattern: Option<Regex>,
+
+    /// The maximum number of agents in the cluster, if any.
+    max_agents: Option<usize>,
+}
+
+impl AdmissionPolicy {
+    /// Create a new `AdmissionPolicy`. Every ID is accepted if there is neither an allowlist nor
+    /// a pattern, otherwise an ID must be in the allowlist or match the pattern.
+    ///
+    /// # Arguments
+    ///
+    /// * `allowed_ids` - The IDs always accepted.
+    /// * `id_pattern` - The regular expression the other IDs must match entirely, if any.
+    /// * `max_agents` - The maximum number of agents in the cluster, if any.
+    ///
+    /// # Errors
+    ///
+    /// * The pattern is not a valid regular expression.
+    pub fn new(
+        allowed_ids: &[String],
+        id_pattern: Option<&str>,
+        max_agents: Option<usize>,
+    ) -> Result<Self, regex::Error> {
+        // Anchor the pattern, so that `node-[0-9]+` does not accept `evil-node-1`
+        let id_pattern = id_pattern
+            .map(|pattern| Regex::new(&format!("^(?:{})$", pattern)))
+            .transpose()?;
+
+        Ok(Self {
+            allowed_ids: allowed_ids.iter().cloned().collect(),
+            id_pattern,
+            max_agents,
+        })
+    }
+
+    /// Check that an agent ID is accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the agent.
+    ///
+    /// # Errors
+    ///
+    /// * The ID is neither in the allowlist nor matches the pattern.
+    pub fn check_id(&self, agent_id: &str) -> Result<(), AdmissionError> {
+        let unrestricted = self.allowed_ids.is_empty() && self.id_pattern.is_none();
+        let allowed = self.allowed_ids.contains(agent_id)
+            || self
+                .id_pattern
+                .as_ref()
+                .is_some_and(|pattern| pattern.is_match(agent_id));
+
+        if unrestricted || allowed {
+            Ok(())
+        } else {
+            Err(AdmissionError::IdNotAllowed(agent_id.to_string()))
+        }
+    }
+
+    /// Get the maximum number of agents in the cluster, if any. It is enforced by the node agent
+    /// manager when agents join, so that concurrent joins cannot exceed it.
+    pub fn max_agents(&self) -> Option<usize> {
+        self.max_agents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_allowed_or_matching_ids() {
+        let policy =
+            AdmissionPolicy::new(&["gateway".to_string()], Some("node-[0-9]+"), None).unwrap();
+
+        assert!(policy.check_id("gateway").is_ok());
+        assert!(policy.check_id("node-12").is_ok());
+        assert!(policy.check_id("evil-node-1").is_err());
+        assert!(policy.check_id("node-1x").is_err());
+
+        assert!(AdmissionPolicy::default().check_id("anything").is_ok());
+        assert!(AdmissionPolicy::new(&[], Some("node-("), None).is_err());
+    }
+}
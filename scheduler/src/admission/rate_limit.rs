@@ -0,0 +1,130 @@
+//! Rate limits of the messages sent by the node agents.
+
+use std::time::Instant;
+
+use dashmap::DashMap;
+
+use super::errors::AdmissionError;
+
+/// The rate at which an agent may send messages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// The number of messages allowed per second, on average.
+    pub per_second: f64,
+    /// The number of messages that may be sent at once, after a quiet period.
+    pub burst: u32,
+}
+
+/// A token bucket, refilled at the rate of the limit and emptied by one token per message.
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    /// The tokens left in the bucket.
+    tokens: f64,
+    /// When the tokens were last refilled.
+    refilled_at: Instant,
+}
+
+/// The rate limiter of the messages of the node agents, with one bucket per agent shared by all
+/// of its streams. The buckets are stored in a sharded map, so that the agents rarely contend.
+#[derive(Debug)]
+pub struct AgentRateLimiter {
+    /// The limit applied to every agent.
+    limit: RateLimit,
+
+    /// The token buckets of the agents, indexed by agent ID.
+    buckets: DashMap<String, TokenBucket>,
+}
+
+impl AgentRateLimiter {
+    /// Create a new `AgentRateLimiter`, the agents starting with a full bucket.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The limit applied to every agent.
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// Account for a message sent by an agent.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the agent.
+    /// * `now` - The time the message was received.
+    ///
+    /// # Errors
+    ///
+    /// * The agent exceeded its rate limit, the message must be refused.
+    pub fn check(&self, agent_id: &str, now: Instant) -> Result<(), AdmissionError> {
+        let capacity = f64::from(self.limit.burst);
+
+        let mut bucket = self
+            .buckets
+            .entry(agent_id.to_string())
+            .or_insert(TokenBucket {
+                tokens: capacity,
+                refilled_at: now,
+            });
+
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        bucket.tokens =
+            (bucket.tokens + elapsed.as_secs_f64() * self.limit.per_second).min(capacity);
+        bucket.refilled_at = bucket.refilled_at.max(now);
+
+        if bucket.tokens < 1.0 {
+            return Err(AdmissionError::RateLimited(
+                agent_id.to_string(),
+                self.limit.per_second,
+            ));
+        }
+
+        bucket.tokens -= 1.0;
+        Ok(())
+    }
+
+    /// Forget the bucket of an agent, once it left the cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the agent.
+    pub fn forget(&self, agent_id: &str) {
+        self.buckets.remove(agent_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn allows_bursts_then_the_average_rate() {
+        let limiter = AgentRateLimiter::new(RateLimit {
+            per_second: 2.0,
+            burst: 3,
+        });
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.check("agent", now).is_ok());
+        }
+
+        assert!(matches!(
+            limiter.check("agent", now),
+            Err(AdmissionError::RateLimited(_, _))
+        ));
+        assert!(limiter.check("other", now).is_ok());
+
+        // A token is refilled every half second
+        assert!(limiter
+            .check("agent", now + Duration::from_millis(500))
+            .is_ok());
+        assert!(limiter
+            .check("agent", now + Duration::from_millis(700))
+            .is_err());
+    }
+}
@@ -1,12 +1,22 @@
 //! Command-line arguments.
 
-use std::{fs, io::ErrorKind};
+use std::{fs, io::ErrorKind, net::IpAddr, path::Path, path::PathBuf, time::Duration};
 
-use anyhow::{Context, Result};
-use clap::Parser;
+use anyhow::{bail, Context, Result};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
 use clap_verbosity_flag::{InfoLevel, Verbosity};
 use tracing::{event, Level};
 
+use crate::admission::policy::AdmissionPolicy;
+use crate::admission::rate_limit::RateLimit;
+use crate::config::ConfigFile;
+use crate::managers::node_agent::reaper::HeartbeatTimeouts;
+use crate::managers::pending::queue::QueueOptions;
+use crate::placement::reservation::OvercommitRatios;
+use crate::placement::strategy::StrategyKind;
+use crate::tls::config::{CertificateOptions, KeyAlgorithm, SanMismatchPolicy};
+use crate::tls::rotation::RotationOptions;
+
 /// Scheduler service for the Orka container orchestration system.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -15,6 +25,16 @@ pub struct CliArguments {
     #[arg(long, default_value = "/var/lib/orka/scheduler/", env)]
     pub data_dir: String,
 
+    /// Configuration file, in the TOML or YAML format depending on its extension. Defaults to
+    /// `scheduler.toml`, `scheduler.yaml` or `scheduler.yml` in the data directory, if present.
+    /// The command line and the environment take precedence over the file.
+    #[arg(long, env)]
+    pub config: Option<PathBuf>,
+
+    /// Print the effective configuration in the TOML format and exit.
+    #[arg(long, default_value_t = false)]
+    pub print_config: bool,
+
     /// Disable TLS for the gRPC server.
     #[arg(long, default_value_t = false, env)]
     pub no_tls: bool,
@@ -23,6 +43,40 @@ pub struct CliArguments {
     #[arg(long, default_value_t = false, env)]
     pub no_tls_secret_generation: bool,
 
+    /// Extra DNS names of the generated TLS certificate, `localhost` is always included.
+    #[arg(long, value_delimiter = ',', env)]
+    pub tls_san_dns: Vec<String>,
+
+    /// IP addresses of the generated TLS certificate.
+    #[arg(long, value_delimiter = ',', env)]
+    pub tls_san_ip: Vec<IpAddr>,
+
+    /// Algorithm of the generated TLS keypairs.
+    #[arg(long, value_enum, default_value_t = KeyAlgorithm::EcdsaP256, env)]
+    pub tls_key_algorithm: KeyAlgorithm,
+
+    /// Days during which the generated TLS certificate is valid.
+    #[arg(long, default_value_t = 3650, env)]
+    pub tls_validity_days: u64,
+
+    /// What to do when the existing TLS certificate lacks some of the requested DNS names or IP
+    /// addresses.
+    #[arg(long, value_enum, default_value_t = SanMismatchPolicy::Warn, env)]
+    pub tls_san_mismatch: SanMismatchPolicy,
+
+    /// Seconds between two checks of the TLS certificate and private key files for changes. The
+    /// files are also reloaded when the process receives SIGHUP.
+    #[arg(long, default_value_t = 30, env)]
+    pub tls_reload_interval: u64,
+
+    /// Automatically renew the generated TLS certificate when it is close to its expiration.
+    #[arg(long, default_value_t = false, env)]
+    pub tls_auto_renew: bool,
+
+    /// Days before its expiration at which the generated TLS certificate is renewed.
+    #[arg(long, default_value_t = 30, env)]
+    pub tls_renew_before_days: u64,
+
     /// The address to bind the gRPC server to.
     #[arg(long, default_value = "[::]", env)]
     pub grpc_bind_address: String,
@@ -31,12 +85,356 @@ pub struct CliArguments {
     #[arg(long, default_value_t = 50051, env)]
     pub grpc_bind_port: u16,
 
+    /// The strategy used to rank the nodes able to run a workload.
+    #[arg(long, value_enum, default_value_t = StrategyKind::Spread, env)]
+    pub scheduling_strategy: StrategyKind,
+
+    /// Ratio by which the CPU reserved by the workloads of a node may exceed its capacity.
+    #[arg(long, default_value_t = 1.0, env)]
+    pub cpu_overcommit_ratio: f64,
+
+    /// Ratio by which the memory reserved by the workloads of a node may exceed its capacity.
+    #[arg(long, default_value_t = 1.0, env)]
+    pub memory_overcommit_ratio: f64,
+
+    /// Ratio by which the disk space reserved by the workloads of a node may exceed its
+    /// capacity.
+    #[arg(long, default_value_t = 1.0, env)]
+    pub disk_overcommit_ratio: f64,
+
+    /// Seconds without heartbeat after which a node agent is considered unhealthy.
+    #[arg(long, default_value_t = 15, env)]
+    pub agent_unhealthy_timeout: u64,
+
+    /// Seconds without heartbeat after which a node agent is removed from the cluster.
+    #[arg(long, default_value_t = 60, env)]
+    pub agent_eviction_timeout: u64,
+
+    /// IDs of the node agents allowed to join the cluster. Every ID is allowed if neither an
+    /// allowlist nor a pattern is set.
+    #[arg(long, value_delimiter = ',', env)]
+    pub agent_id_allowlist: Vec<String>,
+
+    /// Regular expression that the IDs of the node agents outside of the allowlist must match
+    /// entirely to join the cluster.
+    #[arg(long, env)]
+    pub agent_id_pattern: Option<String>,
+
+    /// Maximum number of node agents in the cluster. The number of agents is not limited if
+    /// unset.
+    #[arg(long, env)]
+    pub max_agents: Option<usize>,
+
+    /// Number of node statuses each node agent may send per second, on average. The status
+    /// stream of an agent exceeding it is closed. The statuses are not limited if unset.
+    #[arg(long, env)]
+    pub agent_status_rate: Option<f64>,
+
+    /// Number of node statuses a node agent may send at once after a quiet period, when their
+    /// rate is limited.
+    #[arg(long, default_value_t = 10, env)]
+    pub agent_status_burst: u32,
+
+    /// Seconds before the first retry of a workload that no node agent can run yet. The delay
+    /// doubles with every failed retry.
+    #[arg(long, default_value_t = 1, env)]
+    pub pending_initial_backoff: u64,
+
+    /// Longest delay between two retries of a workload that no node agent can run yet, in
+    /// seconds.
+    #[arg(long, default_value_t = 60, env)]
+    pub pending_max_backoff: u64,
+
+    /// Seconds after which a workload that no node agent can run is abandoned.
+    #[arg(long, default_value_t = 300, env)]
+    pub pending_max_wait: u64,
+
+    /// Number of healthy node agents required before the scheduling service reports itself as
+    /// serving through the gRPC health checking protocol.
+    #[arg(long, default_value_t = 1, env)]
+    pub min_ready_agents: usize,
+
+    /// Port of an additional plaintext listener only serving the gRPC health checking protocol,
+    /// on the gRPC bind address.
+    #[arg(long, env)]
+    pub health_bind_port: Option<u16>,
+
+    /// Port of an HTTP listener exporting Prometheus metrics on `/metrics`, on the gRPC bind
+    /// address. Metrics are not exported if unset.
+    #[arg(long, env)]
+    pub metrics_bind_port: Option<u16>,
+
+    /// Seconds given to the in-flight calls and status streams to finish on shutdown.
+    #[arg(long, default_value_t = 30, env)]
+    pub shutdown_timeout: u64,
+
     /// Verbosity level.
     #[command(flatten)]
     pub verbose: Verbosity<InfoLevel>,
+
+    /// Subcommand to run instead of the scheduler.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Subcommands of the scheduler.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Manage the bootstrap tokens used by node agents to enroll into the cluster.
+    #[command(subcommand)]
+    Token(TokenCommand),
+
+    /// Issue a client certificate granting access to the admin service, signed by the
+    /// certificate authority of the scheduler.
+    AdminCertificate {
+        /// The name of the operator, used as the common name of the certificate and as the
+        /// name of the written files.
+        name: String,
+
+        /// The directory to write the certificate and its private key to.
+        #[arg(long, default_value = ".")]
+        out_dir: String,
+    },
+}
+
+/// Subcommands managing the bootstrap tokens.
+#[derive(Subcommand, Debug)]
+pub enum TokenCommand {
+    /// Create a single-use bootstrap token and print it.
+    Create {
+        /// Seconds during which the token is accepted.
+        #[arg(long, default_value_t = 86400)]
+        ttl: u64,
+    },
+
+    /// List the bootstrap tokens.
+    List,
+
+    /// Revoke a bootstrap token.
+    Revoke {
+        /// The ID of the token, as displayed by the `list` subcommand.
+        id: String,
+    },
 }
 
 impl CliArguments {
+    /// Parse the command-line arguments and the environment, layered over the configuration
+    /// file, then validate the result. Exits with a usage message if the arguments are invalid.
+    ///
+    /// # Errors
+    ///
+    /// * The configuration file could not be loaded.
+    /// * The configuration is invalid.
+    pub fn load() -> Result<Self> {
+        let matches = Self::command().get_matches();
+
+        Self::from_matches(&matches)
+    }
+
+    /// Build the arguments from parsed command-line matches, layered over the configuration
+    /// file, then validate the result.
+    ///
+    /// # Arguments
+    ///
+    /// * `matches` - The parsed command line.
+    ///
+    /// # Errors
+    ///
+    /// * The configuration file could not be loaded.
+    /// * The configuration is invalid.
+    pub fn from_matches(matches: &ArgMatches) -> Result<Self> {
+        let mut args = Self::from_arg_matches(matches).unwrap_or_else(|err| err.exit());
+
+        if let Some(file) = ConfigFile::load(args.config.as_deref(), Path::new(&args.data_dir))? {
+            let path = file.path.clone();
+
+            file.apply(&mut args, matches)
+                .with_context(|| format!("Invalid configuration file: {}", path.display()))?;
+        }
+
+        args.validate()
+            .with_context(|| "Invalid configuration of the scheduler")?;
+
+        Ok(args)
+    }
+
+    /// Check that the configuration is consistent, so that the scheduler does not fail after it
+    /// started.
+    ///
+    /// # Errors
+    ///
+    /// * An option is out of its bounds, or conflicts with another one.
+    pub fn validate(&self) -> Result<()> {
+        self.queue_options()?;
+        self.overcommit_ratios()?;
+        self.rotation_options()?;
+        self.admission_policy()?;
+        self.status_rate_limit()?;
+
+        if self.agent_unhealthy_timeout >= self.agent_eviction_timeout {
+            bail!(
+                "The agent unhealthy timeout ({}s) must be shorter than the eviction timeout ({}s)",
+                self.agent_unhealthy_timeout,
+                self.agent_eviction_timeout
+            );
+        }
+
+        let ports = [
+            ("health", self.health_bind_port),
+            ("metrics", self.metrics_bind_port),
+        ];
+
+        for (listener, port) in ports {
+            if port == Some(self.grpc_bind_port) {
+                bail!(
+                    "The {} port ({}) is already used by the gRPC server",
+                    listener,
+                    self.grpc_bind_port
+                );
+            }
+        }
+
+        if self.health_bind_port.is_some() && self.health_bind_port == self.metrics_bind_port {
+            bail!(
+                "The health and metrics listeners cannot share the same port ({})",
+                self.metrics_bind_port.unwrap_or_default()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Get the timeouts applied to the heartbeats of the node agents.
+    pub fn heartbeat_timeouts(&self) -> HeartbeatTimeouts {
+        HeartbeatTimeouts {
+            unhealthy: Duration::from_secs(self.agent_unhealthy_timeout),
+            eviction: Duration::from_secs(self.agent_eviction_timeout),
+        }
+    }
+
+    /// Get the options of the queue of the workloads waiting for a node agent.
+    ///
+    /// # Errors
+    ///
+    /// * The initial backoff is zero or longer than the maximum backoff.
+    pub fn queue_options(&self) -> Result<QueueOptions> {
+        if self.pending_initial_backoff == 0 {
+            bail!("The initial backoff of pending workloads must be at least one second");
+        }
+
+        if self.pending_initial_backoff > self.pending_max_backoff {
+            bail!(
+                "The initial backoff of pending workloads ({}s) exceeds the maximum backoff ({}s)",
+                self.pending_initial_backoff,
+                self.pending_max_backoff
+            );
+        }
+
+        Ok(QueueOptions {
+            initial_backoff: Duration::from_secs(self.pending_initial_backoff),
+            max_backoff: Duration::from_secs(self.pending_max_backoff),
+            max_wait: Duration::from_secs(self.pending_max_wait),
+        })
+    }
+
+    /// Get the admission policy of the node agents.
+    ///
+    /// # Errors
+    ///
+    /// * The agent ID pattern is not a valid regular expression.
+    /// * The maximum number of agents is zero.
+    pub fn admission_policy(&self) -> Result<AdmissionPolicy> {
+        if self.max_agents == Some(0) {
+            bail!("The maximum number of node agents must be at least one");
+        }
+
+        AdmissionPolicy::new(
+            &self.agent_id_allowlist,
+            self.agent_id_pattern.as_deref(),
+            self.max_agents,
+        )
+        .with_context(|| "The agent ID pattern is not a valid regular expression")
+    }
+
+    /// Get the rate limit of the node statuses sent by each node agent, if any.
+    ///
+    /// # Errors
+    ///
+    /// * The rate is not a positive number.
+    /// * The burst is zero.
+    pub fn status_rate_limit(&self) -> Result<Option<RateLimit>> {
+        let Some(per_second) = self.agent_status_rate else {
+            return Ok(None);
+        };
+
+        if !per_second.is_finite() || per_second <= 0.0 {
+            bail!(
+                "The agent status rate must be a positive number, got {}",
+                per_second
+            );
+        }
+
+        if self.agent_status_burst == 0 {
+            bail!("The agent status burst must be at least one");
+        }
+
+        Ok(Some(RateLimit {
+            per_second,
+            burst: self.agent_status_burst,
+        }))
+    }
+
+    /// Get the ratios by which the resources reserved on a node may exceed its capacity.
+    ///
+    /// # Errors
+    ///
+    /// * A ratio is not a positive number.
+    pub fn overcommit_ratios(&self) -> Result<OvercommitRatios> {
+        OvercommitRatios::new(
+            self.cpu_overcommit_ratio,
+            self.memory_overcommit_ratio,
+            self.disk_overcommit_ratio,
+        )
+    }
+
+    /// Get the parameters of the generated TLS certificates.
+    pub fn certificate_options(&self) -> CertificateOptions {
+        CertificateOptions::new(
+            &self.tls_san_dns,
+            &self.tls_san_ip,
+            self.tls_key_algorithm,
+            Duration::from_secs(self.tls_validity_days.saturating_mul(24 * 60 * 60)),
+            self.tls_san_mismatch,
+        )
+    }
+
+    /// Get the options of the rotation of the TLS identity.
+    ///
+    /// # Errors
+    ///
+    /// * The reload interval is zero.
+    /// * Automatic renewal is enabled, but certificates would be renewed as soon as generated.
+    pub fn rotation_options(&self) -> Result<RotationOptions> {
+        if self.tls_reload_interval == 0 {
+            bail!("The TLS reload interval must be greater than zero");
+        }
+
+        if self.tls_auto_renew && self.tls_renew_before_days >= self.tls_validity_days {
+            bail!(
+                "The TLS certificate would be renewed continuously: it is valid for {} days but renewed {} days before its expiration",
+                self.tls_validity_days,
+                self.tls_renew_before_days
+            );
+        }
+
+        Ok(RotationOptions {
+            reload_interval: Duration::from_secs(self.tls_reload_interval),
+            renew_before: self.tls_auto_renew.then(|| {
+                Duration::from_secs(self.tls_renew_before_days.saturating_mul(24 * 60 * 60))
+            }),
+        })
+    }
+
     /// Prepare the application directories by creating them.
     ///
     /// # Errors
@@ -0,0 +1,121 @@
+//! Simulator replaying a trace of the cluster with the placement code of the scheduler, to
+//! compare the scoring strategies offline.
+
+use std::error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use clap_verbosity_flag::{Verbosity, WarnLevel};
+use tracing_log::AsTrace;
+
+use orka_scheduler::managers::pending::queue::QueueOptions;
+use orka_scheduler::placement::reservation::OvercommitRatios;
+use orka_scheduler::placement::strategy::StrategyKind;
+use orka_scheduler::simulation::engine::{Simulation, SimulationOptions};
+use orka_scheduler::simulation::report::Report;
+use orka_scheduler::simulation::trace::Trace;
+
+/// Discrete-event simulator of the Orka scheduler, reporting how the scoring strategies place
+/// the workloads of a trace.
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct SimulatorArguments {
+    /// The trace of the agents and workloads to simulate, in the JSON format or in the YAML
+    /// format if its extension is `.yaml` or `.yml`.
+    trace: PathBuf,
+
+    /// The scoring strategies to simulate. All of them are simulated if unset.
+    #[arg(long, value_enum, value_delimiter = ',')]
+    strategy: Vec<StrategyKind>,
+
+    /// Ratio by which the CPU reserved by the workloads of a node may exceed its capacity.
+    #[arg(long, default_value_t = 1.0)]
+    cpu_overcommit_ratio: f64,
+
+    /// Ratio by which the memory reserved by the workloads of a node may exceed its capacity.
+    #[arg(long, default_value_t = 1.0)]
+    memory_overcommit_ratio: f64,
+
+    /// Ratio by which the disk space reserved by the workloads of a node may exceed its
+    /// capacity.
+    #[arg(long, default_value_t = 1.0)]
+    disk_overcommit_ratio: f64,
+
+    /// Seconds before the first retry of a workload that no node agent can run yet.
+    #[arg(long, default_value_t = 1)]
+    pending_initial_backoff: u64,
+
+    /// Longest delay between two retries of a workload that no node agent can run yet, in
+    /// seconds.
+    #[arg(long, default_value_t = 60)]
+    pending_max_backoff: u64,
+
+    /// Seconds after which a workload that no node agent can run is abandoned.
+    #[arg(long, default_value_t = 300)]
+    pending_max_wait: u64,
+
+    /// File to write the JSON report to, instead of the standard output.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Verbosity level.
+    #[command(flatten)]
+    verbose: Verbosity<WarnLevel>,
+}
+
+/// The application entry point.
+fn main() -> Result<(), Box<dyn error::Error>> {
+    let args = SimulatorArguments::parse();
+
+    // The report is written to the standard output, keep it clean
+    tracing_subscriber::fmt()
+        .with_max_level(args.verbose.log_level_filter().as_trace())
+        .with_writer(std::io::stderr)
+        .init();
+
+    if args.pending_initial_backoff == 0 || args.pending_initial_backoff > args.pending_max_backoff
+    {
+        return Err(
+            "The initial backoff must be between one second and the maximum backoff".into(),
+        );
+    }
+
+    let trace = Trace::load(&args.trace)?;
+    let options = SimulationOptions {
+        overcommit_ratios: OvercommitRatios::new(
+            args.cpu_overcommit_ratio,
+            args.memory_overcommit_ratio,
+            args.disk_overcommit_ratio,
+        )?,
+        queue: QueueOptions {
+            initial_backoff: Duration::from_secs(args.pending_initial_backoff),
+            max_backoff: Duration::from_secs(args.pending_max_backoff),
+            max_wait: Duration::from_secs(args.pending_max_wait),
+        },
+    };
+
+    let strategies = if args.strategy.is_empty() {
+        StrategyKind::value_variants().to_vec()
+    } else {
+        args.strategy
+    };
+
+    let report = Report {
+        strategies: strategies
+            .into_iter()
+            .map(|strategy| Simulation::new(&trace, strategy, &options).run())
+            .collect(),
+    };
+    let json = serde_json::to_string_pretty(&report)?;
+
+    match args.output {
+        Some(path) => fs::write(&path, json + "\n")
+            .with_context(|| format!("Unable to write the report: {}", path.display()))?,
+        None => println!("{}", json),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,444 @@
+//! Configuration file of the scheduler, layered under the command-line arguments and the
+//! environment.
+
+use std::{
+    fs,
+    io::ErrorKind,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use clap::{parser::ValueSource, ArgMatches};
+use serde::{Deserialize, Serialize};
+
+use crate::args::CliArguments;
+use crate::placement::strategy::StrategyKind;
+use crate::tls::config::{KeyAlgorithm, SanMismatchPolicy};
+
+/// Names of the configuration files looked up in the data directory, in order.
+const DATA_DIR_FILES: &[&str] = &["scheduler.toml", "scheduler.yaml", "scheduler.yml"];
+
+/// The formats of the configuration file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    /// TOML, for the files with the `.toml` extension.
+    Toml,
+    /// YAML, for the files with the `.yaml` or `.yml` extension.
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// Detect the format of a configuration file from its extension.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the configuration file.
+    ///
+    /// # Errors
+    ///
+    /// * The extension is not supported.
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Ok(Self::Toml),
+            Some("yaml" | "yml") => Ok(Self::Yaml),
+            _ => bail!(
+                "Unsupported configuration file, the extension must be `.toml`, `.yaml` or `.yml`: {}",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// The settings of the configuration file, named like the command-line arguments with
+/// underscores instead of dashes. A setting missing from the file keeps the value given on the
+/// command line, by the environment or by default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    /// Directory used to store the configuration, only read from files given by `--config`.
+    pub data_dir: Option<String>,
+    /// Disable TLS for the gRPC server.
+    pub no_tls: Option<bool>,
+    /// Disable the automatic generation of the keypair and certificate for TLS.
+    pub no_tls_secret_generation: Option<bool>,
+    /// Extra DNS names of the generated TLS certificate.
+    pub tls_san_dns: Option<Vec<String>>,
+    /// IP addresses of the generated TLS certificate.
+    pub tls_san_ip: Option<Vec<IpAddr>>,
+    /// Algorithm of the generated TLS keypairs.
+    pub tls_key_algorithm: Option<KeyAlgorithm>,
+    /// Days during which the generated TLS certificate is valid.
+    pub tls_validity_days: Option<u64>,
+    /// What to do when the existing TLS certificate lacks some of the requested names.
+    pub tls_san_mismatch: Option<SanMismatchPolicy>,
+    /// Seconds between two checks of the TLS files for changes.
+    pub tls_reload_interval: Option<u64>,
+    /// Automatically renew the generated TLS certificate.
+    pub tls_auto_renew: Option<bool>,
+    /// Days before its expiration at which the generated TLS certificate is renewed.
+    pub tls_renew_before_days: Option<u64>,
+    /// The address to bind the gRPC server to.
+    pub grpc_bind_address: Option<String>,
+    /// The port to bind the gRPC server to.
+    pub grpc_bind_port: Option<u16>,
+    /// The strategy used to rank the nodes able to run a workload.
+    pub scheduling_strategy: Option<StrategyKind>,
+    /// Ratio by which the CPU reserved on a node may exceed its capacity.
+    pub cpu_overcommit_ratio: Option<f64>,
+    /// Ratio by which the memory reserved on a node may exceed its capacity.
+    pub memory_overcommit_ratio: Option<f64>,
+    /// Ratio by which the disk space reserved on a node may exceed its capacity.
+    pub disk_overcommit_ratio: Option<f64>,
+    /// Seconds without heartbeat after which a node agent is considered unhealthy.
+    pub agent_unhealthy_timeout: Option<u64>,
+    /// Seconds without heartbeat after which a node agent is removed from the cluster.
+    pub agent_eviction_timeout: Option<u64>,
+    /// IDs of the node agents allowed to join the cluster.
+    pub agent_id_allowlist: Option<Vec<String>>,
+    /// Regular expression that the other IDs of the node agents must match entirely.
+    pub agent_id_pattern: Option<String>,
+    /// Maximum number of node agents in the cluster.
+    pub max_agents: Option<usize>,
+    /// Number of node statuses each node agent may send per second, on average.
+    pub agent_status_rate: Option<f64>,
+    /// Number of node statuses a node agent may send at once after a quiet period.
+    pub agent_status_burst: Option<u32>,
+    /// Seconds before the first retry of a pending workload.
+    pub pending_initial_backoff: Option<u64>,
+    /// Longest delay between two retries of a pending workload, in seconds.
+    pub pending_max_backoff: Option<u64>,
+    /// Seconds after which a pending workload is abandoned.
+    pub pending_max_wait: Option<u64>,
+    /// Number of healthy node agents required before the scheduling service is serving.
+    pub min_ready_agents: Option<usize>,
+    /// Port of the plaintext listener only serving the gRPC health checking protocol.
+    pub health_bind_port: Option<u16>,
+    /// Port of the HTTP listener exporting Prometheus metrics.
+    pub metrics_bind_port: Option<u16>,
+    /// Seconds given to the in-flight calls and status streams to finish on shutdown.
+    pub shutdown_timeout: Option<u64>,
+}
+
+/// A configuration file, with the settings it holds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigFile {
+    /// The path to the file.
+    pub path: PathBuf,
+    /// Whether the file was found in the data directory, rather than given by `--config`.
+    pub in_data_dir: bool,
+    /// The settings of the file.
+    pub config: FileConfig,
+}
+
+impl ConfigFile {
+    /// Load the configuration file given by `--config`, or else the first configuration file
+    /// found in the data directory. Returns `None` if no file was given nor found.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path given by `--config`, if any.
+    /// * `data_dir` - The data directory of the scheduler.
+    ///
+    /// # Errors
+    ///
+    /// * The file could not be read, or is not a valid configuration.
+    pub fn load(path: Option<&Path>, data_dir: &Path) -> Result<Option<Self>> {
+        if let Some(path) = path {
+            let config = read_config(path)?.with_context(|| {
+                format!("The configuration file does not exist: {}", path.display())
+            })?;
+
+            return Ok(Some(Self {
+                path: path.to_path_buf(),
+                in_data_dir: false,
+                config,
+            }));
+        }
+
+        for name in DATA_DIR_FILES {
+            let path = data_dir.join(name);
+
+            if let Some(config) = read_config(&path)? {
+                return Ok(Some(Self {
+                    path,
+                    in_data_dir: true,
+                    config,
+                }));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Apply the settings of the file to the arguments, except the ones given on the command
+    /// line or by the environment.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - The arguments to update.
+    /// * `matches` - The matches the arguments were parsed from, telling where each value came
+    ///   from.
+    ///
+    /// # Errors
+    ///
+    /// * A file of the data directory moves the data directory elsewhere.
+    pub fn apply(self, args: &mut CliArguments, matches: &ArgMatches) -> Result<()> {
+        let config = self.config;
+
+        // Only the arguments left to their default value are taken from the file
+        let is_explicit = |id: &str| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+
+        macro_rules! layer {
+            ($($field:ident),* $(,)?) => {
+                $(
+                    if let Some(value) = config.$field {
+                        if !is_explicit(stringify!($field)) {
+                            args.$field = value.into();
+                        }
+                    }
+                )*
+            };
+        }
+
+        if let Some(data_dir) = &config.data_dir {
+            if self.in_data_dir && Path::new(data_dir) != Path::new(&args.data_dir) {
+                bail!(
+                    "The configuration file of the data directory cannot move the data directory to {}, use `--config` instead: {}",
+                    data_dir,
+                    self.path.display()
+                );
+            }
+        }
+
+        layer!(
+            data_dir,
+            no_tls,
+            no_tls_secret_generation,
+            tls_san_dns,
+            tls_san_ip,
+            tls_key_algorithm,
+            tls_validity_days,
+            tls_san_mismatch,
+            tls_reload_interval,
+            tls_auto_renew,
+            tls_renew_before_days,
+            grpc_bind_address,
+            grpc_bind_port,
+            scheduling_strategy,
+            cpu_overcommit_ratio,
+            memory_overcommit_ratio,
+            disk_overcommit_ratio,
+            agent_unhealthy_timeout,
+            agent_eviction_timeout,
+            agent_id_allowlist,
+            agent_id_pattern,
+            max_agents,
+            agent_status_rate,
+            agent_status_burst,
+            pending_initial_backoff,
+            pending_max_backoff,
+            pending_max_wait,
+            min_ready_agents,
+            health_bind_port,
+            metrics_bind_port,
+            shutdown_timeout,
+        );
+
+        Ok(())
+    }
+}
+
+impl From<&CliArguments> for FileConfig {
+    fn from(args: &CliArguments) -> Self {
+        Self {
+            data_dir: Some(args.data_dir.clone()),
+            no_tls: Some(args.no_tls),
+            no_tls_secret_generation: Some(args.no_tls_secret_generation),
+            tls_san_dns: Some(args.tls_san_dns.clone()),
+            tls_san_ip: Some(args.tls_san_ip.clone()),
+            tls_key_algorithm: Some(args.tls_key_algorithm),
+            tls_validity_days: Some(args.tls_validity_days),
+            tls_san_mismatch: Some(args.tls_san_mismatch),
+            tls_reload_interval: Some(args.tls_reload_interval),
+            tls_auto_renew: Some(args.tls_auto_renew),
+            tls_renew_before_days: Some(args.tls_renew_before_days),
+            grpc_bind_address: Some(args.grpc_bind_address.clone()),
+            grpc_bind_port: Some(args.grpc_bind_port),
+            scheduling_strategy: Some(args.scheduling_strategy),
+            cpu_overcommit_ratio: Some(args.cpu_overcommit_ratio),
+            memory_overcommit_ratio: Some(args.memory_overcommit_ratio),
+            disk_overcommit_ratio: Some(args.disk_overcommit_ratio),
+            agent_unhealthy_timeout: Some(args.agent_unhealthy_timeout),
+            agent_eviction_timeout: Some(args.agent_eviction_timeout),
+            agent_id_allowlist: Some(args.agent_id_allowlist.clone()),
+            agent_id_pattern: args.agent_id_pattern.clone(),
+            max_agents: args.max_agents,
+            agent_status_rate: args.agent_status_rate,
+            agent_status_burst: Some(args.agent_status_burst),
+            pending_initial_backoff: Some(args.pending_initial_backoff),
+            pending_max_backoff: Some(args.pending_max_backoff),
+            pending_max_wait: Some(args.pending_max_wait),
+            min_ready_agents: Some(args.min_ready_agents),
+            health_bind_port: args.health_bind_port,
+            metrics_bind_port: args.metrics_bind_port,
+            shutdown_timeout: Some(args.shutdown_timeout),
+        }
+    }
+}
+
+impl FileConfig {
+    /// Parse the settings of a configuration file.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The content of the file.
+    /// * `format` - The format of the file.
+    ///
+    /// # Errors
+    ///
+    /// * The content is not valid in this format, or holds unknown or invalid settings.
+    pub fn parse(content: &str, format: ConfigFormat) -> Result<Self> {
+        let config = match format {
+            ConfigFormat::Toml => toml::from_str(content)?,
+            // An empty YAML document holds no settings
+            ConfigFormat::Yaml if content.trim().is_empty() => Self::default(),
+            ConfigFormat::Yaml => serde_yaml::from_str(content)?,
+        };
+
+        Ok(config)
+    }
+
+    /// Serialize the settings in the TOML format.
+    ///
+    /// # Errors
+    ///
+    /// * A setting cannot be represented in TOML.
+    pub fn to_toml(&self) -> Result<String> {
+        toml::to_string(self).with_context(|| "Unable to serialize the configuration")
+    }
+}
+
+/// Read the settings of a configuration file, returning `None` if it does not exist.
+///
+/// # Arguments
+///
+/// * `path` - The path to the configuration file.
+///
+/// # Errors
+///
+/// * The file could not be read, or is not a valid configuration.
+fn read_config(path: &Path) -> Result<Option<FileConfig>> {
+    let format = ConfigFormat::from_path(path)?;
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| {
+                format!("Unable to read the configuration file: {}", path.display())
+            })
+        }
+    };
+
+    FileConfig::parse(&content, format)
+        .map(Some)
+        .with_context(|| format!("Invalid configuration file: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    /// Create an empty data directory for a test.
+    fn data_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "orka-scheduler-config-{}-{}",
+            name,
+            std::process::id()
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Load the arguments of a command line, with the configuration files.
+    fn load(dir: &Path, extra: &[&str]) -> Result<CliArguments> {
+        let mut command_line = vec!["orka-scheduler", "--data-dir", dir.to_str().unwrap()];
+        command_line.extend_from_slice(extra);
+
+        let matches = CliArguments::command()
+            .try_get_matches_from(command_line)
+            .unwrap();
+
+        CliArguments::from_matches(&matches)
+    }
+
+    #[test]
+    fn command_line_overrides_file_overrides_defaults() {
+        let dir = data_dir("precedence");
+
+        fs::write(
+            dir.join("scheduler.toml"),
+            "grpc_bind_port = 6000\nscheduling_strategy = \"bin-pack\"\ntls_san_dns = [\"a.example\"]\n",
+        )
+        .unwrap();
+
+        let args = load(&dir, &["--grpc-bind-port", "7000"]).unwrap();
+        assert_eq!(args.grpc_bind_port, 7000);
+        assert_eq!(args.scheduling_strategy, StrategyKind::BinPack);
+        assert_eq!(args.tls_san_dns, ["a.example"]);
+        assert_eq!(args.pending_max_wait, 300);
+
+        // The effective configuration can be loaded again
+        let printed = FileConfig::from(&args).to_toml().unwrap();
+        let reloaded = FileConfig::parse(&printed, ConfigFormat::Toml).unwrap();
+        assert_eq!(reloaded, FileConfig::from(&args));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reads_yaml_files_given_on_the_command_line() {
+        let dir = data_dir("yaml");
+        let path = dir.join("custom.yml");
+
+        fs::write(
+            &path,
+            "metrics_bind_port: 9100\ncpu_overcommit_ratio: 2.5\n",
+        )
+        .unwrap();
+
+        let args = load(&dir, &["--config", path.to_str().unwrap()]).unwrap();
+        assert_eq!(args.metrics_bind_port, Some(9100));
+        assert_eq!(args.cpu_overcommit_ratio, 2.5);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_invalid_configurations() {
+        let dir = data_dir("invalid");
+        let message = |extra: &[&str]| format!("{:#}", load(&dir, extra).unwrap_err());
+
+        fs::write(dir.join("scheduler.toml"), "grpc_bind_prot = 6000\n").unwrap();
+        assert!(message(&[]).contains("unknown field `grpc_bind_prot`"));
+
+        fs::write(dir.join("scheduler.toml"), "pending_initial_backoff = 0\n").unwrap();
+        assert!(message(&[]).contains("initial backoff"));
+
+        fs::write(dir.join("scheduler.toml"), "data_dir = \"/elsewhere\"\n").unwrap();
+        assert!(message(&[]).contains("cannot move the data directory"));
+
+        assert!(message(&["--config", "/missing.json"]).contains("Unsupported configuration"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
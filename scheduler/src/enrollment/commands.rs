@@ -0,0 +1,54 @@
+//! Subcommands managing the bootstrap tokens.
+
+use std::{path::Path, time::Duration};
+
+use anyhow::{bail, Result};
+use chrono::SecondsFormat;
+
+use crate::args::TokenCommand;
+
+use super::token::TokenStore;
+
+/// Run a bootstrap token subcommand, printing its result.
+///
+/// # Arguments
+///
+/// * `command` - The subcommand to run.
+/// * `data_dir` - The data directory of the scheduler, where the tokens are stored.
+///
+/// # Errors
+///
+/// * The tokens could not be read from or written to the disk.
+/// * The token to revoke does not exist.
+pub fn run_token_command(command: &TokenCommand, data_dir: &Path) -> Result<()> {
+    let store = TokenStore::new(data_dir);
+
+    match command {
+        TokenCommand::Create { ttl } => {
+            let token = store.create(Duration::from_secs(*ttl))?;
+            println!("{}", token);
+        }
+        TokenCommand::List => {
+            println!("{:<8} {:<21} {:<8} USED BY", "ID", "EXPIRES", "STATE");
+
+            for token in store.list()? {
+                println!(
+                    "{:<8} {:<21} {:<8} {}",
+                    token.id(),
+                    token
+                        .expires_at()
+                        .to_rfc3339_opts(SecondsFormat::Secs, true),
+                    format!("{:?}", token.state()).to_lowercase(),
+                    token.used_by().unwrap_or("-")
+                );
+            }
+        }
+        TokenCommand::Revoke { id } => {
+            if !store.revoke(id)? {
+                bail!("Bootstrap token not found: `{}`", id);
+            }
+        }
+    }
+
+    Ok(())
+}
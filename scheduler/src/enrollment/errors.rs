@@ -0,0 +1,31 @@
+//! Enrollment errors.
+
+use thiserror::Error;
+
+/// Enrollment error enum to have self-explanatory and compact errors.
+#[derive(Error, Debug)]
+pub enum EnrollmentError {
+    /// The bootstrap token is malformed or unknown.
+    #[error("Invalid bootstrap token")]
+    InvalidToken,
+
+    /// The bootstrap token has expired.
+    #[error("Bootstrap token expired: `{0}`")]
+    ExpiredToken(String),
+
+    /// The bootstrap token was already used to enroll an agent.
+    #[error("Bootstrap token already used: `{0}`")]
+    UsedToken(String),
+
+    /// The certificate signing request could not be parsed or signed.
+    #[error("Invalid certificate signing request: {0}")]
+    InvalidCsr(String),
+
+    /// The enrollment is not possible because TLS is disabled.
+    #[error("Enrollment is not available when TLS is disabled")]
+    TlsDisabled,
+
+    /// The bootstrap tokens could not be read from or written to the disk.
+    #[error("Unable to access the bootstrap tokens: {0}")]
+    Storage(String),
+}
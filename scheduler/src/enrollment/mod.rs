@@ -0,0 +1,5 @@
+//! Enrollment of new node agents with bootstrap tokens.
+
+pub mod commands;
+pub mod errors;
+pub mod token;
@@ -0,0 +1,454 @@
+//! Bootstrap tokens used by node agents to enroll into the cluster.
+
+use std::{
+    fs::{self, File},
+    io::{self, ErrorKind, Write},
+    os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use rand::{distributions::Uniform, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{event, Level};
+
+use super::errors::EnrollmentError;
+
+/// Characters used to generate token IDs and secrets.
+const TOKEN_CHARSET: &[u8] = b"abcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Length of the public part of a token.
+const TOKEN_ID_LENGTH: usize = 6;
+
+/// Length of the secret part of a token.
+const TOKEN_SECRET_LENGTH: usize = 16;
+
+/// A bootstrap token, as stored on the disk. Only a hash of the secret is kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapToken {
+    /// Public identifier of the token, used to list and revoke it.
+    id: String,
+    /// SHA-256 hash of the token secret.
+    secret_hash: String,
+    /// When the token was created.
+    created_at: DateTime<Utc>,
+    /// When the token stops being accepted.
+    expires_at: DateTime<Utc>,
+    /// The ID of the agent that enrolled with the token, if it was used.
+    used_by: Option<String>,
+}
+
+/// The state of a bootstrap token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenState {
+    /// The token can be used to enroll an agent.
+    Valid,
+    /// The token has expired.
+    Expired,
+    /// The token was already used to enroll an agent.
+    Used,
+}
+
+impl BootstrapToken {
+    /// Get the public identifier of the token.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Get when the token stops being accepted.
+    pub fn expires_at(&self) -> DateTime<Utc> {
+        self.expires_at
+    }
+
+    /// Get the ID of the agent that enrolled with the token, if it was used.
+    pub fn used_by(&self) -> Option<&str> {
+        self.used_by.as_deref()
+    }
+
+    /// Get the state of the token at the current time.
+    pub fn state(&self) -> TokenState {
+        if self.used_by.is_some() {
+            TokenState::Used
+        } else if self.expires_at <= Utc::now() {
+            TokenState::Expired
+        } else {
+            TokenState::Valid
+        }
+    }
+}
+
+/// The store of bootstrap tokens, kept in a file of the data directory.
+pub struct TokenStore {
+    /// File that contains the tokens.
+    path: PathBuf,
+
+    /// File locked while the tokens are read or modified, shared with the `token` command which
+    /// runs in another process.
+    lock_path: PathBuf,
+
+    /// Lock serializing the modifications of the tokens within the process.
+    lock: Mutex<()>,
+}
+
+/// The locks held while the tokens are read or modified. The file lock is released when the
+/// file is closed.
+struct StoreGuard<'a> {
+    /// The lock of the store within the process.
+    _guard: MutexGuard<'a, ()>,
+    /// The locked file, shared with the other processes.
+    _file: File,
+}
+
+impl TokenStore {
+    /// Create a token store.
+    ///
+    /// # Arguments
+    ///
+    /// * `data_dir` - The data directory of the scheduler.
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            path: data_dir.join("tokens.json"),
+            lock_path: data_dir.join("tokens.lock"),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Create a new bootstrap token, returning it in the `<id>.<secret>` form expected by
+    /// node agents. The secret cannot be retrieved afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `ttl` - How long the token is accepted for.
+    ///
+    /// # Errors
+    ///
+    /// * The tokens could not be read from or written to the disk.
+    pub fn create(&self, ttl: Duration) -> Result<String, EnrollmentError> {
+        let _guard = self.lock()?;
+        let mut tokens = self.load()?;
+
+        let id = random_string(TOKEN_ID_LENGTH);
+        let secret = random_string(TOKEN_SECRET_LENGTH);
+        let created_at = Utc::now();
+        let ttl = chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+
+        tokens.push(BootstrapToken {
+            id: id.clone(),
+            secret_hash: hash_secret(&secret),
+            created_at,
+            expires_at: created_at
+                .checked_add_signed(ttl)
+                .unwrap_or(DateTime::<Utc>::MAX_UTC),
+            used_by: None,
+        });
+
+        self.save(&tokens)?;
+
+        event!(Level::INFO, token_id = id, "Created bootstrap token");
+        Ok(format!("{}.{}", id, secret))
+    }
+
+    /// List the bootstrap tokens.
+    ///
+    /// # Errors
+    ///
+    /// * The tokens could not be read from the disk.
+    pub fn list(&self) -> Result<Vec<BootstrapToken>, EnrollmentError> {
+        let _guard = self.lock()?;
+
+        self.load()
+    }
+
+    /// Revoke a bootstrap token, returning whether it existed.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The public identifier of the token.
+    ///
+    /// # Errors
+    ///
+    /// * The tokens could not be read from or written to the disk.
+    pub fn revoke(&self, id: &str) -> Result<bool, EnrollmentError> {
+        let _guard = self.lock()?;
+        let mut tokens = self.load()?;
+
+        let count = tokens.len();
+        tokens.retain(|token| token.id != id);
+
+        if tokens.len() == count {
+            return Ok(false);
+        }
+
+        self.save(&tokens)?;
+
+        event!(Level::INFO, token_id = id, "Revoked bootstrap token");
+        Ok(true)
+    }
+
+    /// Use a bootstrap token to enroll an agent. A token can only be used once.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - The token presented by the agent, in the `<id>.<secret>` form.
+    /// * `agent_id` - The ID of the agent enrolling with the token.
+    ///
+    /// # Errors
+    ///
+    /// * The token is malformed or unknown.
+    /// * The token has expired or was already used.
+    /// * The tokens could not be read from or written to the disk.
+    pub fn consume(&self, token: &str, agent_id: &str) -> Result<(), EnrollmentError> {
+        let (id, secret) = token.split_once('.').ok_or(EnrollmentError::InvalidToken)?;
+
+        let _guard = self.lock()?;
+        let mut tokens = self.load()?;
+
+        let token = tokens
+            .iter_mut()
+            .find(|token| token.id == id && token.secret_hash == hash_secret(secret))
+            .ok_or(EnrollmentError::InvalidToken)?;
+
+        match token.state() {
+            TokenState::Valid => (),
+            TokenState::Expired => return Err(EnrollmentError::ExpiredToken(id.to_string())),
+            TokenState::Used => return Err(EnrollmentError::UsedToken(id.to_string())),
+        }
+
+        token.used_by = Some(agent_id.to_string());
+        self.save(&tokens)?;
+
+        event!(
+            Level::INFO,
+            token_id = id,
+            agent_id,
+            "Bootstrap token used to enroll agent"
+        );
+        Ok(())
+    }
+
+    /// Acquire the lock of the store, for the whole load, modify and save cycle.
+    ///
+    /// The tokens file is replaced on every save, so an advisory lock is taken on a sibling file
+    /// instead. A poisoned in-process lock is still usable, since the tokens are always read
+    /// back from the disk.
+    ///
+    /// # Errors
+    ///
+    /// * The lock file could not be opened or locked.
+    fn lock(&self) -> Result<StoreGuard<'_>, EnrollmentError> {
+        let guard = self
+            .lock
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .mode(0o600)
+            .open(&self.lock_path)
+            .map_err(|e| EnrollmentError::Storage(e.to_string()))?;
+
+        loop {
+            // SAFETY: the file descriptor is owned by `file`, which outlives the call
+            if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+                break;
+            }
+
+            let error = io::Error::last_os_error();
+            if error.kind() != ErrorKind::Interrupted {
+                return Err(EnrollmentError::Storage(error.to_string()));
+            }
+        }
+
+        Ok(StoreGuard {
+            _guard: guard,
+            _file: file,
+        })
+    }
+
+    /// Read the tokens from the disk. A missing file means that there are no tokens.
+    fn load(&self) -> Result<Vec<BootstrapToken>, EnrollmentError> {
+        let data = match fs::read_to_string(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(EnrollmentError::Storage(e.to_string())),
+        };
+
+        serde_json::from_str(&data).map_err(|e| EnrollmentError::Storage(e.to_string()))
+    }
+
+    /// Write the tokens to the disk, replacing the previous file atomically.
+    fn save(&self, tokens: &[BootstrapToken]) -> Result<(), EnrollmentError> {
+        let data = serde_json::to_vec_pretty(tokens)
+            .map_err(|e| EnrollmentError::Storage(e.to_string()))?;
+
+        let tmp_path = self.path.with_extension("json.tmp");
+
+        // The token hashes are secrets, so only the owner can read them
+        let mut file = File::options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)
+            .map_err(|e| EnrollmentError::Storage(e.to_string()))?;
+
+        file.write_all(&data)
+            .and_then(|_| file.sync_data())
+            .and_then(|_| fs::rename(&tmp_path, &self.path))
+            .map_err(|e| EnrollmentError::Storage(e.to_string()))
+    }
+}
+
+/// Generate a random string from the token character set.
+///
+/// # Arguments
+///
+/// * `length` - The length of the string.
+fn random_string(length: usize) -> String {
+    let range = Uniform::from(0..TOKEN_CHARSET.len());
+
+    rand::thread_rng()
+        .sample_iter(range)
+        .take(length)
+        .map(|i| TOKEN_CHARSET[i] as char)
+        .collect()
+}
+
+/// Hash the secret part of a token.
+///
+/// # Arguments
+///
+/// * `secret` - The secret to hash.
+fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    /// Create an empty data directory for a test.
+    fn data_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "orka-scheduler-tokens-{}-{}",
+            name,
+            std::process::id()
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn refuses_expired_tokens() {
+        let dir = data_dir("expiry");
+        let store = TokenStore::new(&dir);
+
+        let expired = store.create(Duration::ZERO).unwrap();
+        let valid = store.create(Duration::from_secs(3600)).unwrap();
+
+        assert!(matches!(
+            store.consume(&expired, "node-1"),
+            Err(EnrollmentError::ExpiredToken(_))
+        ));
+        assert!(store.consume(&valid, "node-1").is_ok());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn accepts_a_token_once() {
+        let dir = data_dir("single-use");
+        let store = TokenStore::new(&dir);
+
+        let token = store.create(Duration::from_secs(3600)).unwrap();
+        store.consume(&token, "node-1").unwrap();
+
+        assert!(matches!(
+            store.consume(&token, "node-2"),
+            Err(EnrollmentError::UsedToken(_))
+        ));
+
+        let tokens = store.list().unwrap();
+        assert_eq!(tokens[0].used_by(), Some("node-1"));
+        assert_eq!(tokens[0].state(), TokenState::Used);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn refuses_revoked_and_unknown_tokens() {
+        let dir = data_dir("revoke");
+        let store = TokenStore::new(&dir);
+
+        let token = store.create(Duration::from_secs(3600)).unwrap();
+        let (id, secret) = token.split_once('.').unwrap();
+
+        assert!(matches!(
+            store.consume(&format!("{}.wrong", id), "node-1"),
+            Err(EnrollmentError::InvalidToken)
+        ));
+        assert!(matches!(
+            store.consume(secret, "node-1"),
+            Err(EnrollmentError::InvalidToken)
+        ));
+
+        assert!(store.revoke(id).unwrap());
+        assert!(!store.revoke(id).unwrap());
+        assert!(matches!(
+            store.consume(&token, "node-1"),
+            Err(EnrollmentError::InvalidToken)
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn fails_on_a_malformed_tokens_file() {
+        let dir = data_dir("malformed");
+        let store = TokenStore::new(&dir);
+
+        fs::write(dir.join("tokens.json"), "{not json").unwrap();
+
+        assert!(matches!(store.list(), Err(EnrollmentError::Storage(_))));
+        assert!(matches!(
+            store.create(Duration::from_secs(3600)),
+            Err(EnrollmentError::Storage(_))
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn serializes_stores_sharing_a_file() {
+        let dir = data_dir("shared");
+
+        // Separate stores only share the file lock, like the server and the `token` command
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let store = TokenStore::new(&dir);
+                thread::spawn(move || {
+                    for _ in 0..10 {
+                        store.create(Duration::from_secs(3600)).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(TokenStore::new(&dir).list().unwrap().len(), 40);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
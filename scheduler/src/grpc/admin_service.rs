@@ -0,0 +1,364 @@
+//! Admin gRPC service, used by the operators of the cluster to manage its nodes.
+
+use std::sync::Arc;
+
+use orka_proto::scheduler_admin::{
+    admin_service_server::AdminService, DrainResponse, ListNodesRequest, Node, NodeList,
+    NodeRequest,
+};
+use tonic::{Request, Response, Result, Status};
+use tracing::{event, Level};
+
+use crate::managers::instance::manager::InstanceManager;
+use crate::managers::node_agent::manager::NodeAgentManager;
+use crate::managers::pending::queue::PendingQueue;
+use crate::tls::identity::verify_admin_identity;
+
+use super::controller_scheduling_service::ControllerSchedulingSvc;
+use super::conversions::to_admin_node;
+
+/// Implementation of the `AdminService` gRPC service.
+pub struct AdminSvc {
+    /// The shared instance of the node agent manager.
+    node_agent_manager: Arc<NodeAgentManager>,
+
+    /// The shared instance of the workload instance manager.
+    instance_manager: Arc<InstanceManager>,
+
+    /// The shared scheduling service, evicting the instances of the drained nodes.
+    scheduling_svc: Arc<ControllerSchedulingSvc>,
+
+    /// The shared queue of the workloads waiting for a node agent, woken up when a node is
+    /// uncordoned.
+    pending_queue: Arc<PendingQueue>,
+
+    /// Whether the operators must present a client certificate issued to the admin
+    /// organizational unit. Otherwise, only the requests from the loopback interface are
+    /// accepted.
+    verify_identity: bool,
+}
+
+impl AdminSvc {
+    /// Create a new `AdminService` gRPC service manager.
+    ///
+    /// # Arguments
+    ///
+    /// * `manager` - The shared instance of the node agent manager.
+    /// * `instance_manager` - The shared instance of the workload instance manager.
+    /// * `scheduling_svc` - The shared scheduling service.
+    /// * `pending_queue` - The shared queue of the workloads waiting for a node agent.
+    /// * `verify_identity` - Whether the operators must present an admin client certificate,
+    ///   which requires TLS. Otherwise, only local requests are accepted.
+    pub fn new(
+        manager: Arc<NodeAgentManager>,
+        instance_manager: Arc<InstanceManager>,
+        scheduling_svc: Arc<ControllerSchedulingSvc>,
+        pending_queue: Arc<PendingQueue>,
+        verify_identity: bool,
+    ) -> Self {
+        Self {
+            node_agent_manager: manager,
+            instance_manager,
+            scheduling_svc,
+            pending_queue,
+            verify_identity,
+        }
+    }
+
+    /// Check that a request was sent by an operator of the cluster. With TLS, the operator must
+    /// present a client certificate issued to the admin organizational unit. Without TLS, no
+    /// client can be authenticated, so only the requests from the loopback interface are
+    /// accepted.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The gRPC request received from the operator.
+    ///
+    /// # Errors
+    ///
+    /// * The client did not present an admin client certificate.
+    /// * TLS is disabled and the request does not come from the loopback interface.
+    #[allow(clippy::result_large_err)]
+    fn authorize<T>(&self, request: &Request<T>) -> Result<()> {
+        let remote_address = request.remote_addr();
+
+        if !self.verify_identity {
+            if remote_address.is_some_and(|address| address.ip().is_loopback()) {
+                return Ok(());
+            }
+
+            event!(
+                Level::WARN,
+                ?remote_address,
+                "Refusing remote admin request, only local ones are accepted without TLS"
+            );
+
+            return Err(Status::permission_denied(
+                "The admin service only accepts local requests when TLS is disabled",
+            ));
+        }
+
+        match verify_admin_identity(request) {
+            Ok(operator) => {
+                event!(Level::DEBUG, operator, "Accepted admin request");
+                Ok(())
+            }
+            Err(err) => {
+                event!(
+                    Level::WARN,
+                    ?remote_address,
+                    error = %err,
+                    "Refusing admin request without an admin client certificate"
+                );
+
+                Err(Status::from(err))
+            }
+        }
+    }
+
+    /// Describe a node of the cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the node agent.
+    ///
+    /// # Errors
+    ///
+    /// * The node agent is not in the cluster.
+    #[allow(clippy::result_large_err)]
+    fn node(&self, id: &str) -> Result<Node> {
+        let agent = self.node_agent_manager.agent(id)?;
+
+        Ok(to_admin_node(id, &agent, self.instance_ids(id)))
+    }
+
+    /// Get the IDs of the workload instances placed on a node agent.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    fn instance_ids(&self, agent_id: &str) -> Vec<String> {
+        self.instance_manager
+            .agent_instances(agent_id)
+            .into_iter()
+            .map(|(instance_id, _)| instance_id)
+            .collect()
+    }
+
+    /// Cordon or uncordon a node, returning its description.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the node agent.
+    /// * `cordoned` - Whether the node must be cordoned.
+    ///
+    /// # Errors
+    ///
+    /// * The node agent is not in the cluster.
+    #[allow(clippy::result_large_err)]
+    fn set_cordoned(&self, id: &str, cordoned: bool) -> Result<Node> {
+        let changed = self
+            .node_agent_manager
+            .set_cordoned(id, cordoned)
+            .map_err(|err| {
+                event!(
+                    Level::WARN,
+                    agent_id = id,
+                    error = %err,
+                    "Unable to change whether workloads can be placed on the node"
+                );
+
+                Status::from(err)
+            })?;
+
+        // The pending workloads may fit on the uncordoned node
+        if changed && !cordoned {
+            self.pending_queue.wake();
+        }
+
+        self.node(id)
+    }
+}
+
+#[tonic::async_trait]
+impl AdminService for AdminSvc {
+    /// Called by operators to list the nodes of the cluster.
+    async fn list_nodes(&self, request: Request<ListNodesRequest>) -> Result<Response<NodeList>> {
+        self.authorize(&request)?;
+
+        let agents: Vec<_> = self
+            .node_agent_manager
+            .agents()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        let mut nodes: Vec<_> = agents
+            .iter()
+            .map(|(id, agent)| to_admin_node(id, agent, self.instance_ids(id)))
+            .collect();
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(Response::new(NodeList { nodes }))
+    }
+
+    /// Called by operators to stop placing workloads on a node, keeping its instances running.
+    async fn cordon(&self, request: Request<NodeRequest>) -> Result<Response<Node>> {
+        self.authorize(&request)?;
+
+        let node = self.set_cordoned(&request.into_inner().id, true)?;
+
+        Ok(Response::new(node))
+    }
+
+    /// Called by operators to place workloads on a cordoned node again.
+    async fn uncordon(&self, request: Request<NodeRequest>) -> Result<Response<Node>> {
+        self.authorize(&request)?;
+
+        let node = self.set_cordoned(&request.into_inner().id, false)?;
+
+        Ok(Response::new(node))
+    }
+
+    /// Called by operators to move the work off a node: the node is cordoned and its instances
+    /// are stopped, the controller being asked to schedule them again on other nodes.
+    async fn drain(&self, request: Request<NodeRequest>) -> Result<Response<DrainResponse>> {
+        self.authorize(&request)?;
+
+        let id = request.into_inner().id;
+
+        let evicted_instance_ids =
+            self.scheduling_svc
+                .drain_agent(&id)
+                .await
+                .inspect_err(|err| {
+                    event!(
+                        Level::WARN,
+                        agent_id = id,
+                        error = err.message(),
+                        "Unable to drain the node"
+                    );
+                })?;
+
+        Ok(Response::new(DrainResponse {
+            node: Some(self.node(&id)?),
+            evicted_instance_ids,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::{TcpListener, TcpStream};
+    use tonic::{transport::server::Connected, Code};
+
+    use super::*;
+    use crate::grpc::shutdown::Drain;
+    use crate::managers::events::broadcaster::ClusterEvents;
+    use crate::managers::node_agent::client_pool::AgentClientPool;
+    use crate::managers::node_agent::properties::NodeProperties;
+    use crate::managers::pending::queue::QueueOptions;
+    use crate::metrics::registry::SchedulerMetrics;
+    use crate::placement::placer::Placer;
+    use crate::placement::reservation::OvercommitRatios;
+    use crate::placement::strategy::StrategyKind;
+
+    /// Create an admin service for a cluster holding the agent `node-1`.
+    ///
+    /// # Arguments
+    ///
+    /// * `verify_identity` - Whether the operators must present an admin client certificate.
+    fn admin_svc(verify_identity: bool) -> AdminSvc {
+        let manager = Arc::new(NodeAgentManager::new());
+        manager
+            .add_agent("node-1", "", NodeProperties::default())
+            .unwrap();
+
+        let instance_manager = Arc::new(InstanceManager::new());
+        let pending_queue = Arc::new(PendingQueue::new(QueueOptions::default()));
+        let scheduling_svc = Arc::new(ControllerSchedulingSvc::new(
+            Arc::clone(&manager),
+            Arc::new(AgentClientPool::new()),
+            Arc::clone(&instance_manager),
+            Placer::new(StrategyKind::Spread.build(), OvercommitRatios::default()),
+            Arc::clone(&pending_queue),
+            Arc::new(SchedulerMetrics::new().unwrap()),
+            Arc::new(Drain::new()),
+            Arc::new(ClusterEvents::new()),
+        ));
+
+        AdminSvc::new(
+            manager,
+            instance_manager,
+            scheduling_svc,
+            pending_queue,
+            verify_identity,
+        )
+    }
+
+    /// Build a request as received by the gRPC server over a plaintext connection from the
+    /// loopback interface.
+    async fn local_request<T>(message: T) -> Request<T> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let _client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        let mut request = Request::new(message);
+        request.extensions_mut().insert(stream.connect_info());
+        request
+    }
+
+    /// Build a request targeting the agent `node-1`.
+    fn node_request() -> NodeRequest {
+        NodeRequest {
+            id: "node-1".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_an_unauthenticated_drain() {
+        let svc = admin_svc(true);
+
+        let status = svc.drain(Request::new(node_request())).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        // Being local is not enough when operators can be authenticated
+        let status = svc
+            .drain(local_request(node_request()).await)
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        assert!(!svc
+            .node_agent_manager
+            .agent("node-1")
+            .unwrap()
+            .is_cordoned());
+    }
+
+    #[tokio::test]
+    async fn accepts_local_requests_only_without_tls() {
+        let svc = admin_svc(false);
+
+        let status = svc.drain(Request::new(node_request())).await.unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        assert!(!svc
+            .node_agent_manager
+            .agent("node-1")
+            .unwrap()
+            .is_cordoned());
+
+        let node = svc
+            .cordon(local_request(node_request()).await)
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(node.id, "node-1");
+        assert!(svc
+            .node_agent_manager
+            .agent("node-1")
+            .unwrap()
+            .is_cordoned());
+    }
+}
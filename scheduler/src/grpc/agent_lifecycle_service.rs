@@ -1,17 +1,74 @@
 //! Lifecycle gRPC service for the Orka node agents.
 
+use crate::admission::policy::AdmissionPolicy;
+use crate::enrollment::errors::EnrollmentError;
+use crate::enrollment::token::TokenStore;
+use crate::grpc::controller_scheduling_service::StatusSenders;
+use crate::grpc::conversions::to_node_properties;
+use crate::grpc::shutdown::Drain;
+use crate::managers::events::broadcaster::{ClusterEvent, ClusterEvents};
+use crate::managers::instance::manager::InstanceManager;
+use crate::managers::node_agent::client_pool::AgentClientPool;
 use crate::managers::node_agent::manager::NodeAgentManager;
+use crate::managers::pending::queue::PendingQueue;
+use crate::metrics::registry::{LeaveCause, SchedulerMetrics};
+use crate::tls::identity::verify_peer_identity;
+use crate::tls::issuer::CertificateIssuer;
 use orka_proto::scheduler_agent::{
     lifecycle_service_server::LifecycleService, ConnectionRequest, DisconnectionNotice, Empty,
+    EnrollmentRequest, EnrollmentResponse,
 };
-use std::sync::{Arc, Mutex};
+use orka_proto::scheduler_controller::{
+    workload_status::{
+        status::{Reason, StatusCode},
+        Status as ControllerStatus,
+    },
+    WorkloadStatus,
+};
+use std::sync::Arc;
 use tonic::{Request, Response, Result, Status};
 use tracing::{event, Level};
 
 /// Implementation of the `LifecycleService` gRPC service.
 pub struct AgentLifecycleSvc {
     /// The shared instance of the node agent manager.
-    node_agent_manager: Arc<Mutex<NodeAgentManager>>,
+    node_agent_manager: Arc<NodeAgentManager>,
+
+    /// The shared pool of clients for the node agents.
+    agent_client_pool: Arc<AgentClientPool>,
+
+    /// The shared instance of the workload instance manager, releasing the instances of the
+    /// agents leaving the cluster.
+    instance_manager: Arc<InstanceManager>,
+
+    /// The senders of the status streams returned to the controller, told when the instances of
+    /// a leaving agent are lost.
+    status_senders: Arc<StatusSenders>,
+
+    /// The shared queue of the workloads waiting for a node agent, woken up when a leaving agent
+    /// releases resources.
+    pending_queue: Arc<PendingQueue>,
+
+    /// The store of bootstrap tokens accepted for enrollment.
+    token_store: Arc<TokenStore>,
+
+    /// The issuer of agent certificates, if TLS is enabled.
+    certificate_issuer: Option<Arc<CertificateIssuer>>,
+
+    /// Whether agents must present a client certificate issued to their ID.
+    verify_identity: bool,
+
+    /// The metrics of the scheduler, counting the agents joining and leaving the cluster.
+    metrics: Arc<SchedulerMetrics>,
+
+    /// The drain of the scheduler, refusing new agents once it started.
+    drain: Arc<Drain>,
+
+    /// The broadcaster of the changes of the cluster, told when agents join and leave.
+    cluster_events: Arc<ClusterEvents>,
+
+    /// The admission policy, restricting the IDs and the number of the agents.
+    admission: AdmissionPolicy,
 }
 
 impl AgentLifecycleSvc {
@@ -20,41 +77,203 @@ impl AgentLifecycleSvc {
     /// # Arguments
     ///
     /// * `manager` - The shared instance of the node agent manager.
-    pub fn new(manager: Arc<Mutex<NodeAgentManager>>) -> Self {
+    /// * `client_pool` - The shared pool of clients for the node agents.
+    /// * `instance_manager` - The shared instance of the workload instance manager.
+    /// * `status_senders` - The senders of the status streams returned to the controller.
+    /// * `pending_queue` - The shared queue of the workloads waiting for a node agent.
+    /// * `token_store` - The store of bootstrap tokens accepted for enrollment.
+    /// * `certificate_issuer` - The issuer of agent certificates, if TLS is enabled.
+    /// * `verify_identity` - Whether agents must present a client certificate issued to their ID.
+    /// * `metrics` - The metrics of the scheduler.
+    /// * `drain` - The drain of the scheduler.
+    /// * `cluster_events` - The broadcaster of the changes of the cluster.
+    /// * `admission` - The admission policy of the agents.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        manager: Arc<NodeAgentManager>,
+        client_pool: Arc<AgentClientPool>,
+        instance_manager: Arc<InstanceManager>,
+        status_senders: Arc<StatusSenders>,
+        pending_queue: Arc<PendingQueue>,
+        token_store: Arc<TokenStore>,
+        certificate_issuer: Option<Arc<CertificateIssuer>>,
+        verify_identity: bool,
+        metrics: Arc<SchedulerMetrics>,
+        drain: Arc<Drain>,
+        cluster_events: Arc<ClusterEvents>,
+        admission: AdmissionPolicy,
+    ) -> Self {
         Self {
             node_agent_manager: manager,
+            agent_client_pool: client_pool,
+            instance_manager,
+            status_senders,
+            pending_queue,
+            token_store,
+            certificate_issuer,
+            verify_identity,
+            metrics,
+            drain,
+            cluster_events,
+            admission,
         }
     }
+
+    /// Check that an agent may join the cluster, or enroll into it. The maximum number of agents
+    /// is only enforced when the agent is added to the cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID claimed by the agent.
+    ///
+    /// # Errors
+    ///
+    /// * The ID of the agent is not accepted by the admission policy.
+    #[allow(clippy::result_large_err)]
+    fn admit(&self, agent_id: &str) -> Result<(), Status> {
+        self.admission.check_id(agent_id).map_err(|err| {
+            event!(
+                Level::WARN,
+                agent_id,
+                error = %err,
+                "Refusing node agent by admission policy"
+            );
+
+            Status::from(err)
+        })
+    }
+
+    /// Check that the client certificate of an agent was issued to the ID it claims, if identity
+    /// verification is enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The gRPC request received from the agent.
+    /// * `agent_id` - The ID claimed by the agent.
+    ///
+    /// # Errors
+    ///
+    /// * The agent did not present a valid client certificate issued to `agent_id`.
+    #[allow(clippy::result_large_err)]
+    fn verify_identity<T>(&self, request: &Request<T>, agent_id: &str) -> Result<(), Status> {
+        if !self.verify_identity {
+            return Ok(());
+        }
+
+        verify_peer_identity(request, agent_id).map_err(|err| {
+            event!(
+                Level::WARN,
+                agent_id,
+                error = %err,
+                "Node agent failed identity verification"
+            );
+
+            Status::from(err)
+        })
+    }
+
+    /// Forget the workload instances placed on an agent that left the cluster, releasing their
+    /// resources and telling the controller they are lost.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the agent.
+    fn release_instances(&self, agent_id: &str) {
+        let instances = self.instance_manager.remove_agent_instances(agent_id);
+
+        if instances.is_empty() {
+            return;
+        }
+
+        event!(
+            Level::WARN,
+            agent_id,
+            ?instances,
+            "Forgot the workload instances of a leaving agent"
+        );
+
+        for instance_id in instances {
+            let sender = self
+                .status_senders
+                .get(&instance_id)
+                .map(|sender| sender.clone());
+
+            // Never wait for a slow controller while releasing the other instances
+            if let Some(sender) = sender {
+                let _ = sender.try_send(Ok(WorkloadStatus {
+                    instance_id: instance_id.clone(),
+                    status: Some(ControllerStatus {
+                        code: StatusCode::Terminated as u32,
+                        message: Some(format!("The node `{}` left the cluster", agent_id)),
+                        reason: Reason::Unspecified as i32,
+                    }),
+                    resource_usage: None,
+                }));
+            }
+
+            self.cluster_events.publish(ClusterEvent::InstanceLost {
+                instance_id,
+                node_id: agent_id.to_string(),
+            });
+        }
+
+        self.pending_queue.wake();
+    }
 }
 
 #[tonic::async_trait]
 impl LifecycleService for AgentLifecycleSvc {
-    /// Called by node agents when they request to join the cluster.
-    async fn join_cluster(
+    /// Called by new node agents to obtain a client certificate in exchange for a bootstrap
+    /// token and a certificate signing request.
+    async fn enroll(
         &self,
-        request: Request<ConnectionRequest>,
-    ) -> Result<Response<Empty>, Status> {
-        let agent_id = request.into_inner().id;
+        request: Request<EnrollmentRequest>,
+    ) -> Result<Response<EnrollmentResponse>> {
+        let EnrollmentRequest {
+            id: agent_id,
+            token,
+            csr,
+        } = request.into_inner();
 
-        let mut manager = self.node_agent_manager.lock().map_err(|err| {
+        self.admit(&agent_id)?;
+
+        // Signing the certificate and locking the token store would block the runtime
+        let certificate_issuer = self.certificate_issuer.clone();
+        let token_store = Arc::clone(&self.token_store);
+        let enrolled_id = agent_id.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            enroll_agent(
+                certificate_issuer.as_deref(),
+                &token_store,
+                &enrolled_id,
+                &token,
+                &csr,
+            )
+        })
+        .await
+        .map_err(|err| {
             event!(
-                Level::WARN,
+                Level::ERROR,
                 agent_id,
                 error = %err,
-                "Failed to acquire node manager, refusing registration for agent"
+                "The enrollment of the node agent failed"
             );
 
-            Status::internal("Failed to register agent")
+            Status::internal("Failed to enroll the node agent")
         })?;
 
-        match manager.add_agent(&agent_id) {
-            Ok(_) => Ok(Response::new(Empty {})),
+        match result {
+            Ok(response) => {
+                event!(Level::INFO, agent_id, "Enrolled new node agent");
+                Ok(Response::new(response))
+            }
             Err(err) => {
                 event!(
                     Level::WARN,
                     agent_id,
                     error = %err,
-                    "Unable to accept new node agent into the cluster"
+                    "Refusing to enroll node agent"
                 );
 
                 Err(Status::from(err))
@@ -62,29 +281,250 @@ impl LifecycleService for AgentLifecycleSvc {
         }
     }
 
+    /// Called by node agents when they request to join the cluster.
+    async fn join_cluster(
+        &self,
+        request: Request<ConnectionRequest>,
+    ) -> Result<Response<Empty>, Status> {
+        self.drain.check_accepting()?;
+        self.verify_identity(&request, &request.get_ref().id)?;
+
+        let ConnectionRequest {
+            id: agent_id,
+            address,
+            labels,
+            taints,
+        } = request.into_inner();
+
+        self.admit(&agent_id)?;
+
+        let endpoint = AgentClientPool::parse_endpoint(&address).map_err(|err| {
+            event!(
+                Level::WARN,
+                agent_id,
+                error = %err,
+                "Node agent advertised an invalid address"
+            );
+
+            Status::from(err)
+        })?;
+
+        let properties = to_node_properties(labels, taints).map_err(|err| {
+            event!(
+                Level::WARN,
+                agent_id,
+                error = %err,
+                "Node agent declared invalid labels or taints"
+            );
+
+            Status::from(err)
+        })?;
+
+        if let Err(err) = self.node_agent_manager.add_agent_within(
+            &agent_id,
+            &address,
+            properties,
+            self.admission.max_agents(),
+        ) {
+            event!(
+                Level::WARN,
+                agent_id,
+                error = %err,
+                "Unable to accept new node agent into the cluster"
+            );
+
+            return Err(Status::from(err));
+        }
+
+        self.agent_client_pool.insert(&agent_id, endpoint);
+        self.metrics.record_join();
+        self.cluster_events.publish(ClusterEvent::NodeJoined {
+            node_id: agent_id,
+            address,
+        });
+
+        Ok(Response::new(Empty {}))
+    }
+
     /// Called by node agents when they request to gracefully leave the cluster.
     async fn leave_cluster(
         &self,
         request: Request<DisconnectionNotice>,
     ) -> Result<Response<Empty>> {
+        self.verify_identity(&request, &request.get_ref().id)?;
+
         let agent_id = request.into_inner().id;
 
-        match self.node_agent_manager.lock() {
-            Ok(mut manager) => {
-                manager.remove_agent(&agent_id);
-            }
-            Err(err) => {
-                event!(
-                    Level::WARN,
-                    agent_id,
-                    error = %err,
-                    "Failed to acquire node manager, could not remove agent"
-                );
-            }
+        if self.node_agent_manager.remove_agent(&agent_id).is_some() {
+            self.metrics.record_leave(LeaveCause::Notice);
+            self.cluster_events.publish(ClusterEvent::NodeLeft {
+                node_id: agent_id.clone(),
+                cause: LeaveCause::Notice,
+            });
+            self.release_instances(&agent_id);
         }
+        self.agent_client_pool.remove(&agent_id);
 
         // We are receiving a notice and are expected not to respond
         // so we always send an empty response
         Ok(Response::new(Empty {}))
     }
 }
+
+/// Issue a client certificate to a new node agent in exchange for a bootstrap token.
+///
+/// The certificate signing request is signed before using the token, so that a malformed request
+/// does not waste it. The certificate is only returned if the token is accepted.
+///
+/// # Arguments
+///
+/// * `certificate_issuer` - The issuer of agent certificates, if TLS is enabled.
+/// * `token_store` - The store of bootstrap tokens accepted for enrollment.
+/// * `agent_id` - The ID of the agent.
+/// * `token` - The bootstrap token presented by the agent.
+/// * `csr` - The certificate signing request of the agent, in PEM format.
+///
+/// # Errors
+///
+/// * TLS is disabled, or no agent ID was provided.
+/// * The certificate signing request is invalid.
+/// * The token was not accepted, or the tokens could not be accessed.
+fn enroll_agent(
+    certificate_issuer: Option<&CertificateIssuer>,
+    token_store: &TokenStore,
+    agent_id: &str,
+    token: &str,
+    csr: &str,
+) -> Result<EnrollmentResponse, EnrollmentError> {
+    let issuer = certificate_issuer.ok_or(EnrollmentError::TlsDisabled)?;
+
+    if agent_id.is_empty() {
+        return Err(EnrollmentError::InvalidCsr(
+            "No agent ID was provided".to_string(),
+        ));
+    }
+
+    let certificate = issuer.issue_agent_certificate(agent_id, csr)?;
+    token_store.consume(token, agent_id)?;
+
+    Ok(EnrollmentResponse {
+        certificate,
+        ca_certificate: issuer.ca_cert_data().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use orka_proto::scheduler_controller::Workload;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::managers::instance::errors::InstanceError;
+    use crate::managers::node_agent::properties::NodeProperties;
+    use crate::managers::pending::queue::QueueOptions;
+    use crate::metrics::registry::SchedulerMetrics;
+    use crate::placement::requirements::WorkloadRequirements;
+
+    #[tokio::test]
+    async fn releases_the_instances_of_a_leaving_agent() {
+        let manager = Arc::new(NodeAgentManager::new());
+        let instance_manager = Arc::new(InstanceManager::new());
+        let status_senders = Arc::new(StatusSenders::new());
+        let pending_queue = Arc::new(PendingQueue::new(QueueOptions::default()));
+        let cluster_events = Arc::new(ClusterEvents::new());
+        let data_dir =
+            std::env::temp_dir().join(format!("orka-scheduler-lifecycle-{}", std::process::id()));
+
+        let svc = AgentLifecycleSvc::new(
+            Arc::clone(&manager),
+            Arc::new(AgentClientPool::new()),
+            Arc::clone(&instance_manager),
+            Arc::clone(&status_senders),
+            Arc::clone(&pending_queue),
+            Arc::new(TokenStore::new(&data_dir)),
+            None,
+            false,
+            Arc::new(SchedulerMetrics::new().unwrap()),
+            Arc::new(Drain::new()),
+            Arc::clone(&cluster_events),
+            AdmissionPolicy::default(),
+        );
+
+        manager
+            .add_agent("node-1", "", NodeProperties::default())
+            .unwrap();
+
+        let resources = WorkloadRequirements::from_limits(10, 128, 0);
+        for (instance_id, agent_id) in [("a", "node-1"), ("b", "node-1"), ("c", "node-2")] {
+            instance_manager
+                .add_instance(instance_id, agent_id, resources.clone(), 0)
+                .unwrap();
+        }
+
+        let (sender, mut statuses) = mpsc::channel(1);
+        status_senders.insert("a".to_string(), sender.clone());
+
+        // A workload waits for its backoff before being retried, unless the queue is woken up
+        let now = Instant::now();
+        pending_queue
+            .push(
+                Workload {
+                    instance_id: "pending".to_string(),
+                    ..Default::default()
+                },
+                sender,
+                String::new(),
+                now,
+            )
+            .unwrap();
+
+        let mut events = cluster_events.subscribe();
+
+        svc.leave_cluster(Request::new(DisconnectionNotice {
+            id: "node-1".to_string(),
+        }))
+        .await
+        .unwrap();
+
+        assert!(!manager.contains("node-1"));
+        assert_eq!(
+            instance_manager.reserved("node-1"),
+            WorkloadRequirements::default()
+        );
+        assert!(matches!(
+            instance_manager.placement("a"),
+            Err(InstanceError::NotFound(_))
+        ));
+        assert_eq!(
+            instance_manager.placement("c").unwrap().agent_id(),
+            "node-2"
+        );
+
+        let status = statuses.try_recv().unwrap().unwrap();
+        assert_eq!(status.instance_id, "a");
+        assert_eq!(status.status.unwrap().code, StatusCode::Terminated as u32);
+
+        assert_eq!(
+            events.try_recv().unwrap(),
+            ClusterEvent::NodeLeft {
+                node_id: "node-1".to_string(),
+                cause: LeaveCause::Notice,
+            }
+        );
+        let mut lost: Vec<_> = (0..2)
+            .map(|_| match events.try_recv().unwrap() {
+                ClusterEvent::InstanceLost {
+                    instance_id,
+                    node_id,
+                } if node_id == "node-1" => instance_id,
+                event => panic!("unexpected event {:?}", event),
+            })
+            .collect();
+        lost.sort();
+        assert_eq!(lost, ["a", "b"]);
+
+        assert_eq!(pending_queue.take_due(Instant::now()).len(), 1);
+    }
+}
@@ -1,11 +1,31 @@
 //! Status update gRPC service for the Orka node agents.
 
+use crate::admission::rate_limit::AgentRateLimiter;
+use crate::grpc::controller_scheduling_service::StatusSenders;
+use crate::grpc::conversions::{
+    to_controller_instance_status, to_node_metrics, to_reported_instance,
+};
+use crate::managers::events::broadcaster::{ClusterEvent, ClusterEvents};
+use crate::managers::instance::manager::InstanceManager;
 use crate::managers::node_agent::manager::NodeAgentManager;
-use crate::managers::node_agent::metrics::{NodeCpu, NodeMemory};
+use crate::managers::pending::queue::PendingQueue;
+use crate::metrics::registry::{SchedulerMetrics, StatusStream};
+use crate::tls::errors::IdentityError;
+use crate::tls::identity::peer_common_name;
 use orka_proto::scheduler_agent::{
-    status_update_service_server::StatusUpdateService, Empty, NodeStatus,
+    node_status::InstanceStatus, status_update_service_server::StatusUpdateService, Empty,
+    NodeStatus,
+};
+use orka_proto::scheduler_controller::{
+    workload_status::{
+        status::{Reason, StatusCode},
+        Status as ControllerStatus,
+    },
+    WorkloadStatus,
 };
-use std::sync::{Arc, Mutex};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Instant;
 use tokio_stream::StreamExt;
 use tonic::{Request, Response, Result, Status, Streaming};
 use tracing::{event, Level};
@@ -13,7 +33,32 @@ use tracing::{event, Level};
 /// Implementation of the `StatusUpdateService` gRPC service.
 pub struct AgentStatusUpdateSvc {
     /// The shared instance of the node agent manager.
-    node_agent_manager: Arc<Mutex<NodeAgentManager>>,
+    node_agent_manager: Arc<NodeAgentManager>,
+
+    /// The shared instance of the workload instance manager, reconciled with the instances
+    /// listed by the agents.
+    instance_manager: Arc<InstanceManager>,
+
+    /// The senders of the status streams returned to the controller, told when the instances
+    /// listed by the agents change.
+    status_senders: Arc<StatusSenders>,
+
+    /// The shared queue of the workloads waiting for a node agent, woken up when an agent becomes
+    /// able to receive workloads.
+    pending_queue: Arc<PendingQueue>,
+
+    /// Whether agents must present a client certificate issued to their ID.
+    verify_identity: bool,
+
+    /// The metrics of the scheduler, counting the errors of the status streams.
+    metrics: Arc<SchedulerMetrics>,
+
+    /// The broadcaster of the changes of the cluster, told when an agent recovers or loses
+    /// instances.
+    cluster_events: Arc<ClusterEvents>,
+
+    /// The rate limiter of the statuses sent by each agent, if they are limited.
+    rate_limiter: Option<AgentRateLimiter>,
 }
 
 impl AgentStatusUpdateSvc {
@@ -22,42 +67,224 @@ impl AgentStatusUpdateSvc {
     /// # Arguments
     ///
     /// * `manager` - The shared instance of the node agent manager.
-    pub fn new(manager: Arc<Mutex<NodeAgentManager>>) -> Self {
+    /// * `instance_manager` - The shared instance of the workload instance manager.
+    /// * `status_senders` - The senders of the status streams returned to the controller.
+    /// * `pending_queue` - The shared queue of the workloads waiting for a node agent.
+    /// * `verify_identity` - Whether agents must present a client certificate issued to their ID.
+    /// * `metrics` - The metrics of the scheduler.
+    /// * `cluster_events` - The broadcaster of the changes of the cluster.
+    /// * `rate_limiter` - The rate limiter of the statuses sent by each agent, if any.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        manager: Arc<NodeAgentManager>,
+        instance_manager: Arc<InstanceManager>,
+        status_senders: Arc<StatusSenders>,
+        pending_queue: Arc<PendingQueue>,
+        verify_identity: bool,
+        metrics: Arc<SchedulerMetrics>,
+        cluster_events: Arc<ClusterEvents>,
+        rate_limiter: Option<AgentRateLimiter>,
+    ) -> Self {
         Self {
             node_agent_manager: manager,
+            instance_manager,
+            status_senders,
+            pending_queue,
+            verify_identity,
+            metrics,
+            cluster_events,
+            rate_limiter,
         }
     }
-}
 
-#[tonic::async_trait]
-impl StatusUpdateService for AgentStatusUpdateSvc {
-    /// Called by node agents to start streaming status information about the node.
-    async fn update_node_status(
-        &self,
-        request: Request<Streaming<NodeStatus>>,
-    ) -> Result<Response<Empty>> {
-        let mut stream = request.into_inner();
+    /// Reconcile the instance registry with the instances listed by a node agent, relaying the
+    /// changes to the status streams of the controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    /// * `instances` - The complete list of the instances of the node agent.
+    fn reconcile_instances(&self, agent_id: &str, instances: Vec<InstanceStatus>) {
+        let reported = instances.iter().map(to_reported_instance).collect();
+        let reconciliation = self
+            .instance_manager
+            .reconcile(agent_id, reported, Instant::now());
 
+        let mut instances: HashMap<String, InstanceStatus> = instances
+            .into_iter()
+            .map(|instance| (instance.id.clone(), instance))
+            .collect();
+
+        for instance_id in reconciliation.changed {
+            if let Some(instance) = instances.remove(&instance_id) {
+                self.send_status(to_controller_instance_status(instance));
+            }
+        }
+
+        for instance_id in reconciliation.adopted {
+            event!(
+                Level::WARN,
+                instance_id,
+                agent_id,
+                "Adopted an orphan workload instance run by the node agent"
+            );
+        }
+
+        for instance_id in reconciliation.lost {
+            event!(
+                Level::WARN,
+                instance_id,
+                agent_id,
+                "Node agent does not run the workload instance anymore, forgetting it"
+            );
+
+            self.send_status(WorkloadStatus {
+                instance_id: instance_id.clone(),
+                status: Some(ControllerStatus {
+                    code: StatusCode::Terminated as u32,
+                    message: Some(format!(
+                        "The workload instance is not running on node `{}` anymore",
+                        agent_id
+                    )),
+                    reason: Reason::Unspecified as i32,
+                }),
+                resource_usage: None,
+            });
+            self.cluster_events.publish(ClusterEvent::InstanceLost {
+                instance_id,
+                node_id: agent_id.to_string(),
+            });
+        }
+
+        if reconciliation.released {
+            self.pending_queue.wake();
+        }
+    }
+
+    /// Send a status to the stream of its instance returned to the controller, if it is still
+    /// open.
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The status of the instance.
+    fn send_status(&self, status: WorkloadStatus) {
+        let sender = self
+            .status_senders
+            .get(&status.instance_id)
+            .map(|sender| sender.clone());
+
+        // Never wait for a slow controller while processing the node status
+        if let Some(sender) = sender {
+            let _ = sender.try_send(Ok(status));
+        }
+    }
+
+    /// Check that a node agent does not send statuses faster than allowed. The agents that are
+    /// not in the cluster are not limited, their statuses are refused anyway.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    /// * `agent_ids` - The IDs of the limited agents of the stream, updated with the agent.
+    ///
+    /// # Errors
+    ///
+    /// * The agent exceeded its rate limit.
+    #[allow(clippy::result_large_err)]
+    fn check_rate(&self, agent_id: &str, agent_ids: &mut HashSet<String>) -> Result<()> {
+        let Some(rate_limiter) = &self.rate_limiter else {
+            return Ok(());
+        };
+
+        if !self.node_agent_manager.contains(agent_id) {
+            return Ok(());
+        }
+
+        agent_ids.insert(agent_id.to_string());
+
+        rate_limiter.check(agent_id, Instant::now()).map_err(|err| {
+            event!(
+                Level::WARN,
+                agent_id,
+                error = %err,
+                "Node agent sends statuses too fast, closing its status stream"
+            );
+
+            Status::from(err)
+        })
+    }
+
+    /// Process the statuses of a stream until it ends.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The status stream opened by the node agent.
+    /// * `peer_id` - The ID of the node agent, if its identity was verified.
+    /// * `agent_ids` - The IDs of the rate limited agents of the stream, updated as the statuses
+    ///   are received.
+    ///
+    /// # Errors
+    ///
+    /// * A status was sent for another node than the verified one.
+    /// * A status was sent for an agent that is not in the cluster.
+    /// * The agent exceeded its rate limit.
+    /// * The stream was interrupted.
+    async fn process_statuses(
+        &self,
+        stream: &mut Streaming<NodeStatus>,
+        peer_id: Option<&str>,
+        agent_ids: &mut HashSet<String>,
+    ) -> Result<()> {
         while let Some(result) = stream.next().await {
             match result {
-                Ok(status) => match self.node_agent_manager.lock() {
-                    Ok(mut manager) => {
-                        // Prepare metrics
-                        let cpu: Option<NodeCpu> =
-                            status.cpu_load.map(|cpu| NodeCpu { load: cpu.load });
+                Ok(status) if peer_id.is_some_and(|id| id != status.id) => {
+                    let err = IdentityError::Mismatch {
+                        expected: status.id,
+                        actual: peer_id.unwrap_or_default().to_string(),
+                    };
+
+                    event!(
+                        Level::WARN,
+                        error = %err,
+                        "Node agent sent a status update for another node"
+                    );
+
+                    return Err(Status::from(err));
+                }
+                Ok(mut status) => {
+                    let id = status.id.clone();
+                    let instances = status
+                        .lists_instances
+                        .then(|| std::mem::take(&mut status.instances));
+
+                    self.check_rate(&id, agent_ids)?;
+
+                    // Update the node status data
+                    let res = self
+                        .node_agent_manager
+                        .update_node_status(&id, to_node_metrics(status));
 
-                        let memory: Option<NodeMemory> = status.memory.map(|m| NodeMemory {
-                            total: m.total,
-                            free: m.free,
-                        });
+                    match res {
+                        Ok(change) => {
+                            if change.recovered {
+                                self.cluster_events.publish(ClusterEvent::NodeRecovered {
+                                    node_id: id.clone(),
+                                });
+                            }
 
-                        // Update the node status data
-                        let res = manager.update_node_status(&status.id, cpu, memory);
+                            // The pending workloads may fit on the agent that joined or recovered
+                            if change.gained_capacity {
+                                self.pending_queue.wake();
+                            }
 
-                        if let Err(err) = res {
+                            if let Some(instances) = instances {
+                                self.reconcile_instances(&id, instances);
+                            }
+                        }
+                        Err(err) => {
                             event!(
                                 Level::WARN,
-                                agent_id = status.id,
+                                agent_id = id,
                                 error = %err,
                                 "Unable to process node status update"
                             );
@@ -65,29 +292,62 @@ impl StatusUpdateService for AgentStatusUpdateSvc {
                             return Err(Status::from(err));
                         }
                     }
-                    Err(err) => {
-                        event!(
-                            Level::WARN,
-                            agent_id = status.id,
-                            error = %err,
-                            "Failed to acquire node manager, cannot process node status update for agent"
-                        );
-
-                        return Err(Status::internal("Failed to process node status"));
-                    }
-                },
+                }
                 Err(err) => {
                     event!(
                         Level::WARN,
                         error = %err,
                         "An error was received while processing a node status update stream"
                     );
+                    self.metrics.record_stream_error(StatusStream::Node);
 
                     return Err(Status::internal("An error was received from the client"));
                 }
             }
         }
 
-        Ok(Response::new(Empty {}))
+        Ok(())
+    }
+}
+
+#[tonic::async_trait]
+impl StatusUpdateService for AgentStatusUpdateSvc {
+    /// Called by node agents to start streaming status information about the node.
+    async fn update_node_status(
+        &self,
+        request: Request<Streaming<NodeStatus>>,
+    ) -> Result<Response<Empty>> {
+        // The agent can only report the status of the node its certificate was issued to
+        let peer_id = if self.verify_identity {
+            Some(peer_common_name(&request).map_err(|err| {
+                event!(
+                    Level::WARN,
+                    error = %err,
+                    "Node agent failed identity verification, refusing status updates"
+                );
+
+                Status::from(err)
+            })?)
+        } else {
+            None
+        };
+
+        let mut stream = request.into_inner();
+        let mut agent_ids = HashSet::new();
+
+        let result = self
+            .process_statuses(&mut stream, peer_id.as_deref(), &mut agent_ids)
+            .await;
+
+        // Forget the rate limits of the agents that left the cluster meanwhile
+        if let Some(rate_limiter) = &self.rate_limiter {
+            for agent_id in agent_ids {
+                if !self.node_agent_manager.contains(&agent_id) {
+                    rate_limiter.forget(&agent_id);
+                }
+            }
+        }
+
+        result.map(|()| Response::new(Empty {}))
     }
 }
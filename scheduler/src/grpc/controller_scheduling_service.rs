@@ -1,73 +1,993 @@
 //! Scheduling gRPC service for the Orka controller.
 
 use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Instant;
 
+use dashmap::DashMap;
+use orka_proto::node_agent::{workload_signal::Signal, WorkloadSignal};
 use orka_proto::scheduler_controller::{
-    scheduling_service_server::SchedulingService, Empty, SchedulingRequest, WorkloadInstance,
+    scheduling_service_server::SchedulingService,
+    workload_status::{
+        status::{Reason, StatusCode},
+        Status as InstanceStatus,
+    },
+    ClusterEvent, Empty, SchedulingRequest, WatchClusterEventsRequest, Workload, WorkloadInstance,
     WorkloadStatus,
 };
-use tokio_stream::Stream;
-use tonic::{Request, Response, Result, Status};
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use tokio_stream::{wrappers::ReceiverStream, Stream};
+use tonic::{Request, Response, Result, Status, Streaming};
+use tracing::{event, Level};
+
+use crate::managers::events::broadcaster::ClusterEvents;
+use crate::managers::instance::errors::InstanceError;
+use crate::managers::instance::manager::InstanceManager;
+use crate::managers::node_agent::client_pool::AgentClientPool;
+use crate::managers::node_agent::manager::NodeAgentManager;
+use crate::managers::pending::queue::{PendingQueue, PendingWorkload};
+use crate::metrics::registry::{PlacementOutcome, SchedulerMetrics, StatusStream};
+use crate::placement::constraints::WorkloadConstraints;
+use crate::placement::errors::PlacementError;
+use crate::placement::placer::Placer;
+use crate::placement::requirements::WorkloadRequirements;
+
+use super::conversions::{
+    to_agent_workload, to_controller_event, to_controller_status, to_instance_state,
+};
+use super::shutdown::{shutting_down, Drain};
+
+/// Number of workload statuses buffered between a node agent and the controller.
+const STATUS_CHANNEL_CAPACITY: usize = 16;
+
+/// Number of changes of the cluster buffered between the scheduler and a watching controller.
+const EVENT_CHANNEL_CAPACITY: usize = 16;
+
+/// The senders of the status streams returned to the controller, indexed by instance ID.
+pub type StatusSenders = DashMap<String, mpsc::Sender<Result<WorkloadStatus>>>;
+
+/// The outcome of an attempt to place a workload.
+enum Placement {
+    /// The workload was placed on a node agent, once the victims are evicted.
+    Placed {
+        /// The ID of the node agent.
+        agent_id: String,
+        /// The IDs of the instances to evict from the node agent.
+        victims: Vec<String>,
+    },
+
+    /// No node agent can run the workload yet.
+    Pending(PlacementError),
+}
 
 /// Implementation of the `SchedulingService` gRPC service.
-pub struct ControllerSchedulingSvc {}
+pub struct ControllerSchedulingSvc {
+    /// The shared instance of the node agent manager.
+    node_agent_manager: Arc<NodeAgentManager>,
+
+    /// The shared pool of clients for the node agents.
+    agent_client_pool: Arc<AgentClientPool>,
+
+    /// The shared instance of the workload instance manager.
+    instance_manager: Arc<InstanceManager>,
+
+    /// The placer choosing the node agent of each workload.
+    placer: Placer,
+
+    /// Lock held while placing a workload and reserving its resources, so that concurrent
+    /// placements see the reservations of each other.
+    placement_lock: Mutex<()>,
+
+    /// The senders of the status streams of the scheduled instances, used to notify the
+    /// controller of the instances evicted by the scheduler.
+    status_senders: Arc<StatusSenders>,
+
+    /// The shared queue of the workloads waiting for a node agent able to run them.
+    pending_queue: Arc<PendingQueue>,
+
+    /// The metrics of the scheduler, recording the placements and the status stream errors.
+    metrics: Arc<SchedulerMetrics>,
+
+    /// The drain of the scheduler, refusing new workloads once it started.
+    drain: Arc<Drain>,
+
+    /// The broadcaster of the changes of the cluster watched by the controller.
+    cluster_events: Arc<ClusterEvents>,
+}
 
 impl ControllerSchedulingSvc {
     /// Create a new `SchedulingService` gRPC service manager.
-    pub fn new() -> Self {
-        Self {}
+    ///
+    /// # Arguments
+    ///
+    /// * `manager` - The shared instance of the node agent manager.
+    /// * `client_pool` - The shared pool of clients for the node agents.
+    /// * `instance_manager` - The shared instance of the workload instance manager.
+    /// * `placer` - The placer choosing the node agent of each workload.
+    /// * `pending_queue` - The shared queue of the workloads waiting for a node agent.
+    /// * `metrics` - The metrics of the scheduler.
+    /// * `drain` - The drain of the scheduler.
+    /// * `cluster_events` - The broadcaster of the changes of the cluster.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        manager: Arc<NodeAgentManager>,
+        client_pool: Arc<AgentClientPool>,
+        instance_manager: Arc<InstanceManager>,
+        placer: Placer,
+        pending_queue: Arc<PendingQueue>,
+        metrics: Arc<SchedulerMetrics>,
+        drain: Arc<Drain>,
+        cluster_events: Arc<ClusterEvents>,
+    ) -> Self {
+        Self {
+            node_agent_manager: manager,
+            agent_client_pool: client_pool,
+            instance_manager,
+            placer,
+            placement_lock: Mutex::new(()),
+            status_senders: Arc::new(DashMap::new()),
+            pending_queue,
+            metrics,
+            drain,
+            cluster_events,
+        }
+    }
+
+    /// Get the senders of the status streams of the scheduled instances, indexed by instance ID.
+    pub fn status_senders(&self) -> Arc<StatusSenders> {
+        Arc::clone(&self.status_senders)
+    }
+
+    /// Lock the placement of workloads.
+    fn lock_placement(&self) -> std::sync::MutexGuard<'_, ()> {
+        // The lock only guards the placement, the guard holds no data to poison
+        self.placement_lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Try to place a workload on a node agent, evicting instances with a lower priority if no
+    /// agent has enough resources left. The resources of the workload are reserved on the agent
+    /// and the resources of the victims are released.
+    ///
+    /// Must be called while holding the placement lock.
+    ///
+    /// # Arguments
+    ///
+    /// * `workload` - The workload to place.
+    ///
+    /// # Errors
+    ///
+    /// * The placement constraints of the workload are invalid.
+    /// * The instance is already placed on a node.
+    #[allow(clippy::result_large_err)]
+    fn place_instance(&self, workload: &Workload) -> Result<Placement> {
+        let started = Instant::now();
+        let placement = self.try_place_instance(workload);
+
+        let outcome = match &placement {
+            Ok(Placement::Placed { .. }) => PlacementOutcome::Placed,
+            Ok(Placement::Pending(_)) => PlacementOutcome::Pending,
+            Err(_) => PlacementOutcome::Rejected,
+        };
+        self.metrics.record_placement(outcome, started.elapsed());
+
+        placement
+    }
+
+    /// Place a workload on a node agent, as described by [`Self::place_instance`], without
+    /// recording the attempt.
+    ///
+    /// # Arguments
+    ///
+    /// * `workload` - The workload to place.
+    ///
+    /// # Errors
+    ///
+    /// * The placement constraints of the workload are invalid.
+    /// * The instance is already placed on a node.
+    #[allow(clippy::result_large_err)]
+    fn try_place_instance(&self, workload: &Workload) -> Result<Placement> {
+        let instance_id = &workload.instance_id;
+        let requirements = WorkloadRequirements::from(workload);
+        let constraints = WorkloadConstraints::try_from(workload).map_err(|err| {
+            event!(
+                Level::WARN,
+                instance_id,
+                error = %err,
+                "Refusing a workload with invalid placement constraints"
+            );
+
+            Status::from(err)
+        })?;
+
+        let placement = match self.placer.place(
+            instance_id,
+            &self.node_agent_manager,
+            &self.instance_manager,
+            &requirements,
+            &constraints,
+        ) {
+            Ok(agent_id) => Ok((agent_id, Vec::new())),
+            // Make room by evicting instances with a lower priority, if possible
+            Err(err @ PlacementError::InsufficientResources(_)) => self
+                .placer
+                .preempt(
+                    instance_id,
+                    &self.node_agent_manager,
+                    &self.instance_manager,
+                    &requirements,
+                    &constraints,
+                    workload.priority,
+                )
+                .map(|preemption| (preemption.agent_id, preemption.victims))
+                .ok_or(err),
+            Err(err) => Err(err),
+        };
+
+        let (agent_id, victims) = match placement {
+            Ok(placement) => placement,
+            Err(err) if err.is_transient() => return Ok(Placement::Pending(err)),
+            Err(err) => return Err(Status::from(err)),
+        };
+
+        self.instance_manager
+            .add_instance(instance_id, &agent_id, requirements, workload.priority)
+            .map_err(|err| {
+                event!(
+                    Level::WARN,
+                    instance_id,
+                    error = %err,
+                    "Refusing to schedule a workload instance twice"
+                );
+
+                Status::from(err)
+            })?;
+
+        // Release the resources of the victims before another workload is placed
+        for victim in &victims {
+            self.instance_manager.remove_instance(victim);
+        }
+
+        event!(
+            Level::INFO,
+            instance_id,
+            agent_id,
+            preempted = victims.len(),
+            "Placed workload on node agent"
+        );
+
+        Ok(Placement::Placed { agent_id, victims })
+    }
+
+    /// Start a placed workload instance: evict the victims, create the workload on its node agent
+    /// and relay its statuses to the controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `workload` - The placed workload.
+    /// * `agent_id` - The ID of the node agent the workload was placed on.
+    /// * `victims` - The IDs of the instances to evict from the node agent.
+    /// * `sender` - The sender of the status stream returned to the controller.
+    ///
+    /// # Errors
+    ///
+    /// * The node agent could not be reached or refused the workload, the placement is forgotten.
+    async fn start_instance(
+        &self,
+        workload: Workload,
+        agent_id: &str,
+        victims: Vec<String>,
+        sender: mpsc::Sender<Result<WorkloadStatus>>,
+    ) -> Result<()> {
+        let instance_id = workload.instance_id.clone();
+
+        if !victims.is_empty() {
+            event!(
+                Level::INFO,
+                instance_id,
+                agent_id,
+                ?victims,
+                "Preempting workload instances"
+            );
+
+            let message = format!(
+                "Preempted by workload instance `{}` with priority {}",
+                instance_id, workload.priority
+            );
+            self.evict_instances(agent_id, victims, Reason::Preempted, &message)
+                .await;
+        }
+
+        // Forward the workload to the chosen node agent, forgetting the placement on failure
+        let agent_stream = match self.create_on_agent(agent_id, workload).await {
+            Ok(stream) => stream,
+            Err(status) => {
+                if self
+                    .instance_manager
+                    .remove_instance(&instance_id)
+                    .is_some()
+                {
+                    self.pending_queue.wake();
+                }
+                return Err(status);
+            }
+        };
+
+        // Never wait for a slow controller, the status only confirms the placement
+        let _ = sender.try_send(Ok(WorkloadStatus {
+            instance_id: instance_id.clone(),
+            status: Some(InstanceStatus {
+                code: StatusCode::Waiting as u32,
+                message: Some(format!("Workload placed on node `{}`", agent_id)),
+                reason: Reason::Unspecified as i32,
+            }),
+            resource_usage: None,
+        }));
+
+        self.status_senders
+            .insert(instance_id.clone(), sender.clone());
+
+        tokio::spawn(Self::relay_statuses(
+            instance_id,
+            agent_stream,
+            sender,
+            Arc::clone(&self.instance_manager),
+            Arc::clone(&self.status_senders),
+            Arc::clone(&self.pending_queue),
+            Arc::clone(&self.metrics),
+        ));
+
+        Ok(())
+    }
+
+    /// Retry the pending workloads whenever they are due or the cluster gains capacity, forever.
+    pub async fn run_pending_queue(self: Arc<Self>) {
+        event!(Level::DEBUG, "Starting the pending workload queue");
+
+        loop {
+            let deadline = self.pending_queue.next_deadline();
+            let sleep = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline.into()).await,
+                    None => std::future::pending().await,
+                }
+            };
+
+            tokio::select! {
+                _ = self.pending_queue.woken() => {}
+                _ = sleep => {}
+            }
+
+            self.retry_pending();
+        }
+    }
+
+    /// Tell the controller that the scheduler is going away on every open status stream, so that
+    /// it schedules the pending workloads and follows the running instances with another
+    /// scheduler. The streams are closed once the error is sent.
+    pub fn notify_shutdown(&self) {
+        let pending = {
+            let _guard = self.lock_placement();
+            self.pending_queue.take_all()
+        };
+
+        event!(
+            Level::INFO,
+            streams = self.status_senders.len(),
+            pending = pending.len(),
+            "Notifying the controllers that the scheduler is shutting down"
+        );
+
+        for pending in pending {
+            let _ = pending.sender.try_send(Err(shutting_down()));
+        }
+
+        for entry in self.status_senders.iter() {
+            let _ = entry.value().try_send(Err(shutting_down()));
+        }
+    }
+
+    /// Retry the pending workloads that are due and abandon the ones that waited for too long.
+    fn retry_pending(self: &Arc<Self>) {
+        let now = Instant::now();
+
+        let (expired, placed) = {
+            let _guard = self.lock_placement();
+
+            let expired = self.pending_queue.take_expired(now);
+            let mut placed = Vec::new();
+
+            for pending in self.pending_queue.take_due(now) {
+                if pending.sender.is_closed() {
+                    event!(
+                        Level::DEBUG,
+                        instance_id = pending.instance_id(),
+                        "The controller closed the stream of a pending workload, dropping it"
+                    );
+                    continue;
+                }
+
+                match self.place_instance(&pending.workload) {
+                    Ok(Placement::Placed { agent_id, victims }) => {
+                        placed.push((pending, agent_id, victims))
+                    }
+                    Ok(Placement::Pending(err)) => self.requeue(pending, err, now),
+                    Err(status) => {
+                        let _ = pending.sender.try_send(Err(status));
+                    }
+                }
+            }
+
+            (expired, placed)
+        };
+
+        for pending in expired {
+            event!(
+                Level::WARN,
+                instance_id = pending.instance_id(),
+                reason = pending.reason,
+                queue_depth = self.pending_queue.depth(),
+                "Abandoning a workload that could not be placed in time"
+            );
+
+            let _ = pending
+                .sender
+                .try_send(Err(Status::deadline_exceeded(format!(
+                    "The workload instance `{}` could not be placed within {} seconds: {}",
+                    pending.instance_id(),
+                    self.pending_queue.options().max_wait.as_secs(),
+                    pending.reason
+                ))));
+        }
+
+        for (pending, agent_id, victims) in placed {
+            let svc = Arc::clone(self);
+
+            tokio::spawn(async move {
+                let PendingWorkload {
+                    workload, sender, ..
+                } = pending;
+
+                if let Err(status) = svc
+                    .start_instance(workload, &agent_id, victims, sender.clone())
+                    .await
+                {
+                    let _ = sender.send(Err(status)).await;
+                }
+            });
+        }
+
+        event!(
+            Level::DEBUG,
+            queue_depth = self.pending_queue.depth(),
+            "Retried the pending workloads"
+        );
+    }
+
+    /// Queue again a pending workload that still cannot be placed, telling the controller why if
+    /// the reason changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `pending` - The pending workload.
+    /// * `err` - Why the workload cannot be placed.
+    /// * `now` - The current time.
+    fn requeue(&self, pending: PendingWorkload, err: PlacementError, now: Instant) {
+        let reason = err.to_string();
+
+        if reason != pending.reason {
+            let _ = pending
+                .sender
+                .try_send(Ok(waiting_status(pending.instance_id(), &reason)));
+        }
+
+        let sender = pending.sender.clone();
+
+        if let Err(err) = self.pending_queue.requeue(pending, reason, now) {
+            let _ = sender.try_send(Err(Status::from(err)));
+        }
+    }
+
+    /// Remove a workload from the queue of pending workloads, telling the controller it was
+    /// cancelled. Returns whether the workload was pending.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - The ID of the workload instance.
+    fn cancel_pending(&self, instance_id: &str) -> bool {
+        let pending = {
+            let _guard = self.lock_placement();
+            self.pending_queue.remove(instance_id)
+        };
+
+        let Some(pending) = pending else {
+            return false;
+        };
+
+        event!(
+            Level::INFO,
+            instance_id,
+            "Cancelled a pending workload before it was placed"
+        );
+
+        let _ = pending.sender.try_send(Ok(WorkloadStatus {
+            instance_id: instance_id.to_string(),
+            status: Some(InstanceStatus {
+                code: StatusCode::Terminated as u32,
+                message: Some("Cancelled before being placed on a node".to_string()),
+                reason: Reason::Unspecified as i32,
+            }),
+            resource_usage: None,
+        }));
+
+        true
+    }
+
+    /// Send a signal to the node agent running a workload instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - The ID of the workload instance.
+    /// * `signal` - The signal to send.
+    ///
+    /// # Errors
+    ///
+    /// * The instance is unknown.
+    /// * The node agent running the instance could not be reached or refused the signal.
+    async fn signal_instance(&self, instance_id: String, signal: Signal) -> Result<()> {
+        let placement = self.instance_manager.placement(&instance_id)?;
+
+        self.signal_agent(placement.agent_id(), instance_id, signal)
+            .await
+    }
+
+    /// Send a signal about a workload instance to a node agent.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent running the instance.
+    /// * `instance_id` - The ID of the workload instance.
+    /// * `signal` - The signal to send.
+    ///
+    /// # Errors
+    ///
+    /// * The node agent could not be reached or refused the signal.
+    async fn signal_agent(
+        &self,
+        agent_id: &str,
+        instance_id: String,
+        signal: Signal,
+    ) -> Result<()> {
+        let mut client = self.agent_client_pool.client(agent_id)?;
+
+        event!(
+            Level::INFO,
+            instance_id,
+            agent_id,
+            ?signal,
+            "Sending signal to workload instance"
+        );
+
+        client
+            .signal(WorkloadSignal {
+                instance_id: instance_id.clone(),
+                signal: signal.into(),
+            })
+            .await
+            .map_err(|err| {
+                event!(
+                    Level::WARN,
+                    instance_id,
+                    agent_id,
+                    error = %err,
+                    "Node agent failed to signal the workload instance"
+                );
+
+                Status::unavailable(format!(
+                    "Node agent `{}` could not signal the workload instance",
+                    agent_id
+                ))
+            })?;
+
+        Ok(())
+    }
+
+    /// Cordon a node agent and evict all the workload instances placed on it, so that the
+    /// controller schedules them again on other nodes. Returns the IDs of the evicted instances.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    ///
+    /// # Errors
+    ///
+    /// * The node agent is not in the cluster.
+    pub async fn drain_agent(&self, agent_id: &str) -> Result<Vec<String>> {
+        let victims = {
+            let _guard = self.lock_placement();
+
+            self.node_agent_manager
+                .set_cordoned(agent_id, true)
+                .map_err(Status::from)?;
+
+            // Release the resources of the victims, nothing can be placed on the agent anymore
+            let victims: Vec<String> = self
+                .instance_manager
+                .agent_instances(agent_id)
+                .into_iter()
+                .map(|(instance_id, _)| instance_id)
+                .collect();
+
+            for victim in &victims {
+                self.instance_manager.remove_instance(victim);
+            }
+
+            victims
+        };
+
+        event!(
+            Level::INFO,
+            agent_id,
+            instances = ?victims,
+            "Draining node agent"
+        );
+
+        let message = format!(
+            "Node `{}` was drained, the workload must be scheduled again",
+            agent_id
+        );
+        self.evict_instances(agent_id, victims.clone(), Reason::Drained, &message)
+            .await;
+
+        Ok(victims)
+    }
+
+    /// Evict workload instances from a node agent, notifying the controller through their status
+    /// streams and gracefully stopping them. Failures are logged but not returned, the resources
+    /// of the evicted instances are already released.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent running the victims.
+    /// * `victims` - The IDs of the instances to evict.
+    /// * `reason` - Why the instances are evicted.
+    /// * `message` - The message sent to the controller with the status of each evicted instance.
+    async fn evict_instances(
+        &self,
+        agent_id: &str,
+        victims: Vec<String>,
+        reason: Reason,
+        message: &str,
+    ) {
+        for victim in victims {
+            let sender = self
+                .status_senders
+                .get(&victim)
+                .map(|sender| sender.clone());
+
+            if let Some(sender) = sender {
+                let status = WorkloadStatus {
+                    instance_id: victim.clone(),
+                    status: Some(InstanceStatus {
+                        code: StatusCode::Terminated as u32,
+                        message: Some(message.to_string()),
+                        reason: reason as i32,
+                    }),
+                    resource_usage: None,
+                };
+
+                // Never wait for a slow controller while evicting the other instances
+                if sender.try_send(Ok(status)).is_err() {
+                    event!(
+                        Level::WARN,
+                        instance_id = victim,
+                        ?reason,
+                        "Unable to notify the controller of the eviction of the workload instance"
+                    );
+                }
+            }
+
+            // The failure was already logged, and the instance is forgotten regardless
+            let _ = self.signal_agent(agent_id, victim, Signal::Stop).await;
+        }
+    }
+
+    /// Forward a workload to a node agent, returning the status stream opened by the agent.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    /// * `workload` - The workload to create on the node.
+    ///
+    /// # Errors
+    ///
+    /// * The node agent could not be reached or refused the workload.
+    async fn create_on_agent(
+        &self,
+        agent_id: &str,
+        workload: Workload,
+    ) -> Result<Streaming<orka_proto::node_agent::WorkloadStatus>> {
+        let instance_id = workload.instance_id.clone();
+
+        let mut client = self.agent_client_pool.client(agent_id)?;
+
+        let response = client
+            .create(to_agent_workload(workload))
+            .await
+            .map_err(|err| {
+                event!(
+                    Level::WARN,
+                    instance_id,
+                    agent_id,
+                    error = %err,
+                    "Node agent refused to create the workload"
+                );
+
+                Status::unavailable(format!(
+                    "Node agent `{}` could not create the workload",
+                    agent_id
+                ))
+            })?;
+
+        Ok(response.into_inner())
+    }
+
+    /// Relay the workload statuses streamed by a node agent to the controller, until either side
+    /// closes its stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - The ID of the workload instance.
+    /// * `agent_stream` - The status stream opened by the node agent.
+    /// * `sender` - The sender of the status stream returned to the controller.
+    /// * `instance_manager` - The shared instance of the workload instance manager, used to
+    ///   forget the instance once it terminated.
+    /// * `status_senders` - The senders of the status streams, the sender is unregistered once
+    ///   the relay ends.
+    /// * `pending_queue` - The shared queue of the pending workloads, woken up once the instance
+    ///   terminated.
+    /// * `metrics` - The metrics of the scheduler, counting the interrupted streams.
+    async fn relay_statuses(
+        instance_id: String,
+        mut agent_stream: Streaming<orka_proto::node_agent::WorkloadStatus>,
+        sender: mpsc::Sender<Result<WorkloadStatus>>,
+        instance_manager: Arc<InstanceManager>,
+        status_senders: Arc<StatusSenders>,
+        pending_queue: Arc<PendingQueue>,
+        metrics: Arc<SchedulerMetrics>,
+    ) {
+        loop {
+            let message = match agent_stream.message().await {
+                Ok(Some(status)) => {
+                    let status = to_controller_status(status);
+                    let is_terminated = status
+                        .status
+                        .as_ref()
+                        .is_some_and(|s| s.code == StatusCode::Terminated as u32);
+
+                    // The statuses listed with the node status are only relayed if they differ
+                    if let Some(s) = &status.status {
+                        instance_manager.record_state(&instance_id, to_instance_state(s.code));
+                    }
+
+                    if is_terminated && instance_manager.remove_instance(&instance_id).is_some() {
+                        pending_queue.wake();
+                    }
+
+                    Ok(status)
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    event!(
+                        Level::WARN,
+                        instance_id,
+                        error = %err,
+                        "An error was received from the node agent while streaming workload statuses"
+                    );
+                    metrics.record_stream_error(StatusStream::Workload);
+
+                    Err(Status::unavailable(
+                        "The status stream of the node agent was interrupted",
+                    ))
+                }
+            };
+
+            let is_error = message.is_err();
+
+            if sender.send(message).await.is_err() {
+                event!(
+                    Level::DEBUG,
+                    instance_id,
+                    "The controller closed the workload status stream"
+                );
+                break;
+            }
+
+            if is_error {
+                break;
+            }
+        }
+
+        // The instance may have been scheduled again with another stream in the meantime
+        status_senders.remove_if(&instance_id, |_, registered| {
+            registered.same_channel(&sender)
+        });
     }
 }
 
 #[tonic::async_trait]
 impl SchedulingService for ControllerSchedulingSvc {
     type ScheduleStream = Pin<Box<dyn Stream<Item = Result<WorkloadStatus>> + Send>>;
+    type WatchClusterEventsStream = Pin<Box<dyn Stream<Item = Result<ClusterEvent>> + Send>>;
 
     /// Called by the controller when it requests to schedule a workload on a node. The scheduler
     /// responds by streaming status information about the workload.
     async fn schedule(
         &self,
-        _: Request<SchedulingRequest>,
+        request: Request<SchedulingRequest>,
     ) -> Result<Response<Self::ScheduleStream>> {
-        todo!();
-        // Example: https://github.com/hyperium/tonic/blob/master/examples/src/streaming
-        //let (tx, rx) = mpsc::channel(128);
-        //tokio::spawn(async move {
-        //    match tx.send(Ok(WorkloadStatus {
-        //        name: "my_workload".to_string(),
-        //        status_code: StatusCode::Running.into(),
-        //        resource_usage: Some(Resources {
-        //            cpu: 1,
-        //            memory: 1,
-        //            disk: 1,
-        //        }),
-        //        message: "Everything is fine".to_string(),
-        //    }))
-        //    .await {
-        //        Ok(_) => (),
-        //        Err(_) => (),
-        //    };
-        //});
-
-        //let output_stream = ReceiverStream::new(rx);
-        //Ok(Response::new(
-        //    Box::pin(output_stream) as Self::ScheduleStream
-        //))
+        self.drain.check_accepting()?;
+
+        let workload = request
+            .into_inner()
+            .workload
+            .ok_or_else(|| Status::invalid_argument("No workload was provided"))?;
+
+        let instance_id = workload.instance_id.clone();
+        let (sender, receiver) = mpsc::channel(STATUS_CHANNEL_CAPACITY);
+
+        let placed = {
+            let _guard = self.lock_placement();
+
+            if self.pending_queue.contains(&instance_id) {
+                return Err(Status::from(InstanceError::AlreadyExists(instance_id)));
+            }
+
+            match self.place_instance(&workload)? {
+                Placement::Placed { agent_id, victims } => Some((workload, agent_id, victims)),
+                Placement::Pending(err) => {
+                    // The channel is empty, so the first status can always be buffered
+                    let reason = err.to_string();
+                    let _ = sender.try_send(Ok(waiting_status(&instance_id, &reason)));
+
+                    self.pending_queue
+                        .push(workload, sender.clone(), reason, Instant::now())?;
+                    None
+                }
+            }
+        };
+
+        if let Some((workload, agent_id, victims)) = placed {
+            self.start_instance(workload, &agent_id, victims, sender)
+                .await?;
+        }
+
+        Ok(Response::new(
+            Box::pin(ReceiverStream::new(receiver)) as Self::ScheduleStream
+        ))
     }
 
     /// Called by the controller to request a workload instance to be gracefully stopped.
     async fn stop(
         &self,
-        _: Request<WorkloadInstance>,
+        request: Request<WorkloadInstance>,
     ) -> std::result::Result<Response<Empty>, Status> {
-        todo!()
+        let instance_id = request.into_inner().instance_id;
+
+        if self.cancel_pending(&instance_id) {
+            return Ok(Response::new(Empty {}));
+        }
+
+        self.signal_instance(instance_id, Signal::Stop).await?;
+
+        Ok(Response::new(Empty {}))
     }
 
     /// Called by the controller to request a workload instance to be terminated.
     async fn destroy(
         &self,
-        _: Request<WorkloadInstance>,
+        request: Request<WorkloadInstance>,
     ) -> std::result::Result<Response<Empty>, Status> {
-        todo!()
+        let instance_id = request.into_inner().instance_id;
+
+        if self.cancel_pending(&instance_id) {
+            return Ok(Response::new(Empty {}));
+        }
+
+        self.signal_instance(instance_id.clone(), Signal::Kill)
+            .await?;
+
+        // The instance is killed, there is nothing left to track
+        if self
+            .instance_manager
+            .remove_instance(&instance_id)
+            .is_some()
+        {
+            self.pending_queue.wake();
+        }
+
+        Ok(Response::new(Empty {}))
+    }
+
+    /// Called by the controller to watch the changes of the cluster: the node agents joining,
+    /// leaving, becoming unhealthy or recovering, and the instances lost with them. Only the
+    /// changes happening after the call are streamed. The stream is closed with an error if the
+    /// controller falls behind, or when the scheduler shuts down.
+    async fn watch_cluster_events(
+        &self,
+        _: Request<WatchClusterEventsRequest>,
+    ) -> Result<Response<Self::WatchClusterEventsStream>> {
+        self.drain.check_accepting()?;
+
+        let mut cluster_events = self.cluster_events.subscribe();
+        let drain = Arc::clone(&self.drain);
+        let (sender, receiver) = mpsc::channel(EVENT_CHANNEL_CAPACITY);
+
+        event!(
+            Level::INFO,
+            "Controller started watching the changes of the cluster"
+        );
+
+        tokio::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    received = cluster_events.recv() => match received {
+                        Ok(cluster_event) => Ok(to_controller_event(cluster_event)),
+                        Err(RecvError::Lagged(missed)) => {
+                            event!(
+                                Level::WARN,
+                                missed,
+                                "A controller fell behind the changes of the cluster, closing its watch"
+                            );
+
+                            Err(Status::aborted(format!(
+                                "The watch missed {} changes of the cluster, watch again and resynchronize",
+                                missed
+                            )))
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                    _ = drain.started() => Err(shutting_down()),
+                    _ = sender.closed() => break,
+                };
+
+                let is_closing = message.is_err();
+
+                if sender.send(message).await.is_err() || is_closing {
+                    break;
+                }
+            }
+
+            event!(
+                Level::DEBUG,
+                "Controller stopped watching the changes of the cluster"
+            );
+        });
+
+        Ok(Response::new(
+            Box::pin(ReceiverStream::new(receiver)) as Self::WatchClusterEventsStream
+        ))
+    }
+}
+
+/// Create the status telling the controller that a workload waits for a node able to run it.
+///
+/// # Arguments
+///
+/// * `instance_id` - The ID of the workload instance.
+/// * `reason` - Why the workload cannot be placed.
+fn waiting_status(instance_id: &str, reason: &str) -> WorkloadStatus {
+    WorkloadStatus {
+        instance_id: instance_id.to_string(),
+        status: Some(InstanceStatus {
+            code: StatusCode::Waiting as u32,
+            message: Some(format!(
+                "Waiting for a node able to run the workload: {}",
+                reason
+            )),
+            reason: Reason::Unspecified as i32,
+        }),
+        resource_usage: None,
     }
 }
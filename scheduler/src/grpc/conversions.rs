@@ -0,0 +1,391 @@
+//! Conversions between the messages of the controller and node agent gRPC APIs, and between these
+//! messages and the data of the scheduler.
+
+use std::collections::HashMap;
+
+use crate::managers::events::broadcaster::ClusterEvent;
+use crate::managers::instance::report::{InstanceState, ReportedInstance};
+use crate::managers::node_agent::errors::NodeAgentError;
+use crate::managers::node_agent::metrics::{
+    NodeAgent, NodeCpu, NodeDisk, NodeHealth, NodeLoadAverage, NodeMemory, NodeMetrics, NodeNetwork,
+};
+use crate::managers::node_agent::properties::{NodeProperties, NodeTaint, TaintEffect};
+use crate::metrics::registry::LeaveCause;
+use crate::placement::requirements::WorkloadRequirements;
+use orka_proto::node_agent;
+use orka_proto::scheduler_admin::{node, Node};
+use orka_proto::scheduler_agent::{
+    node_status::{instance_status, InstanceStatus},
+    taint, NodeStatus, Taint,
+};
+use orka_proto::scheduler_controller::{
+    cluster_event, workload, workload_status, Workload, WorkloadStatus,
+};
+
+/// Convert a node agent into a node of the admin API.
+///
+/// # Arguments
+///
+/// * `id` - The ID of the node agent.
+/// * `agent` - The node agent.
+/// * `instance_ids` - The IDs of the workload instances placed on the agent.
+pub fn to_admin_node(id: &str, agent: &NodeAgent, mut instance_ids: Vec<String>) -> Node {
+    let health = match agent.health() {
+        NodeHealth::Healthy => node::Health::Healthy,
+        NodeHealth::Unhealthy => node::Health::Unhealthy,
+        NodeHealth::Pending => node::Health::Pending,
+    };
+
+    instance_ids.sort();
+
+    Node {
+        id: id.to_string(),
+        address: agent.address().to_string(),
+        health: health as i32,
+        cordoned: agent.is_cordoned(),
+        labels: agent
+            .labels()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect(),
+        cpu_load: agent.cpu().map(|cpu| cpu.load),
+        memory: agent.memory().map(|memory| node::Memory {
+            total: memory.total,
+            free: memory.free,
+        }),
+        instance_ids,
+    }
+}
+
+/// Convert a change of the cluster into an event for the controller.
+///
+/// # Arguments
+///
+/// * `cluster_event` - The change of the cluster.
+pub fn to_controller_event(
+    cluster_event: ClusterEvent,
+) -> orka_proto::scheduler_controller::ClusterEvent {
+    let (node_id, event) = match cluster_event {
+        ClusterEvent::NodeJoined { node_id, address } => (
+            node_id,
+            cluster_event::Event::NodeJoined(cluster_event::NodeJoined { address }),
+        ),
+        ClusterEvent::NodeLeft { node_id, cause } => {
+            let cause = match cause {
+                LeaveCause::Notice => cluster_event::node_left::Cause::Notice,
+                LeaveCause::Eviction => cluster_event::node_left::Cause::Eviction,
+            };
+
+            (
+                node_id,
+                cluster_event::Event::NodeLeft(cluster_event::NodeLeft {
+                    cause: cause as i32,
+                }),
+            )
+        }
+        ClusterEvent::NodeUnhealthy { node_id } => (
+            node_id,
+            cluster_event::Event::NodeUnhealthy(cluster_event::NodeUnhealthy {}),
+        ),
+        ClusterEvent::NodeRecovered { node_id } => (
+            node_id,
+            cluster_event::Event::NodeRecovered(cluster_event::NodeRecovered {}),
+        ),
+        ClusterEvent::InstanceLost {
+            instance_id,
+            node_id,
+        } => (
+            node_id,
+            cluster_event::Event::InstanceLost(cluster_event::InstanceLost { instance_id }),
+        ),
+    };
+
+    orka_proto::scheduler_controller::ClusterEvent {
+        node_id,
+        event: Some(event),
+    }
+}
+
+/// Convert a workload received from the controller into a workload for a node agent.
+///
+/// # Arguments
+///
+/// * `workload` - The workload received from the controller.
+pub fn to_agent_workload(workload: Workload) -> node_agent::Workload {
+    node_agent::Workload {
+        instance_id: workload.instance_id,
+        r#type: workload.r#type,
+        image: workload.image,
+        environment: workload.environment,
+        resource_limits: workload.resource_limits.map(|limits: workload::Resources| {
+            node_agent::workload::Resources {
+                cpu: limits.cpu,
+                memory: limits.memory,
+                disk: limits.disk,
+            }
+        }),
+    }
+}
+
+/// Convert a workload status received from a node agent into a workload status for the
+/// controller.
+///
+/// # Arguments
+///
+/// * `status` - The workload status received from the node agent.
+pub fn to_controller_status(status: node_agent::WorkloadStatus) -> WorkloadStatus {
+    WorkloadStatus {
+        instance_id: status.instance_id,
+        status: status.status.map(|status| workload_status::Status {
+            code: status.code,
+            message: status.message,
+            reason: workload_status::status::Reason::Unspecified as i32,
+        }),
+        resource_usage: status
+            .resource_usage
+            .map(|usage| workload_status::Resources {
+                cpu: usage.cpu,
+                memory: usage.memory,
+                disk: usage.disk,
+            }),
+    }
+}
+
+/// Get the state of a workload instance from the code of a workload status received from a node
+/// agent. Unknown codes are considered as waiting.
+///
+/// # Arguments
+///
+/// * `code` - The status code received from the node agent.
+pub fn to_instance_state(code: u32) -> InstanceState {
+    use node_agent::workload_status::status::StatusCode;
+
+    match i32::try_from(code).ok().and_then(StatusCode::from_i32) {
+        Some(StatusCode::Running) => InstanceState::Running,
+        Some(StatusCode::Terminated) => InstanceState::Terminated,
+        Some(StatusCode::Waiting) | None => InstanceState::Waiting,
+    }
+}
+
+/// Convert an instance status listed by a node agent into a reported instance.
+///
+/// # Arguments
+///
+/// * `instance` - The instance status received from the node agent.
+pub fn to_reported_instance(instance: &InstanceStatus) -> ReportedInstance {
+    let state = match instance.state() {
+        instance_status::State::Waiting => InstanceState::Waiting,
+        instance_status::State::Running => InstanceState::Running,
+        instance_status::State::Terminated => InstanceState::Terminated,
+    };
+
+    let resource_usage = instance
+        .resource_usage
+        .as_ref()
+        .map(|usage| WorkloadRequirements::from_limits(usage.cpu, usage.memory, usage.disk))
+        .unwrap_or_default();
+
+    ReportedInstance {
+        id: instance.id.clone(),
+        state,
+        resource_usage,
+    }
+}
+
+/// Convert an instance status listed by a node agent into a workload status for the controller.
+///
+/// # Arguments
+///
+/// * `instance` - The instance status received from the node agent.
+pub fn to_controller_instance_status(instance: InstanceStatus) -> WorkloadStatus {
+    let code = match instance.state() {
+        instance_status::State::Waiting => workload_status::status::StatusCode::Waiting,
+        instance_status::State::Running => workload_status::status::StatusCode::Running,
+        instance_status::State::Terminated => workload_status::status::StatusCode::Terminated,
+    };
+
+    WorkloadStatus {
+        instance_id: instance.id,
+        status: Some(workload_status::Status {
+            code: code as u32,
+            message: None,
+            reason: workload_status::status::Reason::Unspecified as i32,
+        }),
+        resource_usage: instance
+            .resource_usage
+            .map(|usage| workload_status::Resources {
+                cpu: usage.cpu,
+                memory: usage.memory,
+                disk: usage.disk,
+            }),
+    }
+}
+
+/// Convert a node status received from a node agent into the metrics of its node.
+///
+/// Agents that only send the CPU load and the memory are supported, the metrics they do not
+/// report are left empty.
+///
+/// # Arguments
+///
+/// * `status` - The node status received from the node agent.
+pub fn to_node_metrics(status: NodeStatus) -> NodeMetrics {
+    NodeMetrics {
+        cpu: status.cpu_load.map(|cpu| NodeCpu {
+            load: cpu.load,
+            cores: cpu.cores,
+        }),
+        memory: status.memory.map(|memory| NodeMemory {
+            total: memory.total,
+            free: memory.free,
+        }),
+        disks: status
+            .disks
+            .into_iter()
+            .map(|disk| NodeDisk {
+                mount_point: disk.mount_point,
+                total: disk.total,
+                free: disk.free,
+            })
+            .collect(),
+        network: status.network.map(|network| NodeNetwork {
+            received: network.received,
+            transmitted: network.transmitted,
+        }),
+        load_average: status.load_average.map(|load| NodeLoadAverage {
+            one: load.one,
+            five: load.five,
+            fifteen: load.fifteen,
+        }),
+        running_instances: status.running_instances,
+    }
+}
+
+/// Convert the labels and taints declared by a node agent into the properties of its node.
+///
+/// # Arguments
+///
+/// * `labels` - The labels declared by the node agent.
+/// * `taints` - The taints declared by the node agent.
+///
+/// # Errors
+///
+/// * A label or taint has an empty key.
+/// * A taint has an unknown effect.
+pub fn to_node_properties(
+    labels: HashMap<String, String>,
+    taints: Vec<Taint>,
+) -> Result<NodeProperties, NodeAgentError> {
+    if labels.contains_key("") {
+        return Err(NodeAgentError::InvalidProperty(
+            "labels must have a key".to_string(),
+        ));
+    }
+
+    let taints = taints
+        .into_iter()
+        .map(|taint| {
+            if taint.key.is_empty() {
+                return Err(NodeAgentError::InvalidProperty(
+                    "taints must have a key".to_string(),
+                ));
+            }
+
+            let effect = match taint::Effect::from_i32(taint.effect) {
+                Some(taint::Effect::NoSchedule) => TaintEffect::NoSchedule,
+                Some(taint::Effect::PreferNoSchedule) => TaintEffect::PreferNoSchedule,
+                None => {
+                    return Err(NodeAgentError::InvalidProperty(format!(
+                        "unknown effect of taint `{}`: {}",
+                        taint.key, taint.effect
+                    )))
+                }
+            };
+
+            Ok(NodeTaint {
+                key: taint.key,
+                value: taint.value,
+                effect,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(NodeProperties {
+        labels: labels.into_iter().collect(),
+        taints,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_workloads_for_the_node_agents() {
+        let workload = Workload {
+            instance_id: "instance-1".to_string(),
+            image: "nginx:1.25".to_string(),
+            environment: vec!["PORT=80".to_string()],
+            resource_limits: Some(workload::Resources {
+                cpu: Some(50),
+                memory: None,
+                disk: Some(1024),
+            }),
+            ..Default::default()
+        };
+
+        let converted = to_agent_workload(workload.clone());
+
+        assert_eq!(converted.instance_id, workload.instance_id);
+        assert_eq!(converted.r#type, workload.r#type);
+        assert_eq!(converted.image, workload.image);
+        assert_eq!(converted.environment, workload.environment);
+        assert_eq!(
+            converted.resource_limits,
+            Some(node_agent::workload::Resources {
+                cpu: Some(50),
+                memory: None,
+                disk: Some(1024),
+            })
+        );
+
+        let converted = to_agent_workload(Workload::default());
+        assert_eq!(converted.resource_limits, None);
+    }
+
+    #[test]
+    fn converts_workload_statuses_for_the_controller() {
+        let status = node_agent::WorkloadStatus {
+            instance_id: "instance-1".to_string(),
+            status: Some(node_agent::workload_status::Status {
+                code: 1,
+                message: Some("started".to_string()),
+            }),
+            resource_usage: Some(node_agent::workload_status::Resources {
+                cpu: 20,
+                memory: 64,
+                disk: 0,
+            }),
+        };
+
+        let converted = to_controller_status(status);
+
+        assert_eq!(converted.instance_id, "instance-1");
+        assert_eq!(
+            converted.status,
+            Some(workload_status::Status {
+                code: 1,
+                message: Some("started".to_string()),
+                reason: workload_status::status::Reason::Unspecified as i32,
+            })
+        );
+        assert_eq!(
+            converted.resource_usage,
+            Some(workload_status::Resources {
+                cpu: 20,
+                memory: 64,
+                disk: 0,
+            })
+        );
+    }
+}
@@ -2,13 +2,77 @@
 
 use tonic::Status;
 
+use crate::admission::errors::AdmissionError;
+use crate::enrollment::errors::EnrollmentError;
+use crate::managers::instance::errors::InstanceError;
 use crate::managers::node_agent::errors::NodeAgentError;
+use crate::placement::errors::PlacementError;
+use crate::tls::errors::IdentityError;
 
 impl From<NodeAgentError> for Status {
     fn from(value: NodeAgentError) -> Self {
         match value {
             NodeAgentError::NotFound(_) => Self::not_found(value.to_string()),
             NodeAgentError::AlreadyExists(_) => Self::already_exists(value.to_string()),
+            NodeAgentError::TooManyAgents(_) => Self::resource_exhausted(value.to_string()),
+            NodeAgentError::InvalidAddress(_) => Self::invalid_argument(value.to_string()),
+            NodeAgentError::InvalidProperty(_) => Self::invalid_argument(value.to_string()),
+        }
+    }
+}
+
+impl From<PlacementError> for Status {
+    fn from(value: PlacementError) -> Self {
+        match value {
+            PlacementError::NoAvailableNode(_) => Self::unavailable(value.to_string()),
+            PlacementError::InsufficientResources(_) => Self::resource_exhausted(value.to_string()),
+            PlacementError::NoMatchingNode(_) => Self::failed_precondition(value.to_string()),
+            PlacementError::InvalidConstraints(_) => Self::invalid_argument(value.to_string()),
+        }
+    }
+}
+
+impl From<InstanceError> for Status {
+    fn from(value: InstanceError) -> Self {
+        match value {
+            InstanceError::NotFound(_) => Self::not_found(value.to_string()),
+            InstanceError::AlreadyExists(_) => Self::already_exists(value.to_string()),
+        }
+    }
+}
+
+impl From<IdentityError> for Status {
+    fn from(value: IdentityError) -> Self {
+        match value {
+            IdentityError::MissingCertificate | IdentityError::InvalidCertificate(_) => {
+                Self::unauthenticated(value.to_string())
+            }
+            IdentityError::MissingCommonName
+            | IdentityError::NotAdmin(_)
+            | IdentityError::Mismatch { .. } => Self::permission_denied(value.to_string()),
+        }
+    }
+}
+
+impl From<EnrollmentError> for Status {
+    fn from(value: EnrollmentError) -> Self {
+        match value {
+            EnrollmentError::InvalidToken => Self::unauthenticated(value.to_string()),
+            EnrollmentError::ExpiredToken(_) | EnrollmentError::UsedToken(_) => {
+                Self::permission_denied(value.to_string())
+            }
+            EnrollmentError::InvalidCsr(_) => Self::invalid_argument(value.to_string()),
+            EnrollmentError::TlsDisabled => Self::failed_precondition(value.to_string()),
+            EnrollmentError::Storage(_) => Self::internal("Failed to access bootstrap tokens"),
+        }
+    }
+}
+
+impl From<AdmissionError> for Status {
+    fn from(value: AdmissionError) -> Self {
+        match value {
+            AdmissionError::IdNotAllowed(_) => Self::permission_denied(value.to_string()),
+            AdmissionError::RateLimited(..) => Self::resource_exhausted(value.to_string()),
         }
     }
 }
@@ -0,0 +1,271 @@
+//! Health of the scheduler services, reported through the gRPC health checking protocol.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use orka_proto::{
+    scheduler_agent::{
+        lifecycle_service_server::LifecycleServiceServer,
+        status_update_service_server::StatusUpdateServiceServer,
+    },
+    scheduler_controller::scheduling_service_server::SchedulingServiceServer,
+};
+use tonic::server::NamedService;
+use tonic_health::pb::health_server::{Health, HealthServer};
+use tonic_health::server::HealthReporter;
+use tonic_health::ServingStatus;
+use tracing::{event, Level};
+
+use crate::managers::node_agent::manager::NodeAgentManager;
+
+use super::{
+    agent_lifecycle_service::AgentLifecycleSvc, agent_status_update_service::AgentStatusUpdateSvc,
+    controller_scheduling_service::ControllerSchedulingSvc, shutdown::Drain,
+};
+
+/// Delay between two checks of the number of agents in the cluster.
+const CHECK_PERIOD: Duration = Duration::from_secs(1);
+
+/// Name of the overall health of the server in the health checking protocol.
+const SERVER: &str = "";
+
+/// Name of the service used by the node agents to join and leave the cluster.
+const LIFECYCLE_SERVICE: &str = <LifecycleServiceServer<AgentLifecycleSvc> as NamedService>::NAME;
+
+/// Name of the service used by the node agents to report the status of their node.
+const STATUS_UPDATE_SERVICE: &str =
+    <StatusUpdateServiceServer<AgentStatusUpdateSvc> as NamedService>::NAME;
+
+/// Name of the service used by the controller to schedule workloads.
+const SCHEDULING_SERVICE: &str =
+    <SchedulingServiceServer<ControllerSchedulingSvc> as NamedService>::NAME;
+
+/// Create the reporter of the health of the scheduler services, with the linked health service.
+/// Every service is reported as not serving until the scheduler is ready.
+pub async fn health_reporter() -> (HealthReporter, HealthServer<impl Health>) {
+    let (mut reporter, service) = tonic_health::server::health_reporter();
+
+    for name in [
+        SERVER,
+        LIFECYCLE_SERVICE,
+        STATUS_UPDATE_SERVICE,
+        SCHEDULING_SERVICE,
+    ] {
+        reporter
+            .set_service_status(name, ServingStatus::NotServing)
+            .await;
+    }
+
+    (reporter, service)
+}
+
+/// The health monitor, reporting the services of the scheduler as serving once it is ready.
+///
+/// The services of the node agents are serving as soon as the monitor runs, so that agents can
+/// join the cluster. The scheduling service, and the server as a whole, are only serving while
+/// enough agents are healthy to run workloads.
+pub struct HealthMonitor {
+    /// The reporter of the health of the services.
+    reporter: HealthReporter,
+
+    /// The shared instance of the node agent manager.
+    node_agent_manager: Arc<NodeAgentManager>,
+
+    /// The number of healthy agents required for the scheduling service to be serving.
+    min_ready_agents: usize,
+
+    /// The drain of the scheduler, after which no service is serving anymore.
+    drain: Arc<Drain>,
+}
+
+impl HealthMonitor {
+    /// Create a new `HealthMonitor`.
+    ///
+    /// # Arguments
+    ///
+    /// * `reporter` - The reporter of the health of the services.
+    /// * `manager` - The shared instance of the node agent manager.
+    /// * `min_ready_agents` - The number of healthy agents required for the scheduling service
+    ///   to be serving.
+    /// * `drain` - The drain of the scheduler.
+    pub fn new(
+        reporter: HealthReporter,
+        manager: Arc<NodeAgentManager>,
+        min_ready_agents: usize,
+        drain: Arc<Drain>,
+    ) -> Self {
+        Self {
+            reporter,
+            node_agent_manager: manager,
+            min_ready_agents,
+            drain,
+        }
+    }
+
+    /// Report the services of the node agents as serving, then periodically update the health
+    /// of the scheduling service depending on the number of healthy agents, until the scheduler
+    /// drains.
+    pub async fn run(mut self) {
+        for name in [LIFECYCLE_SERVICE, STATUS_UPDATE_SERVICE] {
+            self.reporter
+                .set_service_status(name, ServingStatus::Serving)
+                .await;
+        }
+
+        let mut interval = tokio::time::interval(CHECK_PERIOD);
+        let mut was_ready = None;
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = self.drain.started() => break,
+            }
+
+            let agents = self
+                .node_agent_manager
+                .agents()
+                .filter(|entry| entry.is_healthy())
+                .count();
+            let is_ready = agents >= self.min_ready_agents;
+
+            if was_ready == Some(is_ready) {
+                continue;
+            }
+
+            let status = if is_ready {
+                event!(
+                    Level::INFO,
+                    agents,
+                    "Enough node agents joined the cluster, the scheduler is ready"
+                );
+                ServingStatus::Serving
+            } else {
+                event!(
+                    Level::INFO,
+                    agents,
+                    min_ready_agents = self.min_ready_agents,
+                    "Waiting for node agents to join the cluster, the scheduler is not ready"
+                );
+                ServingStatus::NotServing
+            };
+
+            for name in [SERVER, SCHEDULING_SERVICE] {
+                self.reporter.set_service_status(name, status).await;
+            }
+
+            was_ready = Some(is_ready);
+        }
+
+        // The scheduler is shutting down, clients should use another one
+        for name in [
+            SERVER,
+            LIFECYCLE_SERVICE,
+            STATUS_UPDATE_SERVICE,
+            SCHEDULING_SERVICE,
+        ] {
+            self.reporter
+                .set_service_status(name, ServingStatus::NotServing)
+                .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::transport::{Channel, Server};
+    use tonic_health::pb::{
+        health_check_response::ServingStatus as CheckStatus, health_client::HealthClient,
+        HealthCheckRequest,
+    };
+
+    use super::*;
+    use crate::managers::node_agent::properties::NodeProperties;
+
+    /// Serve the health service over the loopback interface, returning a client of it.
+    ///
+    /// # Arguments
+    ///
+    /// * `service` - The health service to serve.
+    async fn health_client(service: HealthServer<impl Health>) -> HealthClient<Channel> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(
+            Server::builder()
+                .add_service(service)
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let channel = Channel::from_shared(format!("http://{}", address))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+
+        HealthClient::new(channel)
+    }
+
+    /// Get the health of the server and of the scheduling service.
+    ///
+    /// # Arguments
+    ///
+    /// * `client` - The client of the health service.
+    async fn readiness(client: &mut HealthClient<Channel>) -> [CheckStatus; 2] {
+        let mut statuses = [CheckStatus::Unknown; 2];
+
+        for (status, service) in statuses.iter_mut().zip([SERVER, SCHEDULING_SERVICE]) {
+            let response = client
+                .check(HealthCheckRequest {
+                    service: service.to_string(),
+                })
+                .await
+                .unwrap();
+            *status = CheckStatus::from_i32(response.into_inner().status).unwrap();
+        }
+
+        statuses
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reports_readiness_from_the_healthy_agents() {
+        let (reporter, service) = health_reporter().await;
+        let manager = Arc::new(NodeAgentManager::new());
+        let drain = Arc::new(Drain::new());
+
+        manager
+            .add_agent("node-1", "", NodeProperties::default())
+            .unwrap();
+
+        tokio::spawn(
+            HealthMonitor::new(reporter, Arc::clone(&manager), 2, Arc::clone(&drain)).run(),
+        );
+        let mut client = health_client(service).await;
+
+        // Not enough agents joined the cluster yet
+        tokio::time::sleep(CHECK_PERIOD * 3).await;
+        assert_eq!(
+            readiness(&mut client).await,
+            [CheckStatus::NotServing, CheckStatus::NotServing]
+        );
+
+        manager
+            .add_agent("node-2", "", NodeProperties::default())
+            .unwrap();
+
+        tokio::time::sleep(CHECK_PERIOD * 2).await;
+        assert_eq!(
+            readiness(&mut client).await,
+            [CheckStatus::Serving, CheckStatus::Serving]
+        );
+
+        drain.start();
+
+        tokio::time::sleep(CHECK_PERIOD * 2).await;
+        assert_eq!(
+            readiness(&mut client).await,
+            [CheckStatus::NotServing, CheckStatus::NotServing]
+        );
+    }
+}
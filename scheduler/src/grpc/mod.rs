@@ -1,7 +1,11 @@
 //! Services and server for gRPC.
 
+pub mod admin_service;
 pub mod agent_lifecycle_service;
 pub mod agent_status_update_service;
 pub mod controller_scheduling_service;
+pub mod conversions;
 pub mod errors;
+pub mod health;
 pub mod server;
+pub mod shutdown;
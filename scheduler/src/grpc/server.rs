@@ -1,35 +1,99 @@
 //! The gRPC server for interacting with the Orka scheduler.
 
 use std::net::SocketAddr;
-use std::sync::{Arc, Mutex};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
+use crate::admission::policy::AdmissionPolicy;
+use crate::admission::rate_limit::{AgentRateLimiter, RateLimit};
+use crate::managers::events::broadcaster::ClusterEvents;
+use crate::managers::instance::manager::InstanceManager;
+use crate::managers::node_agent::client_pool::AgentClientPool;
 use crate::managers::node_agent::manager::NodeAgentManager;
+use crate::managers::node_agent::reaper::{HeartbeatReaper, HeartbeatTimeouts};
+use crate::managers::pending::queue::{PendingQueue, QueueOptions};
+use crate::metrics::registry::SchedulerMetrics;
+use crate::metrics::server::MetricsServer;
+use crate::placement::placer::Placer;
+use crate::state::log::StateLog;
 use anyhow::{Context, Result};
 use orka_proto::{
+    scheduler_admin::admin_service_server::AdminServiceServer,
     scheduler_agent::{
         lifecycle_service_server::LifecycleServiceServer,
         status_update_service_server::StatusUpdateServiceServer,
     },
     scheduler_controller::scheduling_service_server::SchedulingServiceServer,
 };
-use tonic::transport::{Identity, Server, ServerTlsConfig};
+use tonic::transport::Server;
 use tower_http::trace::TraceLayer;
 use tracing::{event, Level};
 
+use crate::enrollment::token::TokenStore;
+use crate::tls::acceptor::{server_config, tls_incoming};
+use crate::tls::guard::ClientCertificateLayer;
+use crate::tls::issuer::CertificateIssuer;
 use crate::tls::manager::TlsManager;
+use crate::tls::resolver::ReloadableCertResolver;
+use crate::tls::rotation::{CertificateRotator, RotationOptions};
 
 use super::{
-    agent_lifecycle_service::AgentLifecycleSvc, agent_status_update_service::AgentStatusUpdateSvc,
+    admin_service::AdminSvc,
+    agent_lifecycle_service::AgentLifecycleSvc,
+    agent_status_update_service::AgentStatusUpdateSvc,
     controller_scheduling_service::ControllerSchedulingSvc,
+    health::{health_reporter, HealthMonitor},
+    shutdown::{serve_until_drained, shutdown_signal, Drain},
 };
 
+/// The options of the scheduler services.
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// The options of the rotation of the TLS identity.
+    pub rotation: RotationOptions,
+
+    /// The timeouts applied to the heartbeats of the node agents.
+    pub heartbeat_timeouts: HeartbeatTimeouts,
+
+    /// The options of the queue of the workloads waiting for a node agent.
+    pub queue: QueueOptions,
+
+    /// The number of healthy node agents required for the scheduling service to be serving.
+    pub min_ready_agents: usize,
+
+    /// The port of the plaintext listener only serving the health checking protocol, if any.
+    pub health_bind_port: Option<u16>,
+
+    /// The port of the HTTP listener exporting the Prometheus metrics, if any.
+    pub metrics_bind_port: Option<u16>,
+
+    /// The time given to the in-flight calls and status streams to finish on shutdown.
+    pub shutdown_timeout: Duration,
+
+    /// The admission policy of the node agents.
+    pub admission: AdmissionPolicy,
+
+    /// The rate limit of the node statuses sent by each node agent, if any.
+    pub status_rate_limit: Option<RateLimit>,
+}
+
 /// The gRPC server manager for the scheduler.
 pub struct GrpcServer {
+    /// The data directory of the scheduler.
+    data_dir: PathBuf,
+
     /// The address to bind the gRPC server to.
     bind_socket_address: SocketAddr,
 
     /// The TLS manager, if it is enabled.
     tls_manager: Option<TlsManager>,
+
+    /// The placer choosing the node agent of each workload.
+    placer: Placer,
+
+    /// The options of the scheduler services.
+    options: ServerOptions,
 }
 
 impl GrpcServer {
@@ -37,13 +101,19 @@ impl GrpcServer {
     ///
     /// # Arguments
     ///
+    /// * `data_dir` - The data directory of the scheduler.
     /// * `bind_address` - The address to bind the gRPC server to.
     /// * `bind_port` - The port to bind the gRPC server to.
     /// * `tls_manager` - The TLS manager, if TLS is enabled.
+    /// * `placer` - The placer choosing the node agent of each workload.
+    /// * `options` - The options of the scheduler services.
     pub fn new(
+        data_dir: &Path,
         bind_address: String,
         bind_port: u16,
         tls_manager: Option<TlsManager>,
+        placer: Placer,
+        options: ServerOptions,
     ) -> Result<Self> {
         let bind_socket_address = format!("{}:{}", bind_address, bind_port)
             .parse()
@@ -55,57 +125,274 @@ impl GrpcServer {
             })?;
 
         Ok(Self {
+            data_dir: data_dir.to_path_buf(),
             bind_socket_address,
             tls_manager,
+            placer,
+            options,
         })
     }
 
     /// Start the gRPC server.
-    pub async fn start_server(&self) -> Result<()> {
+    ///
+    /// When TLS is enabled, the identity of the server is reloaded in the background whenever the
+    /// certificate files change, without interrupting established connections.
+    ///
+    /// The health of the services is reported through the gRPC health checking protocol. They
+    /// are not serving while the TLS secrets are loading, and the scheduling service is not
+    /// serving until enough node agents joined the cluster.
+    ///
+    /// On `SIGTERM` or `SIGINT`, the scheduler drains: new workloads and agents are refused, the
+    /// controllers are told to use another scheduler, and the in-flight calls are given some time
+    /// to finish. The cluster state is persisted before returning.
+    pub async fn start_server(self) -> Result<()> {
+        // Report the services as not serving until the scheduler is ready
+        let (health_reporter, health_svc) = health_reporter().await;
+
+        if let Some(port) = self.options.health_bind_port {
+            let health_address = SocketAddr::new(self.bind_socket_address.ip(), port);
+
+            event!(Level::INFO, %health_address, "Starting gRPC health server");
+
+            let server = Server::builder()
+                .add_service(health_svc.clone())
+                .serve(health_address);
+
+            tokio::spawn(async move {
+                if let Err(err) = server.await {
+                    event!(Level::ERROR, error = %err, "The gRPC health server stopped");
+                }
+            });
+        }
+
+        // Load the TLS secrets, generating them if needed
+        let tls_manager = match self.tls_manager {
+            Some(mut tls_manager) => Some(
+                tokio::task::spawn_blocking(move || {
+                    tls_manager.populate_secrets().map(|_| tls_manager)
+                })
+                .await
+                .with_context(|| "Unable to load the TLS secrets")?
+                .with_context(|| "Unable to provide the certificate and private key for TLS")?,
+            ),
+            None => None,
+        };
+
         // Configure the server
         event!(Level::INFO, bind_address = %self.bind_socket_address, "Starting gRPC server");
 
-        let mut server_builder = Server::builder().layer(TraceLayer::new_for_grpc());
+        // With TLS, only the enrollment may be called without a client certificate
+        let mut server_builder = Server::builder()
+            .layer(TraceLayer::new_for_grpc())
+            .layer(ClientCertificateLayer::new(tls_manager.is_some()));
 
-        // If the TLS manager is present, configure the gRPC server with TLS
-        if let Some(tls_manager) = &self.tls_manager {
-            event!(Level::DEBUG, "Configuring the gRPC server for TLS");
+        // Restore the state of the cluster from the previous run
+        let (state_log, state) = StateLog::open(&self.data_dir)
+            .with_context(|| "Unable to restore the persisted cluster state")?;
+        let state_log = Arc::new(state_log);
 
-            let cert_data = tls_manager
-                .cert_data()
-                .with_context(|| "The certificate data is missing")?;
+        // Drain the scheduler once it is asked to shut down
+        let drain = Arc::new(Drain::new());
+        let signal =
+            shutdown_signal().with_context(|| "Unable to register the shutdown signal handlers")?;
 
-            let key_data = tls_manager
-                .key_data()
-                .with_context(|| "The private key data is missing")?;
+        // Create the metrics recorded by the services
+        let metrics = Arc::new(
+            SchedulerMetrics::new().with_context(|| "Unable to register the scheduler metrics")?,
+        );
 
-            let tls_config =
-                ServerTlsConfig::new().identity(Identity::from_pem(cert_data, key_data));
+        // Create the shared node agent manager
+        let node_agent_manager =
+            Arc::new(NodeAgentManager::restore(&state, Arc::clone(&state_log)));
 
-            server_builder = server_builder
-                .tls_config(tls_config)
-                .with_context(|| "Unable to configure TLS with the gRPC server")?;
+        // Create the shared pool of clients for the node agents, including the restored ones
+        let agent_client_pool = Arc::new(AgentClientPool::new());
+
+        for (agent_id, agent) in state.agents() {
+            match AgentClientPool::parse_endpoint(&agent.address) {
+                Ok(endpoint) => agent_client_pool.insert(agent_id, endpoint),
+                Err(err) => event!(
+                    Level::WARN,
+                    agent_id,
+                    error = %err,
+                    "Restored node agent has an invalid address"
+                ),
+            }
         }
 
-        // Create the shared node agent manager
-        let node_agent_manager = Arc::new(Mutex::new(NodeAgentManager::new()));
+        // Prepare the enrollment of new agents, which requires the certificate authority
+        let token_store = Arc::new(TokenStore::new(&self.data_dir));
+        let certificate_issuer = match &tls_manager {
+            Some(tls_manager) => Some(Arc::new(
+                CertificateIssuer::new(tls_manager)
+                    .with_context(|| "Unable to load the certificate authority")?,
+            )),
+            None => None,
+        };
+
+        // Create the shared workload instance manager
+        let instance_manager = Arc::new(InstanceManager::restore(&state, Arc::clone(&state_log)));
+
+        // Broadcast the changes of the cluster to the watching controllers
+        let cluster_events = Arc::new(ClusterEvents::new());
+
+        // Watch the heartbeats of the agents in the background
+        tokio::spawn(
+            HeartbeatReaper::new(
+                Arc::clone(&node_agent_manager),
+                Arc::clone(&agent_client_pool),
+                Arc::clone(&instance_manager),
+                self.options.heartbeat_timeouts,
+                Arc::clone(&metrics),
+                Arc::clone(&cluster_events),
+            )
+            .run(),
+        );
+
+        // Retry the workloads that no agent can run yet in the background
+        let pending_queue = Arc::new(PendingQueue::new(self.options.queue));
+        let scheduling_svc = Arc::new(ControllerSchedulingSvc::new(
+            Arc::clone(&node_agent_manager),
+            Arc::clone(&agent_client_pool),
+            Arc::clone(&instance_manager),
+            self.placer,
+            Arc::clone(&pending_queue),
+            Arc::clone(&metrics),
+            Arc::clone(&drain),
+            Arc::clone(&cluster_events),
+        ));
+
+        tokio::spawn(Arc::clone(&scheduling_svc).run_pending_queue());
+
+        {
+            let drain = Arc::clone(&drain);
+            let scheduling_svc = Arc::clone(&scheduling_svc);
+
+            tokio::spawn(async move {
+                signal.await;
+
+                drain.start();
+                scheduling_svc.notify_shutdown();
+            });
+        }
+
+        // Export the metrics over HTTP, if enabled
+        if let Some(port) = self.options.metrics_bind_port {
+            let metrics_address = SocketAddr::new(self.bind_socket_address.ip(), port);
+            let metrics_server = MetricsServer::new(
+                Arc::clone(&metrics),
+                Arc::clone(&node_agent_manager),
+                Arc::clone(&pending_queue),
+            );
+
+            tokio::spawn(async move {
+                if let Err(err) = metrics_server.serve(metrics_address).await {
+                    event!(Level::ERROR, error = ?err, "The metrics server stopped");
+                }
+            });
+        }
 
         // Configure the router
         let router = server_builder
             .add_service(LifecycleServiceServer::new(AgentLifecycleSvc::new(
                 Arc::clone(&node_agent_manager),
+                Arc::clone(&agent_client_pool),
+                Arc::clone(&instance_manager),
+                scheduling_svc.status_senders(),
+                Arc::clone(&pending_queue),
+                token_store,
+                certificate_issuer,
+                tls_manager.is_some(),
+                Arc::clone(&metrics),
+                Arc::clone(&drain),
+                Arc::clone(&cluster_events),
+                self.options.admission.clone(),
             )))
             .add_service(StatusUpdateServiceServer::new(AgentStatusUpdateSvc::new(
                 Arc::clone(&node_agent_manager),
+                Arc::clone(&instance_manager),
+                scheduling_svc.status_senders(),
+                Arc::clone(&pending_queue),
+                tls_manager.is_some(),
+                metrics,
+                cluster_events,
+                self.options.status_rate_limit.map(AgentRateLimiter::new),
             )))
-            .add_service(SchedulingServiceServer::new(ControllerSchedulingSvc::new()));
+            .add_service(AdminServiceServer::new(AdminSvc::new(
+                Arc::clone(&node_agent_manager),
+                Arc::clone(&instance_manager),
+                Arc::clone(&scheduling_svc),
+                pending_queue,
+                tls_manager.is_some(),
+            )))
+            .add_service(SchedulingServiceServer::from_arc(scheduling_svc))
+            .add_service(health_svc);
+
+        // Report the services as serving once agents can join
+        tokio::spawn(
+            HealthMonitor::new(
+                health_reporter,
+                Arc::clone(&node_agent_manager),
+                self.options.min_ready_agents,
+                Arc::clone(&drain),
+            )
+            .run(),
+        );
 
         event!(Level::DEBUG, "The gRPC server was configured successfully");
 
-        router
-            .serve(self.bind_socket_address)
+        match tls_manager {
+            // If the TLS manager is present, serve the connections through TLS
+            Some(tls_manager) => {
+                event!(Level::DEBUG, "Configuring the gRPC server for TLS");
+
+                let resolver = Arc::new(
+                    ReloadableCertResolver::new(
+                        tls_manager
+                            .cert_data()
+                            .with_context(|| "The certificate data is missing")?,
+                        tls_manager
+                            .key_data()
+                            .with_context(|| "The private key data is missing")?,
+                    )
+                    .with_context(|| "Unable to load the TLS identity")?,
+                );
+
+                let tls_config = server_config(
+                    Arc::clone(&resolver),
+                    tls_manager
+                        .ca_cert_data()
+                        .with_context(|| "The certificate authority data is missing")?,
+                )
+                .with_context(|| "Unable to configure TLS with the gRPC server")?;
+
+                let incoming = tls_incoming(self.bind_socket_address, tls_config).await?;
+
+                // Keep the identity up to date while serving
+                tokio::spawn(
+                    CertificateRotator::new(tls_manager, resolver, self.options.rotation).run(),
+                );
+
+                let serve = router.serve_with_incoming_shutdown(incoming, drain.started());
+                serve_until_drained(serve, &drain, self.options.shutdown_timeout).await
+            }
+            None => {
+                let serve = router.serve_with_shutdown(self.bind_socket_address, drain.started());
+                serve_until_drained(serve, &drain, self.options.shutdown_timeout).await
+            }
+        }
+        .with_context(|| "An error occurred while serving gRPC requests")?;
+
+        // Persist the state of the cluster before exiting
+        tokio::task::spawn_blocking(move || state_log.persist())
             .await
-            .with_context(|| "An error occurred while serving gRPC requests")?;
+            .with_context(|| "Unable to persist the cluster state")?
+            .with_context(|| "Unable to persist the cluster state")?;
+
+        event!(
+            Level::INFO,
+            "Persisted the cluster state, the scheduler stopped"
+        );
 
         Ok(())
     }
@@ -0,0 +1,133 @@
+//! Graceful shutdown of the scheduler services.
+
+use std::future::Future;
+use std::time::Duration;
+
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tonic::Status;
+use tracing::{event, Level};
+
+/// The drain of the scheduler, started when the scheduler is asked to shut down. While
+/// draining, the services refuse the calls that would start new work, and in-flight calls are
+/// given some time to finish.
+pub struct Drain {
+    /// Whether the drain started.
+    sender: watch::Sender<bool>,
+}
+
+impl Drain {
+    /// Create a new `Drain`, not started yet.
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+
+        Self { sender }
+    }
+
+    /// Start the drain. Starting it again has no effect.
+    pub fn start(&self) {
+        self.sender
+            .send_if_modified(|draining| !std::mem::replace(draining, true));
+    }
+
+    /// Check whether the drain started.
+    pub fn is_draining(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Wait until the drain starts.
+    pub async fn started(&self) {
+        let mut receiver = self.sender.subscribe();
+
+        // The sender lives as long as `self`, so waiting cannot fail
+        let _ = receiver.wait_for(|draining| *draining).await;
+    }
+
+    /// Refuse a call starting new work if the drain started.
+    ///
+    /// # Errors
+    ///
+    /// * The scheduler is shutting down.
+    #[allow(clippy::result_large_err)]
+    pub fn check_accepting(&self) -> Result<(), Status> {
+        if self.is_draining() {
+            return Err(shutting_down());
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Drain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Create the error telling a client that the scheduler is shutting down, so that it retries
+/// with another scheduler.
+pub fn shutting_down() -> Status {
+    Status::unavailable("The scheduler is shutting down, retry with another scheduler")
+}
+
+/// Register the handlers of the signals asking the scheduler to shut down, `SIGTERM` and
+/// `SIGINT`, returning a future completing once one of them is received.
+///
+/// # Errors
+///
+/// * The signal handlers could not be registered.
+pub fn shutdown_signal() -> std::io::Result<impl Future<Output = ()>> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+
+    Ok(async move {
+        let name = tokio::select! {
+            _ = terminate.recv() => "SIGTERM",
+            _ = interrupt.recv() => "SIGINT",
+        };
+
+        event!(Level::INFO, signal = name, "Received shutdown signal");
+    })
+}
+
+/// Serve until the server stops on its own or the drain completes. Once the drain starts, the
+/// in-flight calls and status streams are given some time to finish before being closed.
+///
+/// # Arguments
+///
+/// * `serve` - The future serving the calls, shutting down gracefully once the drain starts.
+/// * `drain` - The drain of the scheduler.
+/// * `timeout` - The time given to the in-flight calls to finish once the drain started.
+///
+/// # Errors
+///
+/// * The server failed before the end of the drain.
+pub async fn serve_until_drained<E>(
+    serve: impl Future<Output = Result<(), E>>,
+    drain: &Drain,
+    timeout: Duration,
+) -> Result<(), E> {
+    tokio::pin!(serve);
+
+    tokio::select! {
+        result = &mut serve => return result,
+        _ = drain.started() => {}
+    }
+
+    event!(
+        Level::INFO,
+        timeout_secs = timeout.as_secs(),
+        "Waiting for the in-flight calls and status streams to finish"
+    );
+
+    match tokio::time::timeout(timeout, serve).await {
+        Ok(result) => result,
+        Err(_) => {
+            event!(
+                Level::WARN,
+                "Some calls did not finish before the shutdown timeout and were interrupted"
+            );
+            Ok(())
+        }
+    }
+}
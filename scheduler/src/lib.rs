@@ -0,0 +1,13 @@
+//! Scheduler service for the Orka container orchestration system.
+
+pub mod admission;
+pub mod args;
+pub mod config;
+pub mod enrollment;
+pub mod grpc;
+pub mod managers;
+pub mod metrics;
+pub mod placement;
+pub mod simulation;
+pub mod state;
+pub mod tls;
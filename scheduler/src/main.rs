@@ -1,30 +1,57 @@
-mod args;
-mod grpc;
-mod managers;
-mod tls;
-
 use anyhow::Context;
-use clap::Parser;
 use std::error;
 use std::path::Path;
+use std::time::Duration;
 use tracing::{event, Level};
 use tracing_log::AsTrace;
 
-use crate::args::CliArguments;
-use crate::grpc::server::GrpcServer;
-use crate::tls::config::TlsConfig;
-use crate::tls::manager::TlsManager;
+use orka_scheduler::args::{CliArguments, Command};
+use orka_scheduler::config::FileConfig;
+use orka_scheduler::enrollment::commands::run_token_command;
+use orka_scheduler::grpc::server::{GrpcServer, ServerOptions};
+use orka_scheduler::placement::placer::Placer;
+use orka_scheduler::tls::commands::run_admin_certificate_command;
+use orka_scheduler::tls::config::TlsConfig;
+use orka_scheduler::tls::manager::TlsManager;
 
 /// The application entry point.
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn error::Error>> {
     // Parse the configuration and configure logger verbosity
-    let args = CliArguments::parse();
+    let args = CliArguments::load()?;
+
+    // Dump the effective configuration instead of running the scheduler, if requested
+    if args.print_config {
+        print!("{}", FileConfig::from(&args).to_toml()?);
+
+        return Ok(());
+    }
 
     tracing_subscriber::fmt()
         .with_max_level(args.verbose.log_level_filter().as_trace())
         .init();
 
+    // Run the subcommand instead of the scheduler, if any
+    match &args.command {
+        Some(Command::Token(command)) => {
+            args.prepare_directories()?;
+            run_token_command(command, Path::new(&args.data_dir))?;
+
+            return Ok(());
+        }
+        Some(Command::AdminCertificate { name, out_dir }) => {
+            let tls_config = TlsConfig::new(
+                &Path::new(&args.data_dir).join("tls/"),
+                false,
+                args.certificate_options(),
+            );
+            run_admin_certificate_command(TlsManager::new(tls_config), name, Path::new(out_dir))?;
+
+            return Ok(());
+        }
+        None => (),
+    }
+
     event!(
         Level::INFO,
         app_name = env!("CARGO_PKG_NAME"),
@@ -39,26 +66,45 @@ async fn main() -> Result<(), Box<dyn error::Error>> {
 
     let tls_manager = if !args.no_tls {
         let tls_base_dir = &Path::new(&args.data_dir).join("tls/");
-        let tls_config = TlsConfig::new(tls_base_dir, !args.no_tls_secret_generation);
+        let tls_config = TlsConfig::new(
+            tls_base_dir,
+            !args.no_tls_secret_generation,
+            args.certificate_options(),
+        );
 
         event!(Level::TRACE, ?tls_config, "Loaded TLS configuration");
 
         tls_config.prepare_directory()?;
 
-        let mut tls_manager = TlsManager::new(tls_config);
-        tls_manager
-            .populate_secrets()
-            .with_context(|| "Unable to provide the certificate and private key for TLS")?;
-
-        Some(tls_manager)
+        // The secrets are loaded by the server, which reports itself as not serving meanwhile
+        Some(TlsManager::new(tls_config))
     } else {
         event!(Level::WARN, "The server will run in an unsecure mode because TLS was disabled. Are you certain whatever you're doing is worth it?");
         None
     };
 
     // Start the gRPC server
-    let grpc_server = GrpcServer::new(args.grpc_bind_address, args.grpc_bind_port, tls_manager)
-        .with_context(|| "Unable to create the gRPC server manager")?;
+    let options = ServerOptions {
+        rotation: args.rotation_options()?,
+        heartbeat_timeouts: args.heartbeat_timeouts(),
+        queue: args.queue_options()?,
+        min_ready_agents: args.min_ready_agents,
+        health_bind_port: args.health_bind_port,
+        metrics_bind_port: args.metrics_bind_port,
+        shutdown_timeout: Duration::from_secs(args.shutdown_timeout),
+        admission: args.admission_policy()?,
+        status_rate_limit: args.status_rate_limit()?,
+    };
+    let placer = Placer::new(args.scheduling_strategy.build(), args.overcommit_ratios()?);
+    let grpc_server = GrpcServer::new(
+        Path::new(&args.data_dir),
+        args.grpc_bind_address,
+        args.grpc_bind_port,
+        tls_manager,
+        placer,
+        options,
+    )
+    .with_context(|| "Unable to create the gRPC server manager")?;
 
     grpc_server.start_server().await?;
 
@@ -0,0 +1,116 @@
+//! Broadcaster of the changes of the cluster to the controllers watching them.
+
+use tokio::sync::broadcast;
+use tracing::{event, Level};
+
+use crate::metrics::registry::LeaveCause;
+
+/// Number of events buffered for each watcher. A watcher falling further behind misses events.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// A change of the cluster.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClusterEvent {
+    /// A node agent joined the cluster, or joined it again after a restart of the scheduler.
+    NodeJoined {
+        /// The ID of the agent.
+        node_id: String,
+        /// The address of the agent's `WorkloadService`.
+        address: String,
+    },
+    /// A node agent left the cluster.
+    NodeLeft {
+        /// The ID of the agent.
+        node_id: String,
+        /// Why the agent left.
+        cause: LeaveCause,
+    },
+    /// A node agent stopped sending heartbeats, workloads are not placed on it anymore.
+    NodeUnhealthy {
+        /// The ID of the agent.
+        node_id: String,
+    },
+    /// An unhealthy node agent sent a heartbeat again.
+    NodeRecovered {
+        /// The ID of the agent.
+        node_id: String,
+    },
+    /// A workload instance was forgotten because its node agent left the cluster.
+    InstanceLost {
+        /// The ID of the instance.
+        instance_id: String,
+        /// The ID of the agent the instance was running on.
+        node_id: String,
+    },
+}
+
+/// The broadcaster of the changes of the cluster, sending every event to all the watchers
+/// subscribed when it happens.
+pub struct ClusterEvents {
+    /// The sender of the events to the watchers.
+    sender: broadcast::Sender<ClusterEvent>,
+}
+
+impl ClusterEvents {
+    /// Create a new `ClusterEvents` broadcaster, without watchers.
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+        Self { sender }
+    }
+
+    /// Send an event to the current watchers. The event is dropped if nobody watches.
+    ///
+    /// # Arguments
+    ///
+    /// * `cluster_event` - The change of the cluster.
+    pub fn publish(&self, cluster_event: ClusterEvent) {
+        event!(
+            Level::DEBUG,
+            event = ?cluster_event,
+            watchers = self.sender.receiver_count(),
+            "Publishing a change of the cluster"
+        );
+
+        let _ = self.sender.send(cluster_event);
+    }
+
+    /// Watch the events published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<ClusterEvent> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for ClusterEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sends_the_events_published_after_subscribing() {
+        let cluster_events = ClusterEvents::new();
+        let unhealthy = ClusterEvent::NodeUnhealthy {
+            node_id: "agent".to_string(),
+        };
+        let left = ClusterEvent::NodeLeft {
+            node_id: "agent".to_string(),
+            cause: LeaveCause::Eviction,
+        };
+
+        cluster_events.publish(unhealthy);
+
+        let mut first = cluster_events.subscribe();
+        let mut second = cluster_events.subscribe();
+
+        cluster_events.publish(left.clone());
+
+        assert_eq!(first.try_recv().unwrap(), left);
+        assert_eq!(second.try_recv().unwrap(), left);
+        assert!(first.try_recv().is_err());
+    }
+}
@@ -0,0 +1,3 @@
+//! Changes of the cluster watched by the controllers.
+
+pub mod broadcaster;
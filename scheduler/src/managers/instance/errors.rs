@@ -0,0 +1,15 @@
+//! Workload instance errors.
+
+use thiserror::Error;
+
+/// Instance error enum to have self-explanatory and compact errors.
+#[derive(Error, Debug)]
+pub enum InstanceError {
+    /// The workload instance could not be found.
+    #[error("Instance not found: `{0}`")]
+    NotFound(String),
+
+    /// The workload instance is already placed on a node.
+    #[error("Instance already exists: `{0}`")]
+    AlreadyExists(String),
+}
@@ -0,0 +1,635 @@
+//! Instance manager used to remember where workload instances run.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Instant;
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+
+use tracing::{event, Level};
+
+use crate::placement::requirements::WorkloadRequirements;
+use crate::state::cluster::{ClusterState, StateEvent};
+use crate::state::log::StateLog;
+
+use super::errors::InstanceError;
+use super::report::{
+    InstanceState, Reconciliation, ReportedInstance, ORPHAN_PRIORITY, REPORT_GRACE_PERIOD,
+};
+
+/// The placement of a workload instance on a node.
+#[derive(Debug, Clone)]
+pub struct InstancePlacement {
+    /// The ID of the node agent running the instance.
+    agent_id: String,
+
+    /// The resources reserved for the instance on the node.
+    resources: WorkloadRequirements,
+
+    /// The priority of the instance's workload.
+    priority: i32,
+
+    /// When the instance was placed on the node, or restored from the persisted state.
+    placed_at: Instant,
+
+    /// The last state of the instance seen by the scheduler, if any.
+    state: Option<InstanceState>,
+}
+
+impl InstancePlacement {
+    /// Get the ID of the node agent running the instance.
+    pub fn agent_id(&self) -> &str {
+        &self.agent_id
+    }
+
+    /// Get the resources reserved for the instance on the node.
+    pub fn resources(&self) -> &WorkloadRequirements {
+        &self.resources
+    }
+
+    /// Get the priority of the instance's workload.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Get the last state of the instance seen by the scheduler, if any.
+    pub fn state(&self) -> Option<InstanceState> {
+        self.state
+    }
+}
+
+/// The resources reserved on a node by the workload instances placed on it.
+#[derive(Debug, Default)]
+struct AgentReservation {
+    /// Number of instances placed on the node.
+    instances: usize,
+
+    /// Sum of the resources reserved by the instances.
+    resources: WorkloadRequirements,
+}
+
+/// A workload instance reported by a node agent without being registered.
+#[derive(Debug)]
+struct UnclaimedInstance {
+    /// The ID of the node agent reporting the instance.
+    agent_id: String,
+
+    /// When the node agent first reported the instance.
+    first_seen: Instant,
+}
+
+/// The instance manager, recording the node each workload instance was placed on and the
+/// resources it reserves there. The placements are stored in sharded maps, so the manager can be
+/// shared without a global lock.
+pub struct InstanceManager {
+    /// The placements of the workload instances, indexed by instance ID.
+    instances: DashMap<String, InstancePlacement>,
+
+    /// The resources reserved on each node agent, indexed by agent ID. They are always updated
+    /// while holding the entry of the instance reserving them.
+    reservations: DashMap<String, AgentReservation>,
+
+    /// The instances reported by the node agents without being registered, indexed by instance
+    /// ID. They are adopted if they are still reported after the grace period.
+    unclaimed: DashMap<String, UnclaimedInstance>,
+
+    /// The log recording the changes of the placements, if the state is persisted.
+    state_log: Option<Arc<StateLog>>,
+}
+
+impl InstanceManager {
+    /// Create a new `InstanceManager`, without persisting the placements.
+    pub fn new() -> Self {
+        Self {
+            instances: DashMap::new(),
+            reservations: DashMap::new(),
+            unclaimed: DashMap::new(),
+            state_log: None,
+        }
+    }
+
+    /// Create an `InstanceManager` holding the placements of a persisted cluster state, and
+    /// recording their changes in the state log.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The persisted state of the cluster.
+    /// * `state_log` - The log recording the changes of the cluster state.
+    pub fn restore(state: &ClusterState, state_log: Arc<StateLog>) -> Self {
+        let manager = Self {
+            state_log: Some(state_log),
+            ..Self::new()
+        };
+
+        for (id, instance) in state.instances() {
+            manager.reserve(&instance.agent_id, &instance.resources);
+            manager.instances.insert(
+                id.clone(),
+                InstancePlacement {
+                    agent_id: instance.agent_id.clone(),
+                    resources: instance.resources.clone(),
+                    priority: instance.priority,
+                    placed_at: Instant::now(),
+                    state: None,
+                },
+            );
+        }
+
+        if !manager.instances.is_empty() {
+            event!(
+                Level::INFO,
+                count = manager.instances.len(),
+                "Restored instance placements from the persisted state"
+            );
+        }
+
+        manager
+    }
+
+    /// Reserve resources on a node agent.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    /// * `resources` - The resources to reserve.
+    fn reserve(&self, agent_id: &str, resources: &WorkloadRequirements) {
+        let mut reservation = self.reservations.entry(agent_id.to_string()).or_default();
+
+        reservation.instances += 1;
+        reservation.resources.add(resources);
+    }
+
+    /// Release resources reserved on a node agent.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    /// * `resources` - The resources to release.
+    fn release(&self, agent_id: &str, resources: &WorkloadRequirements) {
+        let Entry::Occupied(mut e) = self.reservations.entry(agent_id.to_string()) else {
+            return;
+        };
+
+        let reservation = e.get_mut();
+        reservation.instances = reservation.instances.saturating_sub(1);
+        reservation.resources.subtract(resources);
+
+        // Drop the totals with the last instance, so that rounding errors do not accumulate
+        if reservation.instances == 0 {
+            e.remove();
+        }
+    }
+
+    /// Get the resources reserved on a node agent by the instances placed on it.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    pub fn reserved(&self, agent_id: &str) -> WorkloadRequirements {
+        self.reservations
+            .get(agent_id)
+            .map(|reservation| reservation.resources.clone())
+            .unwrap_or_default()
+    }
+
+    /// Record a change of the placements in the state log, if the state is persisted.
+    ///
+    /// # Arguments
+    ///
+    /// * `state_event` - The change to record.
+    fn record(&self, state_event: StateEvent) {
+        if let Some(state_log) = &self.state_log {
+            state_log.append(state_event);
+        }
+    }
+
+    /// Record the placement of a workload instance on a node, reserving its resources there
+    /// until it is forgotten.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the instance.
+    /// * `agent_id` - The ID of the node agent running the instance.
+    /// * `resources` - The resources to reserve for the instance on the node.
+    /// * `priority` - The priority of the instance's workload.
+    ///
+    /// # Errors
+    ///
+    /// * The instance is already placed on a node.
+    pub fn add_instance(
+        &self,
+        id: &str,
+        agent_id: &str,
+        resources: WorkloadRequirements,
+        priority: i32,
+    ) -> Result<(), InstanceError> {
+        if let Entry::Vacant(e) = self.instances.entry(id.to_string()) {
+            event!(
+                Level::DEBUG,
+                instance_id = id,
+                agent_id,
+                ?resources,
+                priority,
+                "Recording instance placement"
+            );
+
+            self.reserve(agent_id, &resources);
+            self.record(StateEvent::InstancePlaced {
+                id: id.to_string(),
+                agent_id: agent_id.to_string(),
+                resources: resources.clone(),
+                priority,
+            });
+
+            let _placement = e.insert(InstancePlacement {
+                agent_id: agent_id.to_string(),
+                resources,
+                priority,
+                placed_at: Instant::now(),
+                state: None,
+            });
+            Ok(())
+        } else {
+            Err(InstanceError::AlreadyExists(id.to_string()))
+        }
+    }
+
+    /// Get the placement of a workload instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the instance.
+    ///
+    /// # Errors
+    ///
+    /// * The instance is unknown.
+    pub fn placement(&self, id: &str) -> Result<InstancePlacement, InstanceError> {
+        self.instances
+            .get(id)
+            .map(|placement| placement.clone())
+            .ok_or_else(|| InstanceError::NotFound(id.to_string()))
+    }
+
+    /// Forget a workload instance if it exists, releasing its resources and returning its
+    /// placement.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the instance.
+    pub fn remove_instance(&self, id: &str) -> Option<InstancePlacement> {
+        let Entry::Occupied(e) = self.instances.entry(id.to_string()) else {
+            return None;
+        };
+
+        event!(
+            Level::DEBUG,
+            instance_id = id,
+            "Forgetting instance placement"
+        );
+
+        self.release(&e.get().agent_id, &e.get().resources);
+        self.record(StateEvent::InstanceRemoved { id: id.to_string() });
+        Some(e.remove())
+    }
+
+    /// Get the workload instances placed on a node agent, with their IDs.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    pub fn agent_instances(&self, agent_id: &str) -> Vec<(String, InstancePlacement)> {
+        self.instances
+            .iter()
+            .filter(|entry| entry.agent_id == agent_id)
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    /// Forget the workload instances placed on a node agent, returning their IDs.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    pub fn remove_agent_instances(&self, agent_id: &str) -> Vec<String> {
+        let ids: Vec<String> = self
+            .agent_instances(agent_id)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+
+        for id in &ids {
+            self.remove_instance(id);
+        }
+
+        self.unclaimed
+            .retain(|_, unclaimed| unclaimed.agent_id != agent_id);
+
+        ids
+    }
+
+    /// Record the last state of a workload instance seen by the scheduler. Returns whether the
+    /// state changed, `false` if the instance is unknown.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the instance.
+    /// * `state` - The state of the instance on its node.
+    pub fn record_state(&self, id: &str, state: InstanceState) -> bool {
+        self.instances
+            .get_mut(id)
+            .is_some_and(|mut placement| placement.state.replace(state) != Some(state))
+    }
+
+    /// Reconcile the registry with the complete list of the instances run by a node agent:
+    ///
+    /// * The registered instances that terminated are forgotten.
+    /// * The registered instances the agent does not run anymore are forgotten once they were
+    ///   placed for longer than the grace period.
+    /// * The orphan instances the agent runs without being registered, such as the instances
+    ///   placed before the persisted state was lost, are adopted once they were reported for
+    ///   longer than the grace period. Their resource usage is reserved, and they get the lowest
+    ///   priority.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    /// * `reported` - The instances run by the node agent.
+    /// * `now` - The current time.
+    pub fn reconcile(
+        &self,
+        agent_id: &str,
+        reported: Vec<ReportedInstance>,
+        now: Instant,
+    ) -> Reconciliation {
+        let mut reconciliation = Reconciliation::default();
+        let reported_ids: HashSet<String> = reported
+            .iter()
+            .map(|instance| instance.id.clone())
+            .collect();
+
+        for instance in reported {
+            let registered_on = self
+                .instances
+                .get(&instance.id)
+                .map(|placement| placement.agent_id.clone());
+
+            match registered_on {
+                Some(registered_on) if registered_on == agent_id => {
+                    if self.record_state(&instance.id, instance.state) {
+                        if instance.state == InstanceState::Terminated
+                            && self.remove_instance(&instance.id).is_some()
+                        {
+                            reconciliation.released = true;
+                        }
+
+                        reconciliation.changed.push(instance.id);
+                    }
+                }
+                Some(registered_on) => event!(
+                    Level::WARN,
+                    instance_id = instance.id,
+                    agent_id,
+                    registered_on,
+                    "Node agent reported an instance placed on another node"
+                ),
+                None if instance.state == InstanceState::Terminated => {
+                    self.unclaimed.remove(&instance.id);
+                }
+                None => {
+                    if self.claim(agent_id, &instance, now) {
+                        reconciliation.adopted.push(instance.id);
+                    }
+                }
+            }
+        }
+
+        for (id, placement) in self.agent_instances(agent_id) {
+            let placed_for = now.saturating_duration_since(placement.placed_at);
+
+            if !reported_ids.contains(&id)
+                && placed_for >= REPORT_GRACE_PERIOD
+                && self.remove_instance(&id).is_some()
+            {
+                reconciliation.lost.push(id);
+                reconciliation.released = true;
+            }
+        }
+
+        // Forget the unclaimed instances that stopped before being adopted
+        self.unclaimed
+            .retain(|id, unclaimed| unclaimed.agent_id != agent_id || reported_ids.contains(id));
+
+        reconciliation
+    }
+
+    /// Track an orphan instance reported by a node agent, adopting it if it was reported for
+    /// longer than the grace period. Returns whether the instance was adopted.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    /// * `instance` - The orphan instance.
+    /// * `now` - The current time.
+    fn claim(&self, agent_id: &str, instance: &ReportedInstance, now: Instant) -> bool {
+        let first_seen = {
+            let mut unclaimed = self
+                .unclaimed
+                .entry(instance.id.clone())
+                .or_insert_with(|| UnclaimedInstance {
+                    agent_id: agent_id.to_string(),
+                    first_seen: now,
+                });
+
+            // The instance moved to another node, wait for it again
+            if unclaimed.agent_id != agent_id {
+                *unclaimed = UnclaimedInstance {
+                    agent_id: agent_id.to_string(),
+                    first_seen: now,
+                };
+            }
+
+            unclaimed.first_seen
+        };
+
+        if now.saturating_duration_since(first_seen) < REPORT_GRACE_PERIOD {
+            return false;
+        }
+
+        self.unclaimed.remove(&instance.id);
+
+        let adopted = self
+            .add_instance(
+                &instance.id,
+                agent_id,
+                instance.resource_usage.clone(),
+                ORPHAN_PRIORITY,
+            )
+            .is_ok();
+
+        if adopted {
+            self.record_state(&instance.id, instance.state);
+        }
+
+        adopted
+    }
+}
+
+impl Default for InstanceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create an instance reported by a node agent.
+    fn reported(id: &str, state: InstanceState) -> ReportedInstance {
+        ReportedInstance {
+            id: id.to_string(),
+            state,
+            resource_usage: WorkloadRequirements::from_limits(10, 256, 0),
+        }
+    }
+
+    #[test]
+    fn reserves_the_resources_of_the_placed_instances() {
+        let manager = InstanceManager::new();
+        let small = WorkloadRequirements::from_limits(10, 128, 0);
+        let large = WorkloadRequirements::from_limits(30, 512, 1024);
+
+        manager
+            .add_instance("a", "agent", small.clone(), 0)
+            .unwrap();
+        manager
+            .add_instance("b", "agent", large.clone(), 5)
+            .unwrap();
+        manager
+            .add_instance("c", "other", large.clone(), 0)
+            .unwrap();
+
+        let mut total = small.clone();
+        total.add(&large);
+        assert_eq!(manager.reserved("agent"), total);
+        assert_eq!(manager.reserved("other"), large);
+        assert_eq!(manager.reserved("unknown"), WorkloadRequirements::default());
+
+        let placement = manager.placement("b").unwrap();
+        assert_eq!(placement.agent_id(), "agent");
+        assert_eq!(placement.priority(), 5);
+        assert_eq!(placement.state(), None);
+
+        let mut ids: Vec<_> = manager
+            .agent_instances("agent")
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        // Removing the instances releases their resources
+        assert_eq!(manager.remove_instance("b").unwrap().resources(), &large);
+        assert_eq!(manager.reserved("agent"), small);
+
+        assert_eq!(manager.remove_agent_instances("agent"), vec!["a"]);
+        assert_eq!(manager.reserved("agent"), WorkloadRequirements::default());
+        assert_eq!(manager.reserved("other"), large);
+    }
+
+    #[test]
+    fn refuses_duplicate_and_unknown_instances() {
+        let manager = InstanceManager::new();
+        let resources = WorkloadRequirements::from_limits(10, 128, 0);
+
+        manager
+            .add_instance("a", "agent", resources.clone(), 0)
+            .unwrap();
+
+        assert!(matches!(
+            manager.add_instance("a", "other", resources.clone(), 0),
+            Err(InstanceError::AlreadyExists(_))
+        ));
+        assert_eq!(manager.placement("a").unwrap().agent_id(), "agent");
+        assert_eq!(manager.reserved("other"), WorkloadRequirements::default());
+
+        assert!(matches!(
+            manager.placement("unknown"),
+            Err(InstanceError::NotFound(_))
+        ));
+        assert!(manager.remove_instance("unknown").is_none());
+        assert!(!manager.record_state("unknown", InstanceState::Running));
+
+        assert!(manager.record_state("a", InstanceState::Running));
+        assert!(!manager.record_state("a", InstanceState::Running));
+        assert_eq!(manager.reserved("agent"), resources);
+    }
+
+    #[test]
+    fn forgets_terminated_and_lost_instances() {
+        let manager = InstanceManager::new();
+        let resources = WorkloadRequirements::from_limits(20, 512, 0);
+
+        for id in ["running", "terminated", "lost"] {
+            manager
+                .add_instance(id, "agent", resources.clone(), 0)
+                .unwrap();
+        }
+
+        // Missing instances may still be created during the grace period
+        let reconciliation = manager.reconcile(
+            "agent",
+            vec![
+                reported("running", InstanceState::Running),
+                reported("terminated", InstanceState::Terminated),
+            ],
+            Instant::now(),
+        );
+
+        assert_eq!(reconciliation.changed, vec!["running", "terminated"]);
+        assert!(reconciliation.lost.is_empty());
+        assert!(reconciliation.released);
+        assert!(manager.placement("terminated").is_err());
+
+        let reconciliation = manager.reconcile(
+            "agent",
+            vec![reported("running", InstanceState::Running)],
+            Instant::now() + REPORT_GRACE_PERIOD,
+        );
+
+        assert!(reconciliation.changed.is_empty());
+        assert_eq!(reconciliation.lost, vec!["lost"]);
+        assert_eq!(
+            manager.placement("running").unwrap().state(),
+            Some(InstanceState::Running)
+        );
+        assert_eq!(manager.reserved("agent"), resources);
+    }
+
+    #[test]
+    fn adopts_orphans_reported_after_the_grace_period() {
+        let manager = InstanceManager::new();
+        let now = Instant::now();
+        let orphan = || vec![reported("orphan", InstanceState::Running)];
+
+        let reconciliation = manager.reconcile("agent", orphan(), now);
+        assert!(reconciliation.adopted.is_empty());
+
+        // An instance stopping before the end of the grace period is never adopted
+        manager.reconcile("agent", Vec::new(), now + REPORT_GRACE_PERIOD / 2);
+        let reconciliation = manager.reconcile("agent", orphan(), now + REPORT_GRACE_PERIOD);
+        assert!(reconciliation.adopted.is_empty());
+
+        let reconciliation = manager.reconcile("agent", orphan(), now + REPORT_GRACE_PERIOD * 2);
+        assert_eq!(reconciliation.adopted, vec!["orphan"]);
+
+        let placement = manager.placement("orphan").unwrap();
+        assert_eq!(placement.agent_id(), "agent");
+        assert_eq!(placement.priority(), ORPHAN_PRIORITY);
+        assert_eq!(
+            manager.reserved("agent"),
+            WorkloadRequirements::from_limits(10, 256, 0)
+        );
+    }
+}
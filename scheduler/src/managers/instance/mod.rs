@@ -0,0 +1,5 @@
+//! Modules for workload instance placement tracking.
+
+pub mod errors;
+pub mod manager;
+pub mod report;
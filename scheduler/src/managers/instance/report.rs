@@ -0,0 +1,51 @@
+//! Workload instances reported by the node agents with the status of their node.
+
+use std::time::Duration;
+
+use crate::placement::requirements::WorkloadRequirements;
+
+/// Time during which a registered instance may be missing from the reports of its node agent,
+/// and an unknown instance may be reported, before the registry is changed. It covers the
+/// instances being created or stopped while the agent sends its status.
+pub const REPORT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// The priority given to the orphan instances adopted by the registry, the lowest one so that
+/// they are the first evicted to make room for the workloads of the controller.
+pub const ORPHAN_PRIORITY: i32 = i32::MIN;
+
+/// The state of a workload instance on its node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstanceState {
+    /// The instance is being created.
+    Waiting,
+    /// The instance is running.
+    Running,
+    /// The instance stopped.
+    Terminated,
+}
+
+/// A workload instance reported by a node agent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportedInstance {
+    /// The ID of the instance.
+    pub id: String,
+    /// The state of the instance on the node.
+    pub state: InstanceState,
+    /// The resources used by the instance on the node.
+    pub resource_usage: WorkloadRequirements,
+}
+
+/// How the registry changed after a node agent reported its instances.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reconciliation {
+    /// The IDs of the registered instances whose state changed since it was last seen.
+    pub changed: Vec<String>,
+    /// The IDs of the orphan instances, running on the node without being registered, that
+    /// were adopted by the registry.
+    pub adopted: Vec<String>,
+    /// The IDs of the registered instances that the node agent does not run anymore, which
+    /// were forgotten.
+    pub lost: Vec<String>,
+    /// Whether resources were released on the node, by terminated or lost instances.
+    pub released: bool,
+}
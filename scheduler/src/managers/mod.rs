@@ -1,3 +1,6 @@
 //! Managers for the scheduler subsystems.
 
+pub mod events;
+pub mod instance;
 pub mod node_agent;
+pub mod pending;
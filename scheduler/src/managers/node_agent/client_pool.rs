@@ -0,0 +1,144 @@
+//! Pool of gRPC clients used to drive the workloads of the node agents.
+
+use dashmap::DashMap;
+use orka_proto::node_agent::workload_service_client::WorkloadServiceClient;
+use tonic::transport::{Channel, Endpoint};
+use tracing::{event, Level};
+
+use super::errors::NodeAgentError;
+
+/// The pool of clients for the `WorkloadService` of the node agents, with one channel per
+/// registered agent. The pool can be shared between tasks without a global lock.
+pub struct AgentClientPool {
+    /// The clients of the node agents, indexed by agent ID.
+    clients: DashMap<String, WorkloadServiceClient<Channel>>,
+}
+
+impl AgentClientPool {
+    /// Create a new, empty `AgentClientPool`.
+    pub fn new() -> Self {
+        Self {
+            clients: DashMap::new(),
+        }
+    }
+
+    /// Parse the address advertised by a node agent into an endpoint. Addresses without a scheme
+    /// are considered to be plain HTTP addresses.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address advertised by the agent, e.g. `10.0.0.2:50052`.
+    ///
+    /// # Errors
+    ///
+    /// * The address is empty or is not a valid URI.
+    pub fn parse_endpoint(address: &str) -> Result<Endpoint, NodeAgentError> {
+        if address.is_empty() {
+            return Err(NodeAgentError::InvalidAddress(address.to_string()));
+        }
+
+        let uri = if address.contains("://") {
+            address.to_string()
+        } else {
+            format!("http://{}", address)
+        };
+
+        Endpoint::from_shared(uri).map_err(|_| NodeAgentError::InvalidAddress(address.to_string()))
+    }
+
+    /// Open a channel to a node agent, replacing any previous channel of the agent. The
+    /// connection is only established when the first request is sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the agent.
+    /// * `endpoint` - The endpoint of the agent's `WorkloadService`.
+    pub fn insert(&self, id: &str, endpoint: Endpoint) {
+        event!(
+            Level::DEBUG,
+            agent_id = id,
+            address = %endpoint.uri(),
+            "Opening channel to node agent"
+        );
+
+        self.clients.insert(
+            id.to_string(),
+            WorkloadServiceClient::new(endpoint.connect_lazy()),
+        );
+    }
+
+    /// Close the channel to a node agent, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the agent.
+    pub fn remove(&self, id: &str) {
+        if self.clients.remove(id).is_some() {
+            event!(Level::DEBUG, agent_id = id, "Closed channel to node agent");
+        }
+    }
+
+    /// Get a client for a node agent. Clients share the channel of their agent and are cheap to
+    /// create.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the agent.
+    ///
+    /// # Errors
+    ///
+    /// * No channel is open to the agent.
+    pub fn client(&self, id: &str) -> Result<WorkloadServiceClient<Channel>, NodeAgentError> {
+        self.clients
+            .get(id)
+            .map(|client| client.clone())
+            .ok_or_else(|| NodeAgentError::NotFound(id.to_string()))
+    }
+}
+
+impl Default for AgentClientPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_agent_addresses() {
+        let endpoint = AgentClientPool::parse_endpoint("10.0.0.2:50052").unwrap();
+        assert_eq!(endpoint.uri().to_string(), "http://10.0.0.2:50052/");
+
+        let endpoint = AgentClientPool::parse_endpoint("https://node-1:50052").unwrap();
+        assert_eq!(endpoint.uri().to_string(), "https://node-1:50052/");
+
+        for address in ["", "not an address"] {
+            assert!(matches!(
+                AgentClientPool::parse_endpoint(address),
+                Err(NodeAgentError::InvalidAddress(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn keeps_a_client_per_agent() {
+        let pool = AgentClientPool::new();
+
+        assert!(matches!(
+            pool.client("node-1"),
+            Err(NodeAgentError::NotFound(_))
+        ));
+
+        let endpoint = AgentClientPool::parse_endpoint("10.0.0.2:50052").unwrap();
+        pool.insert("node-1", endpoint.clone());
+        pool.insert("node-1", endpoint);
+        assert!(pool.client("node-1").is_ok());
+        assert!(pool.client("node-2").is_err());
+
+        pool.remove("node-1");
+        pool.remove("node-2");
+        assert!(pool.client("node-1").is_err());
+    }
+}
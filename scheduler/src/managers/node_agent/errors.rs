@@ -12,4 +12,16 @@ pub enum NodeAgentError {
     /// The node agent is already registered.
     #[error("Agent already exists: `{0}`")]
     AlreadyExists(String),
+
+    /// The cluster already holds the maximum number of node agents.
+    #[error("The cluster is full, it holds the maximum number of {0} node agents")]
+    TooManyAgents(usize),
+
+    /// The address advertised by the node agent is not valid.
+    #[error("Invalid agent address: `{0}`")]
+    InvalidAddress(String),
+
+    /// A label or taint declared by the node agent is not valid.
+    #[error("Invalid agent property: {0}")]
+    InvalidProperty(String),
 }
@@ -1,50 +1,216 @@
 //! Node agent manager used to store agents.
 
-use crate::managers::node_agent::metrics::{NodeAgent, NodeCpu, NodeMemory};
+use crate::managers::node_agent::metrics::{NodeAgent, NodeMetrics};
+use crate::managers::node_agent::properties::NodeProperties;
+use crate::state::cluster::{ClusterState, StateEvent};
+use crate::state::log::StateLog;
 use anyhow::Result;
-use std::collections::hash_map;
-use std::collections::HashMap;
+use chrono::Local;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::{event, Level};
 
 use super::errors::NodeAgentError;
 
+/// How a status update changed a node agent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatusChange {
+    /// Whether the agent was unhealthy and is healthy again.
+    pub recovered: bool,
+    /// Whether workloads can be placed on the agent while they could not before, such as after
+    /// it joined or recovered.
+    pub gained_capacity: bool,
+}
+
+/// The agents whose heartbeats were late during a check.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeartbeatCheck {
+    /// The IDs of the agents marked as unhealthy.
+    pub unhealthy: Vec<String>,
+    /// The IDs of the agents removed from the cluster.
+    pub evicted: Vec<String>,
+}
+
 /// The node agent manager, handling all agents that contact the scheduler.
+///
+/// The agents are stored in a sharded map, so the manager can be shared between tasks without
+/// a global lock: updates of different agents rarely contend, and updates of the same agent are
+/// serialized by the shard holding it.
 pub struct NodeAgentManager {
     /// The list of node agents that are currently active in the cluster.
-    agents: HashMap<String, NodeAgent>,
+    agents: DashMap<String, NodeAgent>,
+
+    /// The number of agents in the cluster. Unlike the length of the map, it is updated
+    /// atomically, so that concurrent joins cannot exceed the maximum number of agents.
+    count: AtomicUsize,
+
+    /// The log recording the changes of the agents, if the state is persisted.
+    state_log: Option<Arc<StateLog>>,
 }
 
 impl NodeAgentManager {
-    /// Create a new `NodeAgentManager` to manage the different node agents.
+    /// Create a new `NodeAgentManager` to manage the different node agents, without persisting
+    /// them.
     pub fn new() -> Self {
         Self {
-            agents: HashMap::new(),
+            agents: DashMap::new(),
+            count: AtomicUsize::new(0),
+            state_log: None,
+        }
+    }
+
+    /// Create a `NodeAgentManager` holding the agents of a persisted cluster state, and recording
+    /// its changes in the state log. The restored agents are pending until they communicate with
+    /// the scheduler again.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The persisted state of the cluster.
+    /// * `state_log` - The log recording the changes of the cluster state.
+    pub fn restore(state: &ClusterState, state_log: Arc<StateLog>) -> Self {
+        let agents: DashMap<_, _> = state
+            .agents()
+            .map(|(id, agent)| {
+                let mut restored = NodeAgent::restored(
+                    &agent.address,
+                    agent.properties.clone(),
+                    agent.metrics.clone(),
+                );
+                restored.set_cordoned(agent.cordoned);
+
+                (id.clone(), restored)
+            })
+            .collect();
+
+        if !agents.is_empty() {
+            event!(
+                Level::INFO,
+                count = agents.len(),
+                "Restored agents from the persisted state, waiting for them to reconnect"
+            );
+        }
+
+        Self {
+            count: AtomicUsize::new(agents.len()),
+            agents,
+            state_log: Some(state_log),
         }
     }
 
-    /// Add a new agent to the managed list to keep track of it.
+    /// Record a change of the agents in the state log, if the state is persisted. Changes of an
+    /// agent must be recorded while holding its entry, so that they are recorded in order.
+    ///
+    /// # Arguments
+    ///
+    /// * `state_event` - The change to record.
+    fn record(&self, state_event: StateEvent) {
+        if let Some(state_log) = &self.state_log {
+            state_log.append(state_event);
+        }
+    }
+
+    /// Add a new agent to the managed list to keep track of it. An agent restored from the
+    /// persisted state is accepted again, as it is reconnecting after a restart of the scheduler.
     ///
     /// # Arguments
     ///
     /// * `id` - The ID of the agent to add.
+    /// * `address` - The address of the agent's `WorkloadService`.
+    /// * `properties` - The labels and taints declared by the agent.
     ///
     /// # Errors
     ///
     /// * An agent with the same ID is already in the cluster.
-    pub fn add_agent(&mut self, id: &str) -> Result<&NodeAgent, NodeAgentError> {
-        if let hash_map::Entry::Vacant(e) = self.agents.entry(id.to_string()) {
-            // No other agent has this ID
-            event!(
-                Level::INFO,
-                agent_id = e.key(),
-                "Adding new agent to the cluster"
-            );
+    pub fn add_agent(
+        &self,
+        id: &str,
+        address: &str,
+        properties: NodeProperties,
+    ) -> Result<(), NodeAgentError> {
+        self.add_agent_within(id, address, properties, None)
+    }
+
+    /// Add a new agent to the managed list, unless the cluster already holds the maximum number
+    /// of agents. The limit is checked and the agent counted in a single atomic operation, so
+    /// that agents joining concurrently cannot exceed it. An agent restored from the persisted
+    /// state is accepted again, as it is already counted.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the agent to add.
+    /// * `address` - The address of the agent's `WorkloadService`.
+    /// * `properties` - The labels and taints declared by the agent.
+    /// * `max_agents` - The maximum number of agents in the cluster, if any.
+    ///
+    /// # Errors
+    ///
+    /// * An agent with the same ID is already in the cluster.
+    /// * The agent is new and the cluster already holds the maximum number of agents.
+    pub fn add_agent_within(
+        &self,
+        id: &str,
+        address: &str,
+        properties: NodeProperties,
+        max_agents: Option<usize>,
+    ) -> Result<(), NodeAgentError> {
+        match self.agents.entry(id.to_string()) {
+            Entry::Vacant(e) => {
+                // Reserve a place for the agent while holding its entry
+                self.count
+                    .fetch_update(
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                        |count| match max_agents {
+                            Some(max_agents) if count >= max_agents => None,
+                            _ => Some(count + 1),
+                        },
+                    )
+                    .map_err(|_| NodeAgentError::TooManyAgents(max_agents.unwrap_or_default()))?;
+
+                // No other agent has this ID
+                event!(
+                    Level::INFO,
+                    agent_id = e.key(),
+                    "Adding new agent to the cluster"
+                );
+
+                self.record(StateEvent::AgentJoined {
+                    id: id.to_string(),
+                    address: address.to_string(),
+                    properties: properties.clone(),
+                });
+                let _agent = e.insert(NodeAgent::new(address, properties));
+            }
+            Entry::Occupied(mut e) if e.get().is_pending() => {
+                event!(
+                    Level::INFO,
+                    agent_id = e.key(),
+                    previous_address = e.get().address(),
+                    address,
+                    "Restored agent joined the cluster again"
+                );
 
-            Ok(e.insert(NodeAgent::new()))
-        } else {
-            // Reject agent as the ID is already registered
-            Err(NodeAgentError::AlreadyExists(id.to_string()))
+                self.record(StateEvent::AgentJoined {
+                    id: id.to_string(),
+                    address: address.to_string(),
+                    properties: properties.clone(),
+                });
+
+                let agent = e.get_mut();
+                agent.set_address(address);
+                agent.set_properties(properties);
+                agent.heartbeat();
+            }
+            Entry::Occupied(_) => {
+                // Reject agent as the ID is already registered
+                return Err(NodeAgentError::AlreadyExists(id.to_string()));
+            }
         }
+
+        Ok(())
     }
 
     /// Remove an agent if it exists, returning it.
@@ -52,42 +218,436 @@ impl NodeAgentManager {
     /// # Arguments
     ///
     /// * `id` - The ID of the agent to remove.
-    pub fn remove_agent(&mut self, id: &str) -> Option<NodeAgent> {
-        let removed_agent = self.agents.remove(id);
+    pub fn remove_agent(&self, id: &str) -> Option<NodeAgent> {
+        self.remove_agent_if(id, |_| true)
+    }
 
-        if removed_agent.is_some() {
-            event!(
-                Level::INFO,
-                agent_id = id,
-                "Removing agent from the cluster"
-            );
+    /// Remove an agent if it exists and matches a condition, returning it. The condition is
+    /// checked while holding the agent, so that it cannot change in between.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the agent to remove.
+    /// * `condition` - The condition the agent must match.
+    fn remove_agent_if(
+        &self,
+        id: &str,
+        condition: impl FnOnce(&NodeAgent) -> bool,
+    ) -> Option<NodeAgent> {
+        let Entry::Occupied(e) = self.agents.entry(id.to_string()) else {
+            return None;
+        };
+
+        if !condition(e.get()) {
+            return None;
         }
 
-        removed_agent
+        event!(
+            Level::INFO,
+            agent_id = id,
+            "Removing agent from the cluster"
+        );
+
+        self.record(StateEvent::AgentLeft { id: id.to_string() });
+        self.count.fetch_sub(1, Ordering::AcqRel);
+        Some(e.remove())
     }
 
-    /// Update the node status for the given agent.
-    /// Metrics are [`NodeCpu`] and [`NodeMemory`].
+    /// Update the node status for the given agent, returning how the agent changed.
+    /// Metrics are described by [`NodeMetrics`].
     ///
     /// # Arguments
     ///
     /// * `id` - The ID of the agent to update.
-    /// * `cpu` - The new CPU metrics of the node, if any.
-    /// * `memory` - The new memory metrics of the node, if any.
+    /// * `metrics` - The new metrics of the node.
     pub fn update_node_status(
-        &mut self,
+        &self,
         id: &str,
-        cpu: Option<NodeCpu>,
-        memory: Option<NodeMemory>,
-    ) -> Result<&mut NodeAgent, NodeAgentError> {
+        metrics: NodeMetrics,
+    ) -> Result<StatusChange, NodeAgentError> {
         event!(Level::TRACE, agent_id = id, "Updating the status of a node");
 
-        let agent = self
+        let mut agent = self
+            .agents
+            .get_mut(id)
+            .ok_or(NodeAgentError::NotFound(id.to_string()))?;
+
+        let recovered = !agent.is_pending() && !agent.is_healthy();
+
+        if agent.is_pending() {
+            event!(
+                Level::INFO,
+                agent_id = id,
+                "Restored agent reconnected after a restart of the scheduler"
+            );
+        } else if recovered {
+            event!(
+                Level::INFO,
+                agent_id = id,
+                "Agent is healthy again after sending a heartbeat"
+            );
+        }
+
+        self.record(StateEvent::AgentMetrics {
+            id: id.to_string(),
+            metrics: metrics.clone(),
+        });
+
+        let could_receive_workloads = agent.can_receive_workloads();
+        agent.update_node_metrics(metrics);
+
+        Ok(StatusChange {
+            recovered,
+            gained_capacity: !could_receive_workloads && agent.can_receive_workloads(),
+        })
+    }
+
+    /// Cordon or uncordon an agent, returning whether its state changed. Workloads are not placed
+    /// on cordoned agents, but the instances already running on them are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the agent.
+    /// * `cordoned` - Whether the agent must be cordoned.
+    ///
+    /// # Errors
+    ///
+    /// * The agent is not in the cluster.
+    pub fn set_cordoned(&self, id: &str, cordoned: bool) -> Result<bool, NodeAgentError> {
+        let mut agent = self
             .agents
             .get_mut(id)
             .ok_or(NodeAgentError::NotFound(id.to_string()))?;
 
-        agent.update_node_metrics(cpu, memory);
-        Ok(agent)
+        if agent.is_cordoned() == cordoned {
+            return Ok(false);
+        }
+
+        event!(
+            Level::INFO,
+            agent_id = id,
+            cordoned,
+            "Changing whether workloads can be placed on the agent"
+        );
+
+        self.record(StateEvent::AgentCordoned {
+            id: id.to_string(),
+            cordoned,
+        });
+        agent.set_cordoned(cordoned);
+
+        Ok(true)
+    }
+
+    /// Check the last heartbeat of every agent, marking as unhealthy the agents that have been
+    /// silent for longer than `unhealthy_timeout` and removing those that have been silent for
+    /// longer than `eviction_timeout`. The IDs of the agents marked as unhealthy and of the
+    /// removed agents are returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `unhealthy_timeout` - The time after which a silent agent is considered unhealthy.
+    /// * `eviction_timeout` - The time after which a silent agent is removed from the cluster.
+    pub fn check_heartbeats(
+        &self,
+        unhealthy_timeout: Duration,
+        eviction_timeout: Duration,
+    ) -> HeartbeatCheck {
+        let silence_of = |agent: &NodeAgent| {
+            // A heartbeat in the future is treated as a heartbeat that just happened
+            (Local::now() - agent.last_heartbeat())
+                .to_std()
+                .unwrap_or_default()
+        };
+
+        let mut silent = Vec::new();
+        let mut unhealthy = Vec::new();
+
+        for mut entry in self.agents.iter_mut() {
+            let silence = silence_of(entry.value());
+
+            if silence >= eviction_timeout {
+                silent.push(entry.key().clone());
+            } else if silence >= unhealthy_timeout && entry.is_healthy() {
+                event!(
+                    Level::WARN,
+                    agent_id = entry.key(),
+                    silence_secs = silence.as_secs(),
+                    "Agent exceeded the heartbeat grace period, marking it as unhealthy"
+                );
+
+                entry.mark_unhealthy();
+                unhealthy.push(entry.key().clone());
+            }
+        }
+
+        // The map cannot be modified while iterating, and agents may have sent a heartbeat since
+        let mut evicted = Vec::new();
+
+        for id in silent {
+            let removed = self.remove_agent_if(&id, |agent| {
+                let silence = silence_of(agent);
+
+                if silence < eviction_timeout {
+                    return false;
+                }
+
+                event!(
+                    Level::WARN,
+                    agent_id = id,
+                    silence_secs = silence.as_secs(),
+                    "Agent exceeded the eviction timeout"
+                );
+                true
+            });
+
+            if removed.is_some() {
+                evicted.push(id);
+            }
+        }
+
+        HeartbeatCheck { unhealthy, evicted }
+    }
+
+    /// Get a copy of an agent of the cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the agent.
+    ///
+    /// # Errors
+    ///
+    /// * The agent is not in the cluster.
+    pub fn agent(&self, id: &str) -> Result<NodeAgent, NodeAgentError> {
+        self.agents
+            .get(id)
+            .map(|agent| agent.clone())
+            .ok_or(NodeAgentError::NotFound(id.to_string()))
+    }
+
+    /// Get whether an agent is in the cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the agent.
+    pub fn contains(&self, id: &str) -> bool {
+        self.agents.contains_key(id)
+    }
+
+    /// Get an iterator over the agents of the cluster and their IDs.
+    ///
+    /// The iterator holds a read lock on the shards it visits, so the manager must not be
+    /// modified while it is alive.
+    pub fn agents(&self) -> dashmap::iter::Iter<'_, String, NodeAgent> {
+        self.agents.iter()
+    }
+
+    /// Get the number of agents in the cluster.
+    pub fn len(&self) -> usize {
+        self.agents.len()
+    }
+
+    /// Get whether the cluster has no agents.
+    pub fn is_empty(&self) -> bool {
+        self.agents.is_empty()
+    }
+}
+
+impl Default for NodeAgentManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Barrier;
+    use std::thread;
+
+    use super::*;
+    use crate::managers::node_agent::metrics::{NodeCpu, NodeMemory};
+
+    /// Create the metrics of a node with a given CPU load.
+    fn metrics(load: f64) -> NodeMetrics {
+        NodeMetrics {
+            cpu: Some(NodeCpu {
+                load,
+                cores: Vec::new(),
+            }),
+            memory: Some(NodeMemory {
+                total: 1 << 30,
+                free: 1 << 29,
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Sort the IDs of the agents of a heartbeat check, which are found in any order.
+    fn sorted(mut check: HeartbeatCheck) -> HeartbeatCheck {
+        check.unhealthy.sort();
+        check.evicted.sort();
+        check
+    }
+
+    #[test]
+    fn adds_and_removes_agents() {
+        let manager = NodeAgentManager::new();
+
+        manager
+            .add_agent("node-1", "10.0.0.1:50052", NodeProperties::default())
+            .unwrap();
+
+        assert!(matches!(
+            manager.add_agent("node-1", "10.0.0.2:50052", NodeProperties::default()),
+            Err(NodeAgentError::AlreadyExists(_))
+        ));
+        assert_eq!(manager.agent("node-1").unwrap().address(), "10.0.0.1:50052");
+        assert!(manager.contains("node-1"));
+        assert_eq!(manager.len(), 1);
+
+        assert!(manager.remove_agent("node-1").is_some());
+        assert!(manager.remove_agent("node-1").is_none());
+        assert!(matches!(
+            manager.agent("node-1"),
+            Err(NodeAgentError::NotFound(_))
+        ));
+        assert!(matches!(
+            manager.update_node_status("node-1", metrics(10.0)),
+            Err(NodeAgentError::NotFound(_))
+        ));
+        assert!(manager.is_empty());
+    }
+
+    #[test]
+    fn applies_concurrent_status_updates() {
+        let manager = Arc::new(NodeAgentManager::new());
+
+        for i in 0..8 {
+            manager
+                .add_agent(&format!("node-{}", i), "", NodeProperties::default())
+                .unwrap();
+        }
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let manager = Arc::clone(&manager);
+
+                thread::spawn(move || {
+                    let id = format!("node-{}", i);
+                    let mut gained_capacity = 0;
+
+                    for update in 0..=100 {
+                        let change = manager
+                            .update_node_status(&id, metrics(f64::from(update)))
+                            .unwrap();
+                        gained_capacity += usize::from(change.gained_capacity);
+
+                        // Every thread also reads the other agents meanwhile
+                        assert_eq!(manager.agents().count(), 8);
+                    }
+
+                    gained_capacity
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            // Only the first status makes an agent able to receive workloads
+            assert_eq!(handle.join().unwrap(), 1);
+        }
+
+        for i in 0..8 {
+            let agent = manager.agent(&format!("node-{}", i)).unwrap();
+            assert_eq!(agent.cpu().unwrap().load, 100.0);
+            assert!(agent.can_receive_workloads());
+        }
+    }
+
+    #[test]
+    fn marks_silent_agents_unhealthy_then_evicts_them() {
+        let manager = NodeAgentManager::new();
+
+        for id in ["node-1", "node-2"] {
+            manager
+                .add_agent(id, "", NodeProperties::default())
+                .unwrap();
+        }
+
+        let check = manager.check_heartbeats(Duration::ZERO, Duration::MAX);
+        assert_eq!(
+            sorted(check),
+            HeartbeatCheck {
+                unhealthy: vec!["node-1".to_string(), "node-2".to_string()],
+                evicted: Vec::new(),
+            }
+        );
+        assert!(!manager.agent("node-1").unwrap().is_healthy());
+
+        // Agents already unhealthy are not reported again
+        let check = manager.check_heartbeats(Duration::ZERO, Duration::MAX);
+        assert_eq!(check, HeartbeatCheck::default());
+
+        let change = manager.update_node_status("node-1", metrics(10.0)).unwrap();
+        assert!(change.recovered);
+        assert!(manager.agent("node-1").unwrap().is_healthy());
+
+        let check = manager.check_heartbeats(Duration::MAX, Duration::MAX);
+        assert_eq!(check, HeartbeatCheck::default());
+
+        let check = manager.check_heartbeats(Duration::ZERO, Duration::ZERO);
+        assert_eq!(
+            sorted(check),
+            HeartbeatCheck {
+                unhealthy: Vec::new(),
+                evicted: vec!["node-1".to_string(), "node-2".to_string()],
+            }
+        );
+        assert!(manager.is_empty());
+    }
+
+    #[test]
+    fn caps_concurrent_joins_at_the_limit() {
+        let manager = Arc::new(NodeAgentManager::new());
+        let barrier = Arc::new(Barrier::new(16));
+
+        let handles: Vec<_> = (0..16)
+            .map(|i| {
+                let manager = Arc::clone(&manager);
+                let barrier = Arc::clone(&barrier);
+
+                thread::spawn(move || {
+                    barrier.wait();
+                    manager.add_agent_within(
+                        &format!("node-{}", i),
+                        "",
+                        NodeProperties::default(),
+                        Some(4),
+                    )
+                })
+            })
+            .collect();
+
+        let results: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 4);
+        assert!(results
+            .iter()
+            .filter_map(|result| result.as_ref().err())
+            .all(|err| matches!(err, NodeAgentError::TooManyAgents(4))));
+        assert_eq!(manager.len(), 4);
+
+        // A place is freed when an agent leaves
+        let id = manager.agents().next().unwrap().key().clone();
+        manager.remove_agent(&id).unwrap();
+
+        assert!(manager
+            .add_agent_within("node-new", "", NodeProperties::default(), Some(4))
+            .is_ok());
+        assert!(manager
+            .add_agent_within("node-late", "", NodeProperties::default(), Some(4))
+            .is_err());
     }
 }
@@ -1,9 +1,14 @@
 //! Node agent and its metrics.
 
+use std::collections::BTreeMap;
+
 use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+use super::properties::{NodeProperties, NodeTaint};
 
 /// The memory (RAM) information of the node the agent is installed on.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NodeMemory {
     /// Total memory on the machine, in bytes.
     pub total: u64,
@@ -12,51 +17,247 @@ pub struct NodeMemory {
 }
 
 /// The CPU information of the node the agent is installed on.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NodeCpu {
     /// CPU load of the machine, represented by the overall CPU usage average.
     /// Lower bound is `0.0`, upper bound is `100.0`.
     pub load: f64,
+    /// CPU usage of each core of the machine, with the same bounds as `load`.
+    /// Empty if the agent does not report it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cores: Vec<f64>,
+}
+
+/// The information of a disk mounted on the node the agent is installed on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeDisk {
+    /// Path where the disk is mounted.
+    pub mount_point: String,
+    /// Capacity of the disk, in bytes.
+    pub total: u64,
+    /// Available space on the disk, in bytes.
+    pub free: u64,
+}
+
+/// The network throughput of the node the agent is installed on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeNetwork {
+    /// Data received by the machine, in bytes per second.
+    pub received: u64,
+    /// Data sent by the machine, in bytes per second.
+    pub transmitted: u64,
+}
+
+/// The load averages of the node the agent is installed on, as the number of processes running
+/// or waiting for the CPU.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeLoadAverage {
+    /// Load average over the last minute.
+    pub one: f64,
+    /// Load average over the last 5 minutes.
+    pub five: f64,
+    /// Load average over the last 15 minutes.
+    pub fifteen: f64,
+}
+
+/// The metrics reported by a node agent about its node.
+///
+/// Agents may only report some of them, older agents only report the CPU load and the memory.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NodeMetrics {
+    /// The CPU metrics of the node, if any.
+    pub cpu: Option<NodeCpu>,
+    /// The memory metrics of the node, if any.
+    pub memory: Option<NodeMemory>,
+    /// The disks mounted on the node, empty if the agent does not report them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disks: Vec<NodeDisk>,
+    /// The network throughput of the node, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<NodeNetwork>,
+    /// The load averages of the node, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub load_average: Option<NodeLoadAverage>,
+    /// The number of workload instances running on the node, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub running_instances: Option<u32>,
+}
+
+/// The health of a node agent, depending on how recently it communicated with the scheduler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NodeHealth {
+    /// The agent communicated with the scheduler recently.
+    Healthy,
+    /// The agent has not communicated with the scheduler for longer than the grace period.
+    Unhealthy,
+    /// The agent was restored from the persisted state of the cluster and has not communicated
+    /// with the scheduler since it restarted.
+    Pending,
 }
 
 /// The node agent and the information it broadcasts.
 #[derive(Debug, Clone)]
 pub struct NodeAgent {
+    /// The address of the agent's `WorkloadService`.
+    address: String,
+    /// The labels and taints declared by the agent.
+    properties: NodeProperties,
     /// Heartbeat represents the last time the agent communicated with the scheduler.
     /// This is used to determine whether the agent has timed out.
     last_heartbeat: DateTime<Local>,
-    /// The last transmitted memory metrics of the agent's machine.
-    /// `None` only if the metrics were never communicated to the scheduler.
-    memory: Option<NodeMemory>,
-    /// The last transmitted CPU metrics of the agent's machine.
-    /// `None` only if the metrics were never communicated to the scheduler.
-    cpu: Option<NodeCpu>,
+    /// The health of the agent, derived from its last heartbeat.
+    health: NodeHealth,
+    /// The last transmitted metrics of the agent's machine.
+    metrics: NodeMetrics,
+    /// Whether an administrator cordoned the agent, so that no workload is placed on it.
+    cordoned: bool,
 }
 
 impl NodeAgent {
     /// Create a new `NodeAgent`.
-    pub fn new() -> Self {
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address of the agent's `WorkloadService`.
+    /// * `properties` - The labels and taints declared by the agent.
+    pub fn new(address: &str, properties: NodeProperties) -> Self {
         NodeAgent {
+            address: address.to_string(),
+            properties,
             last_heartbeat: Local::now(),
-            memory: None,
-            cpu: None,
+            health: NodeHealth::Healthy,
+            metrics: NodeMetrics::default(),
+            cordoned: false,
         }
     }
 
-    /// Update the agent's last heartbeat.
+    /// Create a `NodeAgent` restored from the persisted state of the cluster. The agent is
+    /// pending until it communicates with the scheduler again.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address of the agent's `WorkloadService`.
+    /// * `properties` - The labels and taints declared by the agent.
+    /// * `metrics` - The last metrics reported by the agent.
+    pub fn restored(address: &str, properties: NodeProperties, metrics: NodeMetrics) -> Self {
+        NodeAgent {
+            address: address.to_string(),
+            properties,
+            last_heartbeat: Local::now(),
+            health: NodeHealth::Pending,
+            metrics,
+            cordoned: false,
+        }
+    }
+
+    /// Get the address of the agent's `WorkloadService`.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Set the address of the agent's `WorkloadService`.
+    pub fn set_address(&mut self, address: &str) {
+        self.address = address.to_string();
+    }
+
+    /// Get the labels declared by the agent.
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        &self.properties.labels
+    }
+
+    /// Get the taints declared by the agent.
+    pub fn taints(&self) -> &[NodeTaint] {
+        &self.properties.taints
+    }
+
+    /// Set the labels and taints declared by the agent.
+    pub fn set_properties(&mut self, properties: NodeProperties) {
+        self.properties = properties;
+    }
+
+    /// Update the agent's last heartbeat, which also makes it healthy again.
     pub fn heartbeat(&mut self) {
         self.last_heartbeat = Local::now();
+        self.health = NodeHealth::Healthy;
+    }
+
+    /// Get the last time the agent communicated with the scheduler.
+    pub fn last_heartbeat(&self) -> DateTime<Local> {
+        self.last_heartbeat
+    }
+
+    /// Get the health of the agent.
+    pub fn health(&self) -> NodeHealth {
+        self.health
+    }
+
+    /// Get whether the agent is healthy and can receive workloads.
+    pub fn is_healthy(&self) -> bool {
+        self.health == NodeHealth::Healthy
+    }
+
+    /// Get whether the agent was restored from the persisted state and has not communicated with
+    /// the scheduler since.
+    pub fn is_pending(&self) -> bool {
+        self.health == NodeHealth::Pending
+    }
+
+    /// Get whether an administrator cordoned the agent, so that no workload is placed on it.
+    pub fn is_cordoned(&self) -> bool {
+        self.cordoned
+    }
+
+    /// Set whether the agent is cordoned. The workloads already placed on the agent are kept.
+    pub fn set_cordoned(&mut self, cordoned: bool) {
+        self.cordoned = cordoned;
+    }
+
+    /// Get whether new workloads may be placed on the agent, which requires it to be healthy and
+    /// not cordoned.
+    pub fn is_schedulable(&self) -> bool {
+        self.is_healthy() && !self.cordoned
+    }
+
+    /// Get whether workloads can be placed on the agent, which requires it to be schedulable and
+    /// to have reported the CPU and memory metrics of its node.
+    pub fn can_receive_workloads(&self) -> bool {
+        self.is_schedulable() && self.cpu().is_some() && self.memory().is_some()
+    }
+
+    /// Mark the agent as unhealthy, until its next heartbeat.
+    pub fn mark_unhealthy(&mut self) {
+        self.health = NodeHealth::Unhealthy;
     }
 
     /// Update the agent's node metrics.
     ///
     /// # Arguments
     ///
-    /// * `cpu` - The metrics related to the CPU.
-    /// * `memory` - The metrics related to memory.
-    pub fn update_node_metrics(&mut self, cpu: Option<NodeCpu>, memory: Option<NodeMemory>) {
+    /// * `metrics` - The metrics reported by the agent.
+    pub fn update_node_metrics(&mut self, metrics: NodeMetrics) {
         self.heartbeat();
-        self.cpu = cpu;
-        self.memory = memory;
+        self.metrics = metrics;
+    }
+
+    /// Get the last transmitted metrics of the agent's machine.
+    pub fn metrics(&self) -> &NodeMetrics {
+        &self.metrics
+    }
+
+    /// Get the last transmitted CPU metrics of the agent's machine.
+    /// `None` only if the metrics were never communicated to the scheduler.
+    pub fn cpu(&self) -> Option<&NodeCpu> {
+        self.metrics.cpu.as_ref()
+    }
+
+    /// Get the last transmitted memory metrics of the agent's machine.
+    /// `None` only if the metrics were never communicated to the scheduler.
+    pub fn memory(&self) -> Option<&NodeMemory> {
+        self.metrics.memory.as_ref()
+    }
+
+    /// Get the disks last reported by the agent, empty if it never reported them.
+    pub fn disks(&self) -> &[NodeDisk] {
+        &self.metrics.disks
     }
 }
@@ -1,5 +1,8 @@
 //! Main modules for agent storage and management.
 
+pub mod client_pool;
 pub mod errors;
 pub mod manager;
 pub mod metrics;
+pub mod properties;
+pub mod reaper;
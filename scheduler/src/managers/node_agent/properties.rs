@@ -0,0 +1,40 @@
+//! Labels and taints declared by the node agents.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// How a taint keeps the workloads that do not tolerate it away from a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaintEffect {
+    /// Workloads that do not tolerate the taint are never placed on the node.
+    NoSchedule,
+    /// Workloads that do not tolerate the taint are only placed on the node if no other node
+    /// can run them.
+    PreferNoSchedule,
+}
+
+/// A taint of a node, repelling the workloads that do not tolerate it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeTaint {
+    /// The key of the taint.
+    pub key: String,
+    /// The value of the taint, may be empty.
+    pub value: String,
+    /// How the taint repels the workloads that do not tolerate it.
+    pub effect: TaintEffect,
+}
+
+/// The properties declared by a node agent when it joins the cluster, used to constrain the
+/// placement of workloads.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeProperties {
+    /// The key/value labels of the node, matched by the node selectors and affinity of the
+    /// workloads.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// The taints of the node, only workloads tolerating them can be placed on it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub taints: Vec<NodeTaint>,
+}
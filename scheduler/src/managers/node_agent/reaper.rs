@@ -0,0 +1,131 @@
+//! Background task watching the heartbeats of the node agents.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::{event, Level};
+
+use crate::managers::events::broadcaster::{ClusterEvent, ClusterEvents};
+use crate::managers::instance::manager::InstanceManager;
+use crate::metrics::registry::{LeaveCause, SchedulerMetrics};
+
+use super::client_pool::AgentClientPool;
+use super::manager::NodeAgentManager;
+
+/// Shortest delay between two heartbeat checks.
+const MIN_CHECK_PERIOD: Duration = Duration::from_millis(100);
+
+/// Timeouts applied to the heartbeats of the node agents.
+#[derive(Debug, Clone, Copy)]
+pub struct HeartbeatTimeouts {
+    /// The time after which a silent agent is considered unhealthy.
+    pub unhealthy: Duration,
+    /// The time after which a silent agent is removed from the cluster.
+    pub eviction: Duration,
+}
+
+/// The heartbeat reaper, marking silent node agents as unhealthy and evicting them. The
+/// workload instances placed on evicted agents are forgotten, and the controllers watching the
+/// cluster are told about it.
+pub struct HeartbeatReaper {
+    /// The shared instance of the node agent manager.
+    node_agent_manager: Arc<NodeAgentManager>,
+
+    /// The shared pool of clients for the node agents.
+    agent_client_pool: Arc<AgentClientPool>,
+
+    /// The shared instance of the workload instance manager.
+    instance_manager: Arc<InstanceManager>,
+
+    /// The timeouts applied to the heartbeats.
+    timeouts: HeartbeatTimeouts,
+
+    /// The metrics of the scheduler, counting the evicted agents.
+    metrics: Arc<SchedulerMetrics>,
+
+    /// The broadcaster of the changes of the cluster.
+    cluster_events: Arc<ClusterEvents>,
+}
+
+impl HeartbeatReaper {
+    /// Create a new `HeartbeatReaper`.
+    ///
+    /// # Arguments
+    ///
+    /// * `manager` - The shared instance of the node agent manager.
+    /// * `client_pool` - The shared pool of clients for the node agents.
+    /// * `instance_manager` - The shared instance of the workload instance manager.
+    /// * `timeouts` - The timeouts applied to the heartbeats.
+    /// * `metrics` - The metrics of the scheduler.
+    /// * `cluster_events` - The broadcaster of the changes of the cluster.
+    pub fn new(
+        manager: Arc<NodeAgentManager>,
+        client_pool: Arc<AgentClientPool>,
+        instance_manager: Arc<InstanceManager>,
+        timeouts: HeartbeatTimeouts,
+        metrics: Arc<SchedulerMetrics>,
+        cluster_events: Arc<ClusterEvents>,
+    ) -> Self {
+        Self {
+            node_agent_manager: manager,
+            agent_client_pool: client_pool,
+            instance_manager,
+            timeouts,
+            metrics,
+            cluster_events,
+        }
+    }
+
+    /// Periodically check the heartbeats of the node agents, forever.
+    pub async fn run(self) {
+        let period =
+            (self.timeouts.unhealthy.min(self.timeouts.eviction) / 2).max(MIN_CHECK_PERIOD);
+        let mut interval = tokio::time::interval(period);
+
+        event!(
+            Level::DEBUG,
+            period_ms = period.as_millis(),
+            "Starting the heartbeat reaper"
+        );
+
+        loop {
+            interval.tick().await;
+
+            let check = self
+                .node_agent_manager
+                .check_heartbeats(self.timeouts.unhealthy, self.timeouts.eviction);
+
+            for agent_id in check.unhealthy {
+                self.cluster_events
+                    .publish(ClusterEvent::NodeUnhealthy { node_id: agent_id });
+            }
+
+            for agent_id in &check.evicted {
+                self.agent_client_pool.remove(agent_id);
+                self.metrics.record_leave(LeaveCause::Eviction);
+                self.cluster_events.publish(ClusterEvent::NodeLeft {
+                    node_id: agent_id.clone(),
+                    cause: LeaveCause::Eviction,
+                });
+
+                let instances = self.instance_manager.remove_agent_instances(agent_id);
+
+                for instance_id in &instances {
+                    self.cluster_events.publish(ClusterEvent::InstanceLost {
+                        instance_id: instance_id.clone(),
+                        node_id: agent_id.clone(),
+                    });
+                }
+
+                if !instances.is_empty() {
+                    event!(
+                        Level::WARN,
+                        agent_id,
+                        ?instances,
+                        "Forgot the workload instances of an evicted agent"
+                    );
+                }
+            }
+        }
+    }
+}
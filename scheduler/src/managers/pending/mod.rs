@@ -0,0 +1,3 @@
+//! Modules for the workloads waiting for a node able to run them.
+
+pub mod queue;
@@ -0,0 +1,416 @@
+//! Queue of the workloads that no node can run yet, retried with an exponential backoff.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+use orka_proto::scheduler_controller::{Workload, WorkloadStatus};
+use tokio::sync::{mpsc, Notify};
+use tonic::Status;
+use tracing::{event, Level};
+
+use crate::managers::instance::errors::InstanceError;
+
+/// Options of the queue of pending workloads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueOptions {
+    /// The delay before the first retry of a pending workload.
+    pub initial_backoff: Duration,
+    /// The longest delay between two retries of a pending workload.
+    pub max_backoff: Duration,
+    /// The time after which a workload that could not be placed is abandoned.
+    pub max_wait: Duration,
+}
+
+impl Default for QueueOptions {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            max_wait: Duration::from_secs(300),
+        }
+    }
+}
+
+/// A workload waiting for a node able to run it.
+#[derive(Debug)]
+pub struct PendingWorkload {
+    /// The workload to place.
+    pub workload: Workload,
+
+    /// The sender of the status stream returned to the controller.
+    pub sender: mpsc::Sender<Result<WorkloadStatus, Status>>,
+
+    /// Why the workload could not be placed on its last attempt.
+    pub reason: String,
+
+    /// When the workload was queued.
+    enqueued_at: Instant,
+
+    /// The delay between the last attempt and the next one.
+    backoff: Duration,
+
+    /// When the workload should be retried.
+    next_attempt: Instant,
+}
+
+impl PendingWorkload {
+    /// Get the ID of the workload instance.
+    pub fn instance_id(&self) -> &str {
+        &self.workload.instance_id
+    }
+
+    /// Get how long the workload has been waiting.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    pub fn waited(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.enqueued_at)
+    }
+}
+
+/// The queue of the workloads that no node can run yet.
+///
+/// Each workload is retried after a delay doubling with every failed attempt, and all of them
+/// are retried as soon as the cluster gains capacity. Workloads waiting for longer than the
+/// maximum wait are abandoned.
+pub struct PendingQueue {
+    /// The pending workloads, indexed by instance ID.
+    workloads: Mutex<HashMap<String, PendingWorkload>>,
+
+    /// Notified when a workload is queued or the pending workloads should be retried before
+    /// their next attempt, so that the retries are rescheduled.
+    notify: Notify,
+
+    /// The options of the queue.
+    options: QueueOptions,
+}
+
+impl PendingQueue {
+    /// Create a new, empty `PendingQueue`.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - The options of the queue.
+    pub fn new(options: QueueOptions) -> Self {
+        Self {
+            workloads: Mutex::new(HashMap::new()),
+            notify: Notify::new(),
+            options,
+        }
+    }
+
+    /// Get the options of the queue.
+    pub fn options(&self) -> &QueueOptions {
+        &self.options
+    }
+
+    /// Lock the pending workloads. The map stays consistent if a holder panicked, so the lock
+    /// cannot be poisoned.
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, PendingWorkload>> {
+        self.workloads
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Queue a workload that could not be placed.
+    ///
+    /// # Arguments
+    ///
+    /// * `workload` - The workload to place.
+    /// * `sender` - The sender of the status stream returned to the controller.
+    /// * `reason` - Why the workload could not be placed.
+    /// * `now` - The current time.
+    ///
+    /// # Errors
+    ///
+    /// * A workload instance with the same ID is already pending.
+    pub fn push(
+        &self,
+        workload: Workload,
+        sender: mpsc::Sender<Result<WorkloadStatus, Status>>,
+        reason: String,
+        now: Instant,
+    ) -> Result<(), InstanceError> {
+        let mut workloads = self.lock();
+
+        if workloads.contains_key(&workload.instance_id) {
+            return Err(InstanceError::AlreadyExists(workload.instance_id));
+        }
+
+        event!(
+            Level::INFO,
+            instance_id = workload.instance_id,
+            reason,
+            queue_depth = workloads.len() + 1,
+            "Queueing workload until a node can run it"
+        );
+
+        workloads.insert(
+            workload.instance_id.clone(),
+            PendingWorkload {
+                workload,
+                sender,
+                reason,
+                enqueued_at: now,
+                backoff: self.options.initial_backoff,
+                next_attempt: now + self.options.initial_backoff,
+            },
+        );
+
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Queue again a workload that could not be placed on a retry, doubling the delay before its
+    /// next attempt.
+    ///
+    /// # Arguments
+    ///
+    /// * `pending` - The pending workload, as taken from the queue.
+    /// * `reason` - Why the workload could not be placed.
+    /// * `now` - The current time.
+    ///
+    /// # Errors
+    ///
+    /// * A workload instance with the same ID was queued in the meantime.
+    pub fn requeue(
+        &self,
+        mut pending: PendingWorkload,
+        reason: String,
+        now: Instant,
+    ) -> Result<(), InstanceError> {
+        let mut workloads = self.lock();
+
+        if workloads.contains_key(pending.instance_id()) {
+            return Err(InstanceError::AlreadyExists(pending.workload.instance_id));
+        }
+
+        pending.reason = reason;
+        pending.backoff = (pending.backoff * 2).min(self.options.max_backoff);
+        pending.next_attempt = now + pending.backoff;
+
+        event!(
+            Level::DEBUG,
+            instance_id = pending.instance_id(),
+            reason = pending.reason,
+            backoff_ms = pending.backoff.as_millis(),
+            "Workload still cannot be placed, retrying later"
+        );
+
+        workloads.insert(pending.workload.instance_id.clone(), pending);
+        Ok(())
+    }
+
+    /// Remove a workload from the queue, returning it if it was pending.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - The ID of the workload instance.
+    pub fn remove(&self, instance_id: &str) -> Option<PendingWorkload> {
+        let mut workloads = self.lock();
+        let pending = workloads.remove(instance_id);
+
+        if pending.is_some() {
+            event!(
+                Level::DEBUG,
+                instance_id,
+                queue_depth = workloads.len(),
+                "Removed workload from the queue"
+            );
+        }
+
+        pending
+    }
+
+    /// Check whether a workload instance is pending.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - The ID of the workload instance.
+    pub fn contains(&self, instance_id: &str) -> bool {
+        self.lock().contains_key(instance_id)
+    }
+
+    /// Get the number of pending workloads.
+    pub fn depth(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Retry all the pending workloads without waiting for their backoff, because the cluster
+    /// gained capacity.
+    pub fn wake(&self) {
+        self.wake_at(Instant::now());
+    }
+
+    /// Retry all the pending workloads without waiting for their backoff, from the given time.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    pub fn wake_at(&self, now: Instant) {
+        let mut workloads = self.lock();
+
+        if workloads.is_empty() {
+            return;
+        }
+
+        for pending in workloads.values_mut() {
+            pending.next_attempt = pending.next_attempt.min(now);
+        }
+
+        self.notify.notify_one();
+    }
+
+    /// Wait until a workload is queued or the pending workloads are woken up.
+    pub async fn woken(&self) {
+        self.notify.notified().await;
+    }
+
+    /// Get the next time a pending workload should be retried or abandoned, `None` if the queue
+    /// is empty.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.lock()
+            .values()
+            .map(|pending| {
+                pending
+                    .next_attempt
+                    .min(pending.enqueued_at + self.options.max_wait)
+            })
+            .min()
+    }
+
+    /// Take the workloads that waited for longer than the maximum wait out of the queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    pub fn take_expired(&self, now: Instant) -> Vec<PendingWorkload> {
+        self.take(|pending| pending.waited(now) >= self.options.max_wait)
+    }
+
+    /// Take the workloads that should be retried out of the queue, the highest priorities and
+    /// then the oldest workloads first. Workloads queued at the same time are sorted by instance
+    /// ID, so that the order of the retries does not depend on the map order.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - The current time.
+    pub fn take_due(&self, now: Instant) -> Vec<PendingWorkload> {
+        let mut due = self.take(|pending| pending.next_attempt <= now);
+
+        due.sort_by(|a, b| {
+            b.workload
+                .priority
+                .cmp(&a.workload.priority)
+                .then(a.enqueued_at.cmp(&b.enqueued_at))
+                .then_with(|| a.instance_id().cmp(b.instance_id()))
+        });
+
+        due
+    }
+
+    /// Take all the pending workloads out of the queue.
+    pub fn take_all(&self) -> Vec<PendingWorkload> {
+        self.take(|_| true)
+    }
+
+    /// Take the workloads matching a predicate out of the queue.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - The predicate selecting the workloads to take.
+    fn take(&self, predicate: impl Fn(&PendingWorkload) -> bool) -> Vec<PendingWorkload> {
+        let mut workloads = self.lock();
+
+        let ids: Vec<String> = workloads
+            .iter()
+            .filter(|(_, pending)| predicate(pending))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        ids.iter().filter_map(|id| workloads.remove(id)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workload(instance_id: &str, priority: i32) -> Workload {
+        Workload {
+            instance_id: instance_id.to_string(),
+            priority,
+            ..Default::default()
+        }
+    }
+
+    fn queue() -> PendingQueue {
+        PendingQueue::new(QueueOptions {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(4),
+            max_wait: Duration::from_secs(30),
+        })
+    }
+
+    #[test]
+    fn backs_off_exponentially() {
+        let queue = queue();
+        let (sender, _receiver) = mpsc::channel(1);
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        queue
+            .push(workload("i", 0), sender, String::new(), start)
+            .unwrap();
+        assert!(queue.take_due(start).is_empty());
+
+        // Retried after 1, 2, 4 and then every 4 seconds
+        let mut now = start;
+        for delay in [1, 2, 4, 4] {
+            now += Duration::from_secs(delay);
+            assert_eq!(queue.next_deadline(), Some(now));
+
+            let mut due = queue.take_due(now);
+            assert_eq!(due.len(), 1);
+            queue
+                .requeue(due.pop().unwrap(), String::new(), now)
+                .unwrap();
+        }
+
+        assert!(queue.take_expired(at(29)).is_empty());
+        assert_eq!(queue.take_expired(at(30)).len(), 1);
+        assert_eq!(queue.depth(), 0);
+    }
+
+    #[test]
+    fn wakes_up_and_retries_the_highest_priorities_first() {
+        let queue = queue();
+        let (sender, _receiver) = mpsc::channel(1);
+        let start = Instant::now();
+
+        for (offset, (id, priority)) in [("low", 0), ("high", 10), ("also-low", 0)]
+            .into_iter()
+            .enumerate()
+        {
+            let now = start + Duration::from_millis(offset as u64);
+
+            queue
+                .push(workload(id, priority), sender.clone(), String::new(), now)
+                .unwrap();
+        }
+        assert!(queue
+            .push(workload("low", 5), sender, String::new(), start)
+            .is_err());
+
+        queue.wake();
+
+        let due: Vec<_> = queue
+            .take_due(Instant::now())
+            .iter()
+            .map(|pending| pending.instance_id().to_string())
+            .collect();
+        assert_eq!(due, ["high", "low", "also-low"]);
+    }
+}
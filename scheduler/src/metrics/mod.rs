@@ -0,0 +1,4 @@
+//! Prometheus metrics of the scheduler, exported over HTTP.
+
+pub mod registry;
+pub mod server;
@@ -0,0 +1,380 @@
+//! Registry of the Prometheus metrics of the scheduler.
+
+use std::time::Duration;
+
+use prometheus::{
+    Encoder, GaugeVec, Histogram, HistogramOpts, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    Opts, Registry, TextEncoder,
+};
+
+use crate::managers::node_agent::manager::NodeAgentManager;
+use crate::managers::node_agent::metrics::NodeHealth;
+use crate::managers::pending::queue::PendingQueue;
+
+/// Prefix of the names of the metrics.
+const NAMESPACE: &str = "orka_scheduler";
+
+/// Buckets of the placement latency histogram, in seconds.
+const PLACEMENT_LATENCY_BUCKETS: &[f64] = &[
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25,
+];
+
+/// The outcome of an attempt to place a workload on a node agent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementOutcome {
+    /// The workload was placed on a node agent.
+    Placed,
+    /// No node agent can run the workload yet, it is pending.
+    Pending,
+    /// The workload was refused.
+    Rejected,
+}
+
+/// Why a node agent left the cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaveCause {
+    /// The agent notified the scheduler that it was leaving.
+    Notice,
+    /// The agent stopped sending heartbeats and was evicted.
+    Eviction,
+}
+
+impl LeaveCause {
+    /// Get the label value of the cause.
+    fn as_str(self) -> &'static str {
+        match self {
+            LeaveCause::Notice => "notice",
+            LeaveCause::Eviction => "eviction",
+        }
+    }
+}
+
+/// A status stream of the scheduler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusStream {
+    /// The stream of node statuses sent by a node agent.
+    Node,
+    /// The stream of workload statuses relayed from a node agent to the controller.
+    Workload,
+}
+
+impl StatusStream {
+    /// Get the label value of the stream.
+    fn as_str(self) -> &'static str {
+        match self {
+            StatusStream::Node => "node",
+            StatusStream::Workload => "workload",
+        }
+    }
+}
+
+/// The metrics of the scheduler.
+///
+/// Counters are updated by the services as events happen, while the gauges describing the
+/// cluster are refreshed from the managers every time the metrics are gathered.
+pub struct SchedulerMetrics {
+    /// The registry holding all the metrics.
+    registry: Registry,
+
+    /// The number of node agents, by health.
+    agents: IntGaugeVec,
+
+    /// The CPU load of each node agent.
+    agent_cpu_load: GaugeVec,
+
+    /// The free memory of each node agent, in bytes.
+    agent_memory_free: GaugeVec,
+
+    /// The number of workloads waiting for a node agent.
+    pending_workloads: IntGauge,
+
+    /// The number of attempts to place a workload.
+    scheduling_attempts: IntCounter,
+
+    /// The number of workloads placed on a node agent.
+    scheduling_successes: IntCounter,
+
+    /// The number of workloads that could not be placed, by reason.
+    scheduling_failures: IntCounterVec,
+
+    /// The duration of the placement of the workloads.
+    placement_latency: Histogram,
+
+    /// The number of node agents that joined the cluster.
+    agent_joins: IntCounter,
+
+    /// The number of node agents that left the cluster, by cause.
+    agent_leaves: IntCounterVec,
+
+    /// The number of errors received on the status streams, by stream.
+    status_stream_errors: IntCounterVec,
+}
+
+impl SchedulerMetrics {
+    /// Create the metrics of the scheduler in a new registry.
+    ///
+    /// # Errors
+    ///
+    /// * A metric could not be registered.
+    pub fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some(NAMESPACE.to_string()), None)?;
+
+        let metrics = Self {
+            agents: IntGaugeVec::new(
+                Opts::new("agents", "Number of node agents in the cluster, by health"),
+                &["health"],
+            )?,
+            agent_cpu_load: GaugeVec::new(
+                Opts::new("agent_cpu_load", "CPU load of each node agent, in percent"),
+                &["agent_id"],
+            )?,
+            agent_memory_free: GaugeVec::new(
+                Opts::new(
+                    "agent_memory_free_bytes",
+                    "Free memory of each node agent, in bytes",
+                ),
+                &["agent_id"],
+            )?,
+            pending_workloads: IntGauge::new(
+                "pending_workloads",
+                "Number of workloads waiting for a node agent able to run them",
+            )?,
+            scheduling_attempts: IntCounter::new(
+                "scheduling_attempts_total",
+                "Number of attempts to place a workload on a node agent",
+            )?,
+            scheduling_successes: IntCounter::new(
+                "scheduling_successes_total",
+                "Number of workloads placed on a node agent",
+            )?,
+            scheduling_failures: IntCounterVec::new(
+                Opts::new(
+                    "scheduling_failures_total",
+                    "Number of attempts that did not place a workload, by reason",
+                ),
+                &["reason"],
+            )?,
+            placement_latency: Histogram::with_opts(
+                HistogramOpts::new(
+                    "placement_latency_seconds",
+                    "Time spent choosing the node agent of a workload, in seconds",
+                )
+                .buckets(PLACEMENT_LATENCY_BUCKETS.to_vec()),
+            )?,
+            agent_joins: IntCounter::new(
+                "agent_joins_total",
+                "Number of node agents that joined the cluster",
+            )?,
+            agent_leaves: IntCounterVec::new(
+                Opts::new(
+                    "agent_leaves_total",
+                    "Number of node agents that left the cluster, by cause",
+                ),
+                &["cause"],
+            )?,
+            status_stream_errors: IntCounterVec::new(
+                Opts::new(
+                    "status_stream_errors_total",
+                    "Number of errors received on the status streams, by stream",
+                ),
+                &["stream"],
+            )?,
+            registry,
+        };
+
+        metrics.register()?;
+        Ok(metrics)
+    }
+
+    /// Register all the metrics in the registry.
+    fn register(&self) -> prometheus::Result<()> {
+        self.registry.register(Box::new(self.agents.clone()))?;
+        self.registry
+            .register(Box::new(self.agent_cpu_load.clone()))?;
+        self.registry
+            .register(Box::new(self.agent_memory_free.clone()))?;
+        self.registry
+            .register(Box::new(self.pending_workloads.clone()))?;
+        self.registry
+            .register(Box::new(self.scheduling_attempts.clone()))?;
+        self.registry
+            .register(Box::new(self.scheduling_successes.clone()))?;
+        self.registry
+            .register(Box::new(self.scheduling_failures.clone()))?;
+        self.registry
+            .register(Box::new(self.placement_latency.clone()))?;
+        self.registry.register(Box::new(self.agent_joins.clone()))?;
+        self.registry
+            .register(Box::new(self.agent_leaves.clone()))?;
+        self.registry
+            .register(Box::new(self.status_stream_errors.clone()))?;
+
+        Ok(())
+    }
+
+    /// Record an attempt to place a workload.
+    ///
+    /// # Arguments
+    ///
+    /// * `outcome` - The outcome of the attempt.
+    /// * `latency` - The time spent placing the workload.
+    pub fn record_placement(&self, outcome: PlacementOutcome, latency: Duration) {
+        self.scheduling_attempts.inc();
+        self.placement_latency.observe(latency.as_secs_f64());
+
+        match outcome {
+            PlacementOutcome::Placed => self.scheduling_successes.inc(),
+            PlacementOutcome::Pending => self
+                .scheduling_failures
+                .with_label_values(&["pending"])
+                .inc(),
+            PlacementOutcome::Rejected => self
+                .scheduling_failures
+                .with_label_values(&["rejected"])
+                .inc(),
+        }
+    }
+
+    /// Record a node agent joining the cluster.
+    pub fn record_join(&self) {
+        self.agent_joins.inc();
+    }
+
+    /// Record a node agent leaving the cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `cause` - Why the agent left.
+    pub fn record_leave(&self, cause: LeaveCause) {
+        self.agent_leaves.with_label_values(&[cause.as_str()]).inc();
+    }
+
+    /// Record an error received on a status stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `stream` - The stream the error was received on.
+    pub fn record_stream_error(&self, stream: StatusStream) {
+        self.status_stream_errors
+            .with_label_values(&[stream.as_str()])
+            .inc();
+    }
+
+    /// Refresh the gauges describing the cluster, then encode all the metrics in the Prometheus
+    /// text format.
+    ///
+    /// # Arguments
+    ///
+    /// * `manager` - The node agent manager holding the agents of the cluster.
+    /// * `pending_queue` - The queue of the workloads waiting for a node agent.
+    ///
+    /// # Errors
+    ///
+    /// * The metrics could not be encoded.
+    pub fn gather(
+        &self,
+        manager: &NodeAgentManager,
+        pending_queue: &PendingQueue,
+    ) -> prometheus::Result<String> {
+        // Forget the agents that left the cluster since the last gathering
+        self.agent_cpu_load.reset();
+        self.agent_memory_free.reset();
+
+        let (mut healthy, mut unhealthy, mut pending) = (0, 0, 0);
+
+        for entry in manager.agents() {
+            let (id, agent) = entry.pair();
+
+            match agent.health() {
+                NodeHealth::Healthy => healthy += 1,
+                NodeHealth::Unhealthy => unhealthy += 1,
+                NodeHealth::Pending => pending += 1,
+            }
+
+            if let Some(cpu) = agent.cpu() {
+                self.agent_cpu_load.with_label_values(&[id]).set(cpu.load);
+            }
+
+            if let Some(memory) = agent.memory() {
+                self.agent_memory_free
+                    .with_label_values(&[id])
+                    .set(memory.free as f64);
+            }
+        }
+
+        self.agents.with_label_values(&["healthy"]).set(healthy);
+        self.agents.with_label_values(&["unhealthy"]).set(unhealthy);
+        self.agents.with_label_values(&["pending"]).set(pending);
+        self.pending_workloads.set(pending_queue.depth() as i64);
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+
+        String::from_utf8(buffer).map_err(|err| prometheus::Error::Msg(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::node_agent::metrics::{NodeCpu, NodeMemory, NodeMetrics};
+    use crate::managers::node_agent::properties::NodeProperties;
+    use crate::managers::pending::queue::QueueOptions;
+
+    #[test]
+    fn exports_the_cluster_and_the_recorded_events() {
+        let metrics = SchedulerMetrics::new().unwrap();
+        let manager = NodeAgentManager::new();
+        let pending_queue = PendingQueue::new(QueueOptions::default());
+
+        manager
+            .add_agent("node", "", NodeProperties::default())
+            .unwrap();
+        manager
+            .update_node_status(
+                "node",
+                NodeMetrics {
+                    cpu: Some(NodeCpu {
+                        load: 42.5,
+                        cores: Vec::new(),
+                    }),
+                    memory: Some(NodeMemory {
+                        total: 2048,
+                        free: 1024,
+                    }),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        metrics.record_join();
+        metrics.record_leave(LeaveCause::Eviction);
+        metrics.record_placement(PlacementOutcome::Placed, Duration::from_millis(1));
+        metrics.record_placement(PlacementOutcome::Pending, Duration::from_millis(1));
+        metrics.record_stream_error(StatusStream::Workload);
+
+        let text = metrics.gather(&manager, &pending_queue).unwrap();
+
+        for line in [
+            "orka_scheduler_agents{health=\"healthy\"} 1",
+            "orka_scheduler_agents{health=\"unhealthy\"} 0",
+            "orka_scheduler_agent_cpu_load{agent_id=\"node\"} 42.5",
+            "orka_scheduler_agent_memory_free_bytes{agent_id=\"node\"} 1024",
+            "orka_scheduler_pending_workloads 0",
+            "orka_scheduler_scheduling_attempts_total 2",
+            "orka_scheduler_scheduling_successes_total 1",
+            "orka_scheduler_scheduling_failures_total{reason=\"pending\"} 1",
+            "orka_scheduler_placement_latency_seconds_count 2",
+            "orka_scheduler_agent_joins_total 1",
+            "orka_scheduler_agent_leaves_total{cause=\"eviction\"} 1",
+            "orka_scheduler_status_stream_errors_total{stream=\"workload\"} 1",
+        ] {
+            assert!(
+                text.lines().any(|l| l == line),
+                "missing `{}` in:\n{}",
+                line,
+                text
+            );
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! HTTP server exporting the metrics of the scheduler to Prometheus.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use prometheus::TEXT_FORMAT;
+use tracing::{event, Level};
+
+use crate::managers::node_agent::manager::NodeAgentManager;
+use crate::managers::pending::queue::PendingQueue;
+
+use super::registry::SchedulerMetrics;
+
+/// Path of the metrics endpoint.
+const METRICS_PATH: &str = "/metrics";
+
+/// The HTTP server exporting the metrics of the scheduler in the Prometheus text format.
+#[derive(Clone)]
+pub struct MetricsServer {
+    /// The metrics of the scheduler.
+    metrics: Arc<SchedulerMetrics>,
+
+    /// The shared instance of the node agent manager, describing the agents of the cluster.
+    node_agent_manager: Arc<NodeAgentManager>,
+
+    /// The shared queue of the workloads waiting for a node agent.
+    pending_queue: Arc<PendingQueue>,
+}
+
+impl MetricsServer {
+    /// Create a new `MetricsServer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `metrics` - The metrics of the scheduler.
+    /// * `manager` - The shared instance of the node agent manager.
+    /// * `pending_queue` - The shared queue of the workloads waiting for a node agent.
+    pub fn new(
+        metrics: Arc<SchedulerMetrics>,
+        manager: Arc<NodeAgentManager>,
+        pending_queue: Arc<PendingQueue>,
+    ) -> Self {
+        Self {
+            metrics,
+            node_agent_manager: manager,
+            pending_queue,
+        }
+    }
+
+    /// Serve the metrics on `GET /metrics` until the server fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to bind the server to.
+    ///
+    /// # Errors
+    ///
+    /// * The server could not bind to the address or stopped serving.
+    pub async fn serve(self, address: SocketAddr) -> Result<()> {
+        let make_service = make_service_fn(move |_| {
+            let server = self.clone();
+
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(request)) }
+                }))
+            }
+        });
+
+        let server = Server::try_bind(&address)
+            .with_context(|| format!("Unable to bind the metrics server to {}", address))?
+            .serve(make_service);
+
+        event!(Level::INFO, %address, "Serving Prometheus metrics");
+
+        server
+            .await
+            .with_context(|| "An error occurred while serving the metrics")
+    }
+
+    /// Respond to an HTTP request.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The HTTP request.
+    fn handle(&self, request: Request<Body>) -> Response<Body> {
+        if request.uri().path() != METRICS_PATH {
+            return respond(StatusCode::NOT_FOUND, "Not found\n".to_string());
+        }
+
+        if request.method() != Method::GET {
+            return respond(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed\n".to_string(),
+            );
+        }
+
+        match self
+            .metrics
+            .gather(&self.node_agent_manager, &self.pending_queue)
+        {
+            Ok(text) => {
+                let mut response = respond(StatusCode::OK, text);
+                response
+                    .headers_mut()
+                    .insert(CONTENT_TYPE, HeaderValue::from_static(TEXT_FORMAT));
+                response
+            }
+            Err(err) => {
+                event!(Level::WARN, error = %err, "Unable to gather the metrics");
+
+                respond(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Unable to gather the metrics\n".to_string(),
+                )
+            }
+        }
+    }
+}
+
+/// Build a plain text HTTP response.
+///
+/// # Arguments
+///
+/// * `status` - The status of the response.
+/// * `body` - The body of the response.
+fn respond(status: StatusCode, body: String) -> Response<Body> {
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use hyper::Client;
+
+    use super::*;
+    use crate::managers::pending::queue::QueueOptions;
+
+    /// Serve the metrics of an empty cluster on the loopback interface, returning the address.
+    async fn serve() -> SocketAddr {
+        // Reserve a free port, then let the server bind it
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let server = MetricsServer::new(
+            Arc::new(SchedulerMetrics::new().unwrap()),
+            Arc::new(NodeAgentManager::new()),
+            Arc::new(PendingQueue::new(QueueOptions::default())),
+        );
+        tokio::spawn(server.serve(address));
+
+        address
+    }
+
+    /// Send a request to the server, retrying while it is starting.
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - The method of the request.
+    /// * `uri` - The URI of the request.
+    async fn send(method: Method, uri: &str) -> Response<Body> {
+        let client = Client::new();
+
+        for _ in 0..100 {
+            let request = Request::builder()
+                .method(method.clone())
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+
+            match client.request(request).await {
+                Ok(response) => return response,
+                Err(err) if err.is_connect() => tokio::time::sleep(Duration::from_millis(10)).await,
+                Err(err) => panic!("request failed: {}", err),
+            }
+        }
+
+        panic!("the metrics server did not start");
+    }
+
+    #[tokio::test]
+    async fn serves_the_metrics_on_get_only() {
+        let address = serve().await;
+
+        let response = send(Method::GET, &format!("http://{}/metrics", address)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get(CONTENT_TYPE).unwrap(),
+            "text/plain; version=0.0.4"
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let text = String::from_utf8(body.to_vec()).unwrap();
+        assert!(text
+            .lines()
+            .any(|line| line == "orka_scheduler_pending_workloads 0"));
+
+        let response = send(Method::GET, &format!("http://{}/other", address)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = send(Method::POST, &format!("http://{}/metrics", address)).await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+}
@@ -0,0 +1,490 @@
+//! Constraints restricting the nodes a workload can be placed on.
+
+use std::collections::BTreeMap;
+
+use orka_proto::scheduler_controller::{
+    workload::{self, label_requirement, toleration},
+    Workload,
+};
+
+use crate::managers::node_agent::metrics::NodeAgent;
+use crate::managers::node_agent::properties::{NodeTaint, TaintEffect};
+
+use super::errors::PlacementError;
+
+/// Highest weight of a preferred affinity term.
+const MAX_PREFERENCE_WEIGHT: u32 = 100;
+
+/// How a label requirement compares the value of a node label.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelOperator {
+    /// The label exists and its value is one of the values of the requirement.
+    In,
+    /// The label does not exist or its value is none of the values of the requirement.
+    NotIn,
+    /// The label exists, whatever its value.
+    Exists,
+    /// The label does not exist.
+    DoesNotExist,
+}
+
+/// A requirement on a label of the nodes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelRequirement {
+    /// The key of the label.
+    pub key: String,
+    /// How the value of the label is compared.
+    pub operator: LabelOperator,
+    /// The values compared with the value of the label, empty unless the operator is `In` or
+    /// `NotIn`.
+    pub values: Vec<String>,
+}
+
+/// A group of label requirements preferred by a workload, the nodes matching all of them are
+/// favored by the weight of the preference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelPreference {
+    /// The weight of the preference, between `1` and `100`.
+    pub weight: u32,
+    /// The requirements a node must all match to be favored.
+    pub requirements: Vec<LabelRequirement>,
+}
+
+/// How a toleration compares the value of a taint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TolerationOperator {
+    /// The taint has the key and the value of the toleration.
+    Equal,
+    /// The taint has the key of the toleration, whatever its value. An empty key matches every
+    /// taint.
+    Exists,
+}
+
+/// A toleration of a workload, allowing it to be placed on the nodes with matching taints.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toleration {
+    /// The key of the tolerated taints.
+    pub key: String,
+    /// How the value of the taints is compared.
+    pub operator: TolerationOperator,
+    /// The value of the tolerated taints, empty if the operator is `Exists`.
+    pub value: String,
+    /// The effect of the tolerated taints, `None` to tolerate every effect.
+    pub effect: Option<TaintEffect>,
+}
+
+/// The constraints a node must satisfy to run a workload.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkloadConstraints {
+    /// The labels a node must have, with the same values.
+    pub node_selector: BTreeMap<String, String>,
+    /// The label requirements a node must all match.
+    pub required: Vec<LabelRequirement>,
+    /// The label requirements favoring the nodes matching them.
+    pub preferred: Vec<LabelPreference>,
+    /// The taints tolerated by the workload.
+    pub tolerations: Vec<Toleration>,
+}
+
+impl LabelRequirement {
+    /// Check whether the labels of a node match the requirement.
+    ///
+    /// # Arguments
+    ///
+    /// * `labels` - The labels of the node.
+    fn matches(&self, labels: &BTreeMap<String, String>) -> bool {
+        let value = labels.get(&self.key);
+
+        match self.operator {
+            LabelOperator::In => value.is_some_and(|value| self.values.contains(value)),
+            LabelOperator::NotIn => value.is_none_or(|value| !self.values.contains(value)),
+            LabelOperator::Exists => value.is_some(),
+            LabelOperator::DoesNotExist => value.is_none(),
+        }
+    }
+}
+
+impl Toleration {
+    /// Check whether the toleration tolerates a taint.
+    ///
+    /// # Arguments
+    ///
+    /// * `taint` - The taint of a node.
+    fn tolerates(&self, taint: &NodeTaint) -> bool {
+        if self.effect.is_some_and(|effect| effect != taint.effect) {
+            return false;
+        }
+
+        match self.operator {
+            TolerationOperator::Equal => self.key == taint.key && self.value == taint.value,
+            TolerationOperator::Exists => self.key.is_empty() || self.key == taint.key,
+        }
+    }
+}
+
+impl WorkloadConstraints {
+    /// Check whether a node agent can run the workload: it matches the node selector and the
+    /// required affinity, and the workload tolerates all its `NoSchedule` taints.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent` - The node agent to check.
+    pub fn allows(&self, agent: &NodeAgent) -> bool {
+        let labels = agent.labels();
+
+        self.node_selector
+            .iter()
+            .all(|(key, value)| labels.get(key) == Some(value))
+            && self.required.iter().all(|r| r.matches(labels))
+            && !self.repels(agent, TaintEffect::NoSchedule)
+    }
+
+    /// Check whether a node agent should be avoided, because the workload does not tolerate one
+    /// of its `PreferNoSchedule` taints.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent` - The node agent to check.
+    pub fn avoids(&self, agent: &NodeAgent) -> bool {
+        self.repels(agent, TaintEffect::PreferNoSchedule)
+    }
+
+    /// Compute the sum of the weights of the preferences matched by a node agent.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent` - The node agent to evaluate.
+    pub fn affinity(&self, agent: &NodeAgent) -> u32 {
+        let labels = agent.labels();
+
+        self.preferred
+            .iter()
+            .filter(|preference| preference.requirements.iter().all(|r| r.matches(labels)))
+            .map(|preference| preference.weight)
+            .sum()
+    }
+
+    /// Check whether a node agent has a taint with the given effect that the workload does not
+    /// tolerate.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent` - The node agent to check.
+    /// * `effect` - The effect of the taints to check.
+    fn repels(&self, agent: &NodeAgent, effect: TaintEffect) -> bool {
+        agent
+            .taints()
+            .iter()
+            .filter(|taint| taint.effect == effect)
+            .any(|taint| !self.tolerations.iter().any(|t| t.tolerates(taint)))
+    }
+}
+
+impl TryFrom<&Workload> for WorkloadConstraints {
+    type Error = PlacementError;
+
+    /// Extract the placement constraints of a workload, checking that they are consistent.
+    fn try_from(workload: &Workload) -> Result<Self, Self::Error> {
+        let (required, preferred) = match &workload.affinity {
+            Some(affinity) => (
+                to_requirements(&affinity.required)?,
+                affinity
+                    .preferred
+                    .iter()
+                    .map(to_preference)
+                    .collect::<Result<_, _>>()?,
+            ),
+            None => Default::default(),
+        };
+
+        Ok(Self {
+            node_selector: workload
+                .node_selector
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            required,
+            preferred,
+            tolerations: workload
+                .tolerations
+                .iter()
+                .map(to_toleration)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Convert the label requirements of a workload.
+///
+/// # Arguments
+///
+/// * `requirements` - The label requirements received from the controller.
+///
+/// # Errors
+///
+/// * A requirement has no key or an unknown operator.
+/// * A requirement has no values while its operator compares them, or the other way around.
+fn to_requirements(
+    requirements: &[workload::LabelRequirement],
+) -> Result<Vec<LabelRequirement>, PlacementError> {
+    requirements
+        .iter()
+        .map(|requirement| {
+            let invalid = |reason: &str| {
+                PlacementError::InvalidConstraints(format!(
+                    "label requirement on `{}` {}",
+                    requirement.key, reason
+                ))
+            };
+
+            if requirement.key.is_empty() {
+                return Err(invalid("has no key"));
+            }
+
+            let operator = match label_requirement::Operator::from_i32(requirement.operator) {
+                Some(label_requirement::Operator::In) => LabelOperator::In,
+                Some(label_requirement::Operator::NotIn) => LabelOperator::NotIn,
+                Some(label_requirement::Operator::Exists) => LabelOperator::Exists,
+                Some(label_requirement::Operator::DoesNotExist) => LabelOperator::DoesNotExist,
+                None => return Err(invalid("has an unknown operator")),
+            };
+
+            let compares_values = matches!(operator, LabelOperator::In | LabelOperator::NotIn);
+
+            if compares_values && requirement.values.is_empty() {
+                return Err(invalid("must have values"));
+            }
+
+            if !compares_values && !requirement.values.is_empty() {
+                return Err(invalid("must not have values"));
+            }
+
+            Ok(LabelRequirement {
+                key: requirement.key.clone(),
+                operator,
+                values: requirement.values.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Convert a preferred affinity term of a workload.
+///
+/// # Arguments
+///
+/// * `preference` - The preferred affinity term received from the controller.
+///
+/// # Errors
+///
+/// * The weight is not between `1` and `100`.
+/// * One of the label requirements is invalid.
+fn to_preference(
+    preference: &workload::affinity::Preference,
+) -> Result<LabelPreference, PlacementError> {
+    if !(1..=MAX_PREFERENCE_WEIGHT).contains(&preference.weight) {
+        return Err(PlacementError::InvalidConstraints(format!(
+            "preference weight {} is not between 1 and {}",
+            preference.weight, MAX_PREFERENCE_WEIGHT
+        )));
+    }
+
+    Ok(LabelPreference {
+        weight: preference.weight,
+        requirements: to_requirements(&preference.requirements)?,
+    })
+}
+
+/// Convert a toleration of a workload.
+///
+/// # Arguments
+///
+/// * `toleration` - The toleration received from the controller.
+///
+/// # Errors
+///
+/// * The operator or the effect is unknown.
+/// * The operator is `Equal` and the key is empty.
+/// * The operator is `Exists` and a value is set.
+fn to_toleration(toleration: &workload::Toleration) -> Result<Toleration, PlacementError> {
+    let invalid = |reason: &str| {
+        PlacementError::InvalidConstraints(format!("toleration of `{}` {}", toleration.key, reason))
+    };
+
+    let operator = match toleration::Operator::from_i32(toleration.operator) {
+        Some(toleration::Operator::Equal) => TolerationOperator::Equal,
+        Some(toleration::Operator::Exists) => TolerationOperator::Exists,
+        None => return Err(invalid("has an unknown operator")),
+    };
+
+    let effect = match toleration::Effect::from_i32(toleration.effect) {
+        Some(toleration::Effect::All) => None,
+        Some(toleration::Effect::NoSchedule) => Some(TaintEffect::NoSchedule),
+        Some(toleration::Effect::PreferNoSchedule) => Some(TaintEffect::PreferNoSchedule),
+        None => return Err(invalid("has an unknown effect")),
+    };
+
+    match operator {
+        TolerationOperator::Equal if toleration.key.is_empty() => {
+            return Err(invalid("must have a key to compare values"))
+        }
+        TolerationOperator::Exists if !toleration.value.is_empty() => {
+            return Err(invalid("must not have a value"))
+        }
+        _ => (),
+    }
+
+    Ok(Toleration {
+        key: toleration.key.clone(),
+        operator,
+        value: toleration.value.clone(),
+        effect,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::node_agent::properties::NodeProperties;
+
+    /// Create a node agent with the given labels and taints.
+    fn agent(labels: &[(&str, &str)], taints: &[(&str, &str, TaintEffect)]) -> NodeAgent {
+        NodeAgent::new(
+            "",
+            NodeProperties {
+                labels: labels
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+                taints: taints
+                    .iter()
+                    .map(|(key, value, effect)| NodeTaint {
+                        key: key.to_string(),
+                        value: value.to_string(),
+                        effect: *effect,
+                    })
+                    .collect(),
+            },
+        )
+    }
+
+    fn requirement(key: &str, operator: LabelOperator, values: &[&str]) -> LabelRequirement {
+        LabelRequirement {
+            key: key.to_string(),
+            operator,
+            values: values.iter().map(|value| value.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn matches_the_node_selector_and_required_affinity() {
+        let gpu = agent(&[("gpu", "nvidia"), ("zone", "a")], &[]);
+        let build = agent(&[("zone", "b")], &[]);
+
+        let constraints = WorkloadConstraints {
+            node_selector: [("zone".to_string(), "b".to_string())].into(),
+            required: vec![requirement("gpu", LabelOperator::DoesNotExist, &[])],
+            ..Default::default()
+        };
+        assert!(!constraints.allows(&gpu));
+        assert!(constraints.allows(&build));
+
+        let constraints = WorkloadConstraints {
+            required: vec![
+                requirement("zone", LabelOperator::In, &["a", "c"]),
+                requirement("gpu", LabelOperator::NotIn, &["amd"]),
+            ],
+            ..Default::default()
+        };
+        assert!(constraints.allows(&gpu));
+        assert!(!constraints.allows(&build));
+    }
+
+    #[test]
+    fn only_tolerated_taints_allow_placement() {
+        let database = agent(&[], &[("dedicated", "database", TaintEffect::NoSchedule)]);
+        let shared = agent(&[], &[("spot", "", TaintEffect::PreferNoSchedule)]);
+
+        let intolerant = WorkloadConstraints::default();
+        assert!(!intolerant.allows(&database));
+        assert!(intolerant.allows(&shared));
+        assert!(intolerant.avoids(&shared));
+
+        let tolerant = WorkloadConstraints {
+            tolerations: vec![
+                Toleration {
+                    key: "dedicated".to_string(),
+                    operator: TolerationOperator::Equal,
+                    value: "database".to_string(),
+                    effect: Some(TaintEffect::NoSchedule),
+                },
+                Toleration {
+                    key: String::new(),
+                    operator: TolerationOperator::Exists,
+                    value: String::new(),
+                    effect: Some(TaintEffect::PreferNoSchedule),
+                },
+            ],
+            ..Default::default()
+        };
+        assert!(tolerant.allows(&database));
+        assert!(!tolerant.avoids(&shared));
+    }
+
+    #[test]
+    fn sums_the_weights_of_matched_preferences() {
+        let constraints = WorkloadConstraints {
+            preferred: vec![
+                LabelPreference {
+                    weight: 10,
+                    requirements: vec![requirement("ssd", LabelOperator::Exists, &[])],
+                },
+                LabelPreference {
+                    weight: 50,
+                    requirements: vec![requirement("zone", LabelOperator::In, &["a"])],
+                },
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(constraints.affinity(&agent(&[("ssd", "")], &[])), 10);
+        assert_eq!(
+            constraints.affinity(&agent(&[("ssd", ""), ("zone", "a")], &[])),
+            60
+        );
+        assert_eq!(constraints.affinity(&agent(&[], &[])), 0);
+    }
+
+    #[test]
+    fn rejects_inconsistent_constraints() {
+        let workload = |affinity, tolerations| Workload {
+            affinity: Some(affinity),
+            tolerations,
+            ..Default::default()
+        };
+        let in_without_values = workload::LabelRequirement {
+            key: "zone".to_string(),
+            operator: label_requirement::Operator::In as i32,
+            values: Vec::new(),
+        };
+
+        let result = WorkloadConstraints::try_from(&workload(
+            workload::Affinity {
+                required: vec![in_without_values],
+                preferred: Vec::new(),
+            },
+            Vec::new(),
+        ));
+        assert!(matches!(result, Err(PlacementError::InvalidConstraints(_))));
+
+        let result = WorkloadConstraints::try_from(&workload(
+            workload::Affinity::default(),
+            vec![workload::Toleration {
+                key: String::new(),
+                operator: toleration::Operator::Equal as i32,
+                value: "database".to_string(),
+                effect: toleration::Effect::All as i32,
+            }],
+        ));
+        assert!(matches!(result, Err(PlacementError::InvalidConstraints(_))));
+    }
+}
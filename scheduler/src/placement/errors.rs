@@ -0,0 +1,33 @@
+//! Placement errors.
+
+use thiserror::Error;
+
+/// Placement error enum to have self-explanatory and compact errors.
+#[derive(Error, Debug)]
+pub enum PlacementError {
+    /// No node agent is able to receive workloads.
+    #[error("No node agent is available to run the workload: `{0}`")]
+    NoAvailableNode(String),
+
+    /// Node agents satisfy the placement constraints, but none of them has enough resources for
+    /// the workload.
+    #[error("No node agent has enough resources to run the workload: `{0}`")]
+    InsufficientResources(String),
+
+    /// Node agents are available, but none of them satisfies the placement constraints of the
+    /// workload.
+    #[error("No node agent satisfies the placement constraints of the workload: `{0}`")]
+    NoMatchingNode(String),
+
+    /// The placement constraints of the workload are not consistent.
+    #[error("Invalid placement constraints: {0}")]
+    InvalidConstraints(String),
+}
+
+impl PlacementError {
+    /// Check whether the workload may be placed once the cluster changes, such as when agents
+    /// join or instances terminate.
+    pub fn is_transient(&self) -> bool {
+        !matches!(self, Self::InvalidConstraints(_))
+    }
+}
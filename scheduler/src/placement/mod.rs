@@ -0,0 +1,9 @@
+//! Placement of workloads on the node agents of the cluster.
+
+pub mod constraints;
+pub mod errors;
+pub mod placer;
+pub mod preemption;
+pub mod requirements;
+pub mod reservation;
+pub mod strategy;
@@ -0,0 +1,571 @@
+//! Selection of the node agent that will run a workload.
+
+use std::cmp::Ordering;
+
+use tracing::{event, Level};
+
+use crate::managers::instance::manager::InstanceManager;
+use crate::managers::node_agent::manager::NodeAgentManager;
+
+use super::constraints::WorkloadConstraints;
+use super::errors::PlacementError;
+use super::requirements::WorkloadRequirements;
+use super::reservation::{AvailableResources, OvercommitRatios};
+use super::strategy::ScoringStrategy;
+
+/// The placer, ranking the node agents of the cluster to choose where workloads run.
+pub struct Placer {
+    /// The strategy used to rank the node agents able to run a workload.
+    strategy: Box<dyn ScoringStrategy>,
+
+    /// The ratios by which the resources reserved on a node may exceed its capacity.
+    overcommit_ratios: OvercommitRatios,
+}
+
+/// A node agent able to run a workload, with its rank.
+struct Candidate {
+    /// The ID of the agent.
+    id: String,
+    /// Whether the workload does not tolerate some of the `PreferNoSchedule` taints of the agent.
+    avoided: bool,
+    /// The sum of the weights of the preferences of the workload matched by the agent.
+    affinity: u32,
+    /// The score of the agent according to the scoring strategy.
+    score: f64,
+}
+
+impl Candidate {
+    /// Check whether the workload should rather be placed on this agent than on another one.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other candidate.
+    fn is_better_than(&self, other: &Candidate) -> bool {
+        // Break ties on the agent ID so that placement does not depend on the map order
+        let ordering = other
+            .avoided
+            .cmp(&self.avoided)
+            .then(self.affinity.cmp(&other.affinity))
+            .then(self.score.total_cmp(&other.score))
+            .then(other.id.cmp(&self.id));
+
+        ordering == Ordering::Greater
+    }
+}
+
+impl Placer {
+    /// Create a new `Placer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - The strategy used to rank the node agents able to run a workload.
+    /// * `overcommit_ratios` - The ratios by which the resources reserved on a node may exceed
+    ///   its capacity.
+    pub fn new(strategy: Box<dyn ScoringStrategy>, overcommit_ratios: OvercommitRatios) -> Self {
+        Self {
+            strategy,
+            overcommit_ratios,
+        }
+    }
+
+    /// Get the ratios by which the resources reserved on a node may exceed its capacity.
+    pub fn overcommit_ratios(&self) -> &OvercommitRatios {
+        &self.overcommit_ratios
+    }
+
+    /// Select the node agent that should run a workload, returning its ID.
+    ///
+    /// Only the healthy, uncordoned agents that reported their metrics, satisfy the placement
+    /// constraints of the workload and have enough resources left for it are considered. The
+    /// resources reserved by the instances already placed on an agent are not available, even if
+    /// its metrics do not reflect them yet. Among them, the agents with taints the workload
+    /// prefers to avoid come last, then the agents matching the most preferred affinity come
+    /// first. Remaining ties are broken by the scoring strategy.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - The ID of the workload instance to place.
+    /// * `manager` - The node agent manager holding the agents of the cluster.
+    /// * `instances` - The instance manager holding the resources reserved on each agent.
+    /// * `requirements` - The resources required by the workload.
+    /// * `constraints` - The constraints on the nodes that can run the workload.
+    ///
+    /// # Errors
+    ///
+    /// * No healthy agent has reported its metrics yet.
+    /// * No agent satisfies the placement constraints of the workload.
+    /// * No agent satisfying the constraints has enough resources left for the workload.
+    pub fn place(
+        &self,
+        instance_id: &str,
+        manager: &NodeAgentManager,
+        instances: &InstanceManager,
+        requirements: &WorkloadRequirements,
+        constraints: &WorkloadConstraints,
+    ) -> Result<String, PlacementError> {
+        let mut has_candidates = false;
+        let mut has_matching = false;
+        let mut best: Option<Candidate> = None;
+
+        for entry in manager.agents().filter(|entry| entry.is_schedulable()) {
+            let (id, agent) = entry.pair();
+
+            let reserved = instances.reserved(id);
+
+            let Some(available) =
+                AvailableResources::new(agent, &reserved, &self.overcommit_ratios)
+            else {
+                continue;
+            };
+            has_candidates = true;
+
+            if !constraints.allows(agent) {
+                continue;
+            }
+            has_matching = true;
+
+            if !available.fits(requirements) {
+                continue;
+            }
+
+            let candidate = Candidate {
+                id: id.clone(),
+                avoided: constraints.avoids(agent),
+                affinity: constraints.affinity(agent),
+                score: self.strategy.score(agent, &available, requirements),
+            };
+
+            event!(
+                Level::TRACE,
+                instance_id,
+                agent_id = id,
+                avoided = candidate.avoided,
+                affinity = candidate.affinity,
+                score = candidate.score,
+                "Scored node agent for placement"
+            );
+
+            if best
+                .as_ref()
+                .is_none_or(|best| candidate.is_better_than(best))
+            {
+                best = Some(candidate);
+            }
+        }
+
+        match best {
+            Some(candidate) => Ok(candidate.id),
+            None if has_matching => Err(PlacementError::InsufficientResources(
+                instance_id.to_string(),
+            )),
+            None if has_candidates => Err(PlacementError::NoMatchingNode(instance_id.to_string())),
+            None => Err(PlacementError::NoAvailableNode(instance_id.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::node_agent::metrics::{NodeCpu, NodeDisk, NodeMemory, NodeMetrics};
+    use crate::managers::node_agent::properties::{NodeProperties, NodeTaint, TaintEffect};
+    use crate::placement::constraints::{LabelOperator, LabelPreference, LabelRequirement};
+    use crate::placement::strategy::StrategyKind;
+    use std::time::Duration;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    /// Create a manager holding agents with the given CPU load and free memory, out of 8 GiB.
+    fn manager_with(agents: &[(&str, f64, u64)]) -> NodeAgentManager {
+        let agents: Vec<_> = agents
+            .iter()
+            .map(|(id, load, free)| (*id, *load, *free, NodeProperties::default()))
+            .collect();
+
+        manager_with_properties(&agents)
+    }
+
+    /// Create a manager holding agents with the given CPU load, free memory out of 8 GiB, labels
+    /// and taints.
+    fn manager_with_properties(agents: &[(&str, f64, u64, NodeProperties)]) -> NodeAgentManager {
+        let manager = NodeAgentManager::new();
+
+        for (id, load, free, properties) in agents {
+            manager.add_agent(id, "", properties.clone()).unwrap();
+            manager
+                .update_node_status(
+                    id,
+                    NodeMetrics {
+                        cpu: Some(NodeCpu {
+                            load: *load,
+                            cores: Vec::new(),
+                        }),
+                        memory: Some(NodeMemory {
+                            total: 8 * GIB,
+                            free: *free,
+                        }),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+
+        manager
+    }
+
+    fn place(kind: StrategyKind, manager: &NodeAgentManager, memory: u64) -> String {
+        let requirements = WorkloadRequirements {
+            cpu: 10.0,
+            memory,
+            disk: 0,
+        };
+
+        Placer::new(kind.build(), OvercommitRatios::default())
+            .place(
+                "instance",
+                manager,
+                &InstanceManager::new(),
+                &requirements,
+                &WorkloadConstraints::default(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn spread_prefers_the_emptiest_node() {
+        let manager = manager_with(&[
+            ("busy", 80.0, GIB),
+            ("idle", 10.0, 7 * GIB),
+            ("half", 50.0, 4 * GIB),
+        ]);
+
+        assert_eq!(place(StrategyKind::Spread, &manager, GIB), "idle");
+    }
+
+    #[test]
+    fn bin_pack_prefers_the_fullest_node_that_fits() {
+        let manager = manager_with(&[
+            ("full", 95.0, 7 * GIB),
+            ("busy", 80.0, 2 * GIB),
+            ("idle", 10.0, 7 * GIB),
+        ]);
+
+        assert_eq!(place(StrategyKind::BinPack, &manager, GIB), "busy");
+    }
+
+    #[test]
+    fn least_loaded_ignores_memory() {
+        let manager = manager_with(&[("cool", 5.0, 2 * GIB), ("roomy", 40.0, 8 * GIB)]);
+
+        assert_eq!(place(StrategyKind::LeastLoaded, &manager, GIB), "cool");
+        assert_eq!(place(StrategyKind::Spread, &manager, GIB), "roomy");
+    }
+
+    #[test]
+    fn ties_are_broken_on_the_agent_id() {
+        let manager = manager_with(&[("b", 20.0, 4 * GIB), ("a", 20.0, 4 * GIB)]);
+
+        assert_eq!(place(StrategyKind::Spread, &manager, GIB), "a");
+    }
+
+    #[test]
+    fn reservations_are_subtracted_from_capacity() {
+        let manager = manager_with(&[("node", 10.0, 7 * GIB)]);
+        let instances = InstanceManager::new();
+        let requirements = WorkloadRequirements {
+            cpu: 10.0,
+            memory: 3 * GIB,
+            disk: 0,
+        };
+        let none = WorkloadConstraints::default();
+
+        let placer = Placer::new(StrategyKind::Spread.build(), OvercommitRatios::default());
+        let place = |placer: &Placer| placer.place("i", &manager, &instances, &requirements, &none);
+
+        // The metrics do not change, but only 8 GiB can be reserved
+        for id in ["i1", "i2"] {
+            assert_eq!(place(&placer).unwrap(), "node");
+            instances
+                .add_instance(id, "node", requirements.clone(), 0)
+                .unwrap();
+        }
+        assert!(matches!(
+            place(&placer),
+            Err(PlacementError::InsufficientResources(_))
+        ));
+
+        let overcommitting = Placer::new(
+            StrategyKind::Spread.build(),
+            OvercommitRatios {
+                memory: 2.0,
+                ..Default::default()
+            },
+        );
+        assert_eq!(place(&overcommitting).unwrap(), "node");
+
+        instances.remove_instance("i1");
+        assert_eq!(place(&placer).unwrap(), "node");
+    }
+
+    #[test]
+    fn spread_accounts_for_reservations() {
+        let manager = manager_with(&[("a", 10.0, 7 * GIB), ("b", 10.0, 6 * GIB)]);
+        let instances = InstanceManager::new();
+        let requirements = WorkloadRequirements {
+            cpu: 10.0,
+            memory: 3 * GIB,
+            disk: 0,
+        };
+
+        let placer = Placer::new(StrategyKind::Spread.build(), OvercommitRatios::default());
+        let place = || {
+            placer
+                .place(
+                    "i",
+                    &manager,
+                    &instances,
+                    &requirements,
+                    &WorkloadConstraints::default(),
+                )
+                .unwrap()
+        };
+
+        assert_eq!(place(), "a");
+        instances
+            .add_instance("i1", "a", requirements.clone(), 0)
+            .unwrap();
+        assert_eq!(place(), "b");
+    }
+
+    #[test]
+    fn rejects_workloads_that_fit_nowhere() {
+        let manager = manager_with(&[("small", 10.0, GIB)]);
+        let requirements = WorkloadRequirements {
+            cpu: 0.0,
+            memory: 2 * GIB,
+            disk: 0,
+        };
+
+        let result = Placer::new(StrategyKind::Spread.build(), OvercommitRatios::default()).place(
+            "i",
+            &manager,
+            &InstanceManager::new(),
+            &requirements,
+            &WorkloadConstraints::default(),
+        );
+
+        assert!(matches!(
+            result,
+            Err(PlacementError::InsufficientResources(_))
+        ));
+    }
+
+    #[test]
+    fn checks_the_disk_space_of_agents_reporting_their_disks() {
+        let manager = manager_with(&[("reporting", 10.0, 7 * GIB), ("legacy", 20.0, 4 * GIB)]);
+        let metrics = NodeMetrics {
+            disks: vec![
+                NodeDisk {
+                    mount_point: "/".to_string(),
+                    total: 100 * GIB,
+                    free: GIB,
+                },
+                NodeDisk {
+                    mount_point: "/var/lib".to_string(),
+                    total: 100 * GIB,
+                    free: 3 * GIB,
+                },
+            ],
+            ..manager
+                .agents()
+                .find(|e| e.key() == "reporting")
+                .unwrap()
+                .metrics()
+                .clone()
+        };
+        manager.update_node_status("reporting", metrics).unwrap();
+
+        let requirements = |disk| WorkloadRequirements {
+            cpu: 0.0,
+            memory: 0,
+            disk,
+        };
+        let placer = Placer::new(StrategyKind::Spread.build(), OvercommitRatios::default());
+        let none = WorkloadConstraints::default();
+
+        // A single disk must have enough space, the agent not reporting its disks is not checked
+        assert_eq!(
+            placer
+                .place(
+                    "i",
+                    &manager,
+                    &InstanceManager::new(),
+                    &requirements(2 * GIB),
+                    &none
+                )
+                .unwrap(),
+            "reporting"
+        );
+        assert_eq!(
+            placer
+                .place(
+                    "i",
+                    &manager,
+                    &InstanceManager::new(),
+                    &requirements(4 * GIB),
+                    &none
+                )
+                .unwrap(),
+            "legacy"
+        );
+    }
+
+    #[test]
+    fn skips_unhealthy_agents() {
+        let manager = manager_with(&[("idle", 10.0, 7 * GIB), ("busy", 80.0, GIB)]);
+        manager.check_heartbeats(Duration::ZERO, Duration::MAX);
+
+        let result = Placer::new(StrategyKind::Spread.build(), OvercommitRatios::default()).place(
+            "i",
+            &manager,
+            &InstanceManager::new(),
+            &WorkloadRequirements::default(),
+            &WorkloadConstraints::default(),
+        );
+
+        assert!(matches!(result, Err(PlacementError::NoAvailableNode(_))));
+    }
+
+    #[test]
+    fn skips_cordoned_agents() {
+        let manager = manager_with(&[("idle", 10.0, 7 * GIB), ("busy", 80.0, GIB)]);
+        manager.set_cordoned("idle", true).unwrap();
+
+        let placer = Placer::new(StrategyKind::Spread.build(), OvercommitRatios::default());
+        let place = || {
+            placer.place(
+                "i",
+                &manager,
+                &InstanceManager::new(),
+                &WorkloadRequirements::default(),
+                &WorkloadConstraints::default(),
+            )
+        };
+
+        assert_eq!(place().unwrap(), "busy");
+
+        manager.set_cordoned("busy", true).unwrap();
+        assert!(matches!(place(), Err(PlacementError::NoAvailableNode(_))));
+    }
+
+    #[test]
+    fn rejects_workloads_without_reporting_agents() {
+        let manager = NodeAgentManager::new();
+        manager
+            .add_agent("silent", "", NodeProperties::default())
+            .unwrap();
+
+        let result = Placer::new(StrategyKind::Spread.build(), OvercommitRatios::default()).place(
+            "i",
+            &manager,
+            &InstanceManager::new(),
+            &WorkloadRequirements::default(),
+            &WorkloadConstraints::default(),
+        );
+
+        assert!(matches!(result, Err(PlacementError::NoAvailableNode(_))));
+    }
+
+    #[test]
+    fn constraints_filter_and_rank_agents() {
+        let labels = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect()
+        };
+        let tainted = |key: &str, effect| NodeProperties {
+            labels: labels(&[("zone", "a")]),
+            taints: vec![NodeTaint {
+                key: key.to_string(),
+                value: String::new(),
+                effect,
+            }],
+        };
+
+        let manager = manager_with_properties(&[
+            (
+                "database",
+                5.0,
+                7 * GIB,
+                tainted("database", TaintEffect::NoSchedule),
+            ),
+            (
+                "spot",
+                10.0,
+                7 * GIB,
+                tainted("spot", TaintEffect::PreferNoSchedule),
+            ),
+            (
+                "busy",
+                70.0,
+                GIB,
+                NodeProperties {
+                    labels: labels(&[("zone", "a"), ("ssd", "")]),
+                    taints: Vec::new(),
+                },
+            ),
+            (
+                "other",
+                0.0,
+                8 * GIB,
+                NodeProperties {
+                    labels: labels(&[("zone", "b")]),
+                    taints: Vec::new(),
+                },
+            ),
+        ]);
+
+        let placer = Placer::new(StrategyKind::Spread.build(), OvercommitRatios::default());
+        let requirements = WorkloadRequirements::default();
+        let mut constraints = WorkloadConstraints {
+            node_selector: labels(&[("zone", "a")]),
+            ..Default::default()
+        };
+
+        // The tainted spot node is only used if nothing else fits
+        let place = |constraints: &WorkloadConstraints| {
+            placer.place(
+                "i",
+                &manager,
+                &InstanceManager::new(),
+                &requirements,
+                constraints,
+            )
+        };
+        assert_eq!(place(&constraints).unwrap(), "busy");
+
+        constraints.preferred = vec![LabelPreference {
+            weight: 1,
+            requirements: vec![LabelRequirement {
+                key: "ssd".to_string(),
+                operator: LabelOperator::DoesNotExist,
+                values: Vec::new(),
+            }],
+        }];
+        assert_eq!(place(&constraints).unwrap(), "busy");
+
+        constraints.required = vec![LabelRequirement {
+            key: "ssd".to_string(),
+            operator: LabelOperator::DoesNotExist,
+            values: Vec::new(),
+        }];
+        assert_eq!(place(&constraints).unwrap(), "spot");
+
+        constraints.node_selector = labels(&[("zone", "c")]);
+        assert!(matches!(
+            place(&constraints),
+            Err(PlacementError::NoMatchingNode(_))
+        ));
+    }
+}
@@ -0,0 +1,321 @@
+//! Selection of the workload instances evicted to make room for a workload with a higher
+//! priority.
+
+use std::cmp::Ordering;
+
+use tracing::{event, Level};
+
+use crate::managers::instance::manager::{InstanceManager, InstancePlacement};
+use crate::managers::node_agent::manager::NodeAgentManager;
+use crate::managers::node_agent::metrics::NodeAgent;
+
+use super::constraints::WorkloadConstraints;
+use super::placer::Placer;
+use super::requirements::WorkloadRequirements;
+use super::reservation::AvailableResources;
+
+/// The instances to evict from a node agent so that a workload can be placed on it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Preemption {
+    /// The ID of the agent that will run the workload.
+    pub agent_id: String,
+    /// The IDs of the instances to evict, lowest priority first.
+    pub victims: Vec<String>,
+}
+
+/// The instances to evict from a node agent, with the rank of the eviction.
+struct Eviction {
+    /// The ID of the agent.
+    agent_id: String,
+    /// Whether the workload does not tolerate some of the `PreferNoSchedule` taints of the agent.
+    avoided: bool,
+    /// The highest priority of the evicted instances.
+    highest_priority: i32,
+    /// The IDs of the instances to evict, lowest priority first.
+    victims: Vec<String>,
+}
+
+impl Eviction {
+    /// Check whether this eviction disrupts the cluster less than another one.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The other eviction.
+    fn is_better_than(&self, other: &Eviction) -> bool {
+        let ordering = other
+            .avoided
+            .cmp(&self.avoided)
+            .then(other.highest_priority.cmp(&self.highest_priority))
+            .then(other.victims.len().cmp(&self.victims.len()))
+            .then(other.agent_id.cmp(&self.agent_id));
+
+        ordering == Ordering::Greater
+    }
+}
+
+impl Placer {
+    /// Select the node agent that should run a workload by evicting some of the instances placed
+    /// on it, when no agent has enough resources left for the workload.
+    ///
+    /// Only the instances with a lower priority than the workload can be evicted. On each
+    /// healthy, uncordoned agent satisfying the constraints of the workload, the instances with
+    /// the lowest priority are selected until the workload fits, then the selected instances
+    /// whose eviction turns out to be unnecessary are spared. The agent whose victims have the lowest priority
+    /// is chosen, then the one with the fewest victims.
+    ///
+    /// Returns `None` if evicting lower-priority instances cannot make room for the workload.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - The ID of the workload instance to place.
+    /// * `manager` - The node agent manager holding the agents of the cluster.
+    /// * `instances` - The instance manager holding the instances placed on each agent.
+    /// * `requirements` - The resources required by the workload.
+    /// * `constraints` - The constraints on the nodes that can run the workload.
+    /// * `priority` - The priority of the workload.
+    pub fn preempt(
+        &self,
+        instance_id: &str,
+        manager: &NodeAgentManager,
+        instances: &InstanceManager,
+        requirements: &WorkloadRequirements,
+        constraints: &WorkloadConstraints,
+        priority: i32,
+    ) -> Option<Preemption> {
+        let mut best: Option<Eviction> = None;
+
+        for entry in manager.agents().filter(|entry| entry.is_schedulable()) {
+            let (id, agent) = entry.pair();
+
+            if !constraints.allows(agent) {
+                continue;
+            }
+
+            let mut candidates: Vec<_> = instances
+                .agent_instances(id)
+                .into_iter()
+                .filter(|(_, placement)| placement.priority() < priority)
+                .collect();
+            candidates.sort_by(|(a_id, a), (b_id, b)| {
+                a.priority().cmp(&b.priority()).then(a_id.cmp(b_id))
+            });
+
+            let Some(victims) = self.select_victims(id, agent, instances, requirements, candidates)
+            else {
+                continue;
+            };
+
+            let eviction = Eviction {
+                agent_id: id.clone(),
+                avoided: constraints.avoids(agent),
+                highest_priority: victims
+                    .iter()
+                    .map(|(_, placement)| placement.priority())
+                    .max()
+                    .unwrap_or(i32::MIN),
+                victims: victims.into_iter().map(|(id, _)| id).collect(),
+            };
+
+            event!(
+                Level::TRACE,
+                instance_id,
+                agent_id = id,
+                avoided = eviction.avoided,
+                highest_priority = eviction.highest_priority,
+                victims = ?eviction.victims,
+                "Evaluated preemption on node agent"
+            );
+
+            if best
+                .as_ref()
+                .is_none_or(|best| eviction.is_better_than(best))
+            {
+                best = Some(eviction);
+            }
+        }
+
+        best.map(|eviction| Preemption {
+            agent_id: eviction.agent_id,
+            victims: eviction.victims,
+        })
+    }
+
+    /// Select the instances to evict from a node agent so that a workload fits on it.
+    ///
+    /// Returns `None` if evicting all the candidates does not make room for the workload.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    /// * `agent` - The node agent.
+    /// * `instances` - The instance manager holding the resources reserved on the agent.
+    /// * `requirements` - The resources required by the workload.
+    /// * `candidates` - The instances that may be evicted, in eviction order.
+    fn select_victims(
+        &self,
+        agent_id: &str,
+        agent: &NodeAgent,
+        instances: &InstanceManager,
+        requirements: &WorkloadRequirements,
+        candidates: Vec<(String, InstancePlacement)>,
+    ) -> Option<Vec<(String, InstancePlacement)>> {
+        let reserved = instances.reserved(agent_id);
+        let fits = |victims: &[(String, InstancePlacement)]| {
+            let mut released = WorkloadRequirements::default();
+
+            for (_, placement) in victims {
+                released.add(placement.resources());
+            }
+
+            AvailableResources::after_release(agent, &reserved, &released, self.overcommit_ratios())
+                .is_some_and(|available| available.fits(requirements))
+        };
+
+        // Evict the instances with the lowest priority until the workload fits
+        let mut victims = Vec::new();
+
+        for candidate in candidates {
+            if fits(&victims) {
+                break;
+            }
+
+            victims.push(candidate);
+        }
+
+        if !fits(&victims) {
+            return None;
+        }
+
+        // Spare the victims that do not need to be evicted, highest priority first
+        for index in (0..victims.len()).rev() {
+            let spared = victims.remove(index);
+
+            if !fits(&victims) {
+                victims.insert(index, spared);
+            }
+        }
+
+        Some(victims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::node_agent::metrics::{NodeCpu, NodeMemory, NodeMetrics};
+    use crate::managers::node_agent::properties::NodeProperties;
+    use crate::placement::reservation::OvercommitRatios;
+    use crate::placement::strategy::StrategyKind;
+
+    const GIB: u64 = 1024 * 1024 * 1024;
+
+    /// Create a manager holding idle agents with 8 GiB of memory.
+    fn manager_with(ids: &[&str]) -> NodeAgentManager {
+        let manager = NodeAgentManager::new();
+
+        for id in ids {
+            manager
+                .add_agent(id, "", NodeProperties::default())
+                .unwrap();
+            manager
+                .update_node_status(
+                    id,
+                    NodeMetrics {
+                        cpu: Some(NodeCpu {
+                            load: 0.0,
+                            cores: Vec::new(),
+                        }),
+                        memory: Some(NodeMemory {
+                            total: 8 * GIB,
+                            free: 8 * GIB,
+                        }),
+                        ..Default::default()
+                    },
+                )
+                .unwrap();
+        }
+
+        manager
+    }
+
+    fn memory(memory: u64) -> WorkloadRequirements {
+        WorkloadRequirements {
+            cpu: 0.0,
+            memory,
+            disk: 0,
+        }
+    }
+
+    fn preempt(
+        manager: &NodeAgentManager,
+        instances: &InstanceManager,
+        requirements: &WorkloadRequirements,
+        priority: i32,
+    ) -> Option<Preemption> {
+        Placer::new(StrategyKind::Spread.build(), OvercommitRatios::default()).preempt(
+            "new",
+            manager,
+            instances,
+            requirements,
+            &WorkloadConstraints::default(),
+            priority,
+        )
+    }
+
+    #[test]
+    fn evicts_the_lowest_priority_instances_needed() {
+        let manager = manager_with(&["node"]);
+        let instances = InstanceManager::new();
+
+        instances
+            .add_instance("batch", "node", memory(2 * GIB), 0)
+            .unwrap();
+        instances
+            .add_instance("small", "node", memory(GIB), 1)
+            .unwrap();
+        instances
+            .add_instance("large", "node", memory(4 * GIB), 2)
+            .unwrap();
+        instances
+            .add_instance("critical", "node", memory(GIB), 10)
+            .unwrap();
+
+        // Evicting the batch instance is not enough, the small one is spared in favor of the
+        // large one
+        let preemption = preempt(&manager, &instances, &memory(5 * GIB), 5).unwrap();
+        assert_eq!(preemption.agent_id, "node");
+        assert_eq!(preemption.victims, ["batch", "large"]);
+
+        // The critical instance cannot be evicted by a workload with a lower priority
+        assert_eq!(preempt(&manager, &instances, &memory(8 * GIB), 5), None);
+        assert_eq!(
+            preempt(&manager, &instances, &memory(4 * GIB), 0),
+            None,
+            "instances with the same priority are never evicted"
+        );
+    }
+
+    #[test]
+    fn prefers_the_node_with_the_least_important_victims() {
+        let manager = manager_with(&["a", "b"]);
+        let instances = InstanceManager::new();
+
+        instances
+            .add_instance("a1", "a", memory(4 * GIB), 1)
+            .unwrap();
+        instances
+            .add_instance("a2", "a", memory(4 * GIB), 1)
+            .unwrap();
+        instances
+            .add_instance("b1", "b", memory(8 * GIB), 3)
+            .unwrap();
+
+        let preemption = preempt(&manager, &instances, &memory(8 * GIB), 5).unwrap();
+        assert_eq!(preemption.agent_id, "a");
+        assert_eq!(preemption.victims, ["a1", "a2"]);
+
+        let preemption = preempt(&manager, &instances, &memory(4 * GIB), 5).unwrap();
+        assert_eq!(preemption.agent_id, "a");
+        assert_eq!(preemption.victims, ["a1"]);
+    }
+}
@@ -0,0 +1,151 @@
+//! Resources required by a workload to be placed on a node.
+
+use orka_proto::scheduler_controller::Workload;
+use serde::{Deserialize, Serialize};
+
+/// Number of bytes in a mebibyte, the unit used for memory and disk limits in workloads.
+const BYTES_PER_MEBIBYTE: u64 = 1024 * 1024;
+
+/// The resources a node must have available to run a workload, which are reserved on the node
+/// once the workload is placed on it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkloadRequirements {
+    /// CPU share required by the workload, as a percentage of the node CPU capacity.
+    /// Lower bound is `0.0`, upper bound is `100.0`.
+    pub cpu: f64,
+    /// Memory required by the workload, in bytes.
+    pub memory: u64,
+    /// Disk space required by the workload, in bytes.
+    pub disk: u64,
+}
+
+impl From<&Workload> for WorkloadRequirements {
+    /// Extract the requirements of a workload from its resource limits. Missing or negative
+    /// limits are considered as not requiring anything.
+    ///
+    /// The CPU limit is a percentage of the node CPU capacity, the memory and disk limits are
+    /// expressed in mebibytes.
+    fn from(workload: &Workload) -> Self {
+        let Some(limits) = &workload.resource_limits else {
+            return Self::default();
+        };
+
+        Self::from_limits(
+            limits.cpu.unwrap_or(0),
+            limits.memory.unwrap_or(0),
+            limits.disk.unwrap_or(0),
+        )
+    }
+}
+
+impl WorkloadRequirements {
+    /// Create the requirements from resource amounts in the units of the workloads. Negative
+    /// amounts are considered as not requiring anything.
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - The CPU share, as a percentage of the node CPU capacity.
+    /// * `memory` - The memory, in mebibytes.
+    /// * `disk` - The disk space, in mebibytes.
+    pub fn from_limits(cpu: i32, memory: i32, disk: i32) -> Self {
+        let mebibytes =
+            |amount: i32| u64::try_from(amount.max(0)).unwrap_or(0) * BYTES_PER_MEBIBYTE;
+
+        Self {
+            cpu: f64::from(cpu.clamp(0, 100)),
+            memory: mebibytes(memory),
+            disk: mebibytes(disk),
+        }
+    }
+
+    /// Add the resources of another workload to these resources.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The resources to add.
+    pub fn add(&mut self, other: &WorkloadRequirements) {
+        self.cpu += other.cpu;
+        self.memory = self.memory.saturating_add(other.memory);
+        self.disk = self.disk.saturating_add(other.disk);
+    }
+
+    /// Subtract the resources of another workload from these resources, without going below
+    /// zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The resources to subtract.
+    pub fn subtract(&mut self, other: &WorkloadRequirements) {
+        self.cpu = (self.cpu - other.cpu).max(0.0);
+        self.memory = self.memory.saturating_sub(other.memory);
+        self.disk = self.disk.saturating_sub(other.disk);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use orka_proto::scheduler_controller::workload::Resources;
+
+    use super::*;
+
+    #[test]
+    fn extracts_the_requirements_of_a_workload() {
+        let workload = Workload {
+            resource_limits: Some(Resources {
+                cpu: Some(50),
+                memory: Some(256),
+                disk: None,
+            }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            WorkloadRequirements::from(&workload),
+            WorkloadRequirements {
+                cpu: 50.0,
+                memory: 256 * BYTES_PER_MEBIBYTE,
+                disk: 0,
+            }
+        );
+        assert_eq!(
+            WorkloadRequirements::from(&Workload::default()),
+            WorkloadRequirements::default()
+        );
+    }
+
+    #[test]
+    fn ignores_negative_limits_and_caps_the_cpu() {
+        assert_eq!(
+            WorkloadRequirements::from_limits(150, -1, -20),
+            WorkloadRequirements {
+                cpu: 100.0,
+                memory: 0,
+                disk: 0,
+            }
+        );
+        assert_eq!(
+            WorkloadRequirements::from_limits(-5, 1, 2),
+            WorkloadRequirements {
+                cpu: 0.0,
+                memory: BYTES_PER_MEBIBYTE,
+                disk: 2 * BYTES_PER_MEBIBYTE,
+            }
+        );
+    }
+
+    #[test]
+    fn adds_and_subtracts_without_overflowing() {
+        let mut total = WorkloadRequirements::from_limits(30, 100, 10);
+        total.add(&WorkloadRequirements::from_limits(20, 50, 0));
+
+        assert_eq!(total, WorkloadRequirements::from_limits(50, 150, 10));
+
+        total.subtract(&WorkloadRequirements::from_limits(60, 200, 5));
+        assert_eq!(total, WorkloadRequirements::from_limits(0, 0, 5));
+
+        total.memory = u64::MAX;
+        total.add(&WorkloadRequirements::from_limits(0, 1, 0));
+        assert_eq!(total.memory, u64::MAX);
+    }
+}
@@ -0,0 +1,194 @@
+//! Resources of the nodes left for new workloads, once the resources reserved by the workload
+//! instances placed on them are accounted for.
+
+use anyhow::{bail, Result};
+
+use crate::managers::node_agent::metrics::NodeAgent;
+
+use super::requirements::WorkloadRequirements;
+
+/// Ratios by which the resources reserved on a node may exceed its capacity. A ratio of `1.0`
+/// never reserves more than the capacity of the node, a ratio of `2.0` reserves up to twice its
+/// capacity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OvercommitRatios {
+    /// Overcommit ratio of the CPU.
+    pub cpu: f64,
+    /// Overcommit ratio of the memory.
+    pub memory: f64,
+    /// Overcommit ratio of the disk space.
+    pub disk: f64,
+}
+
+impl OvercommitRatios {
+    /// Create the overcommit ratios of the resources.
+    ///
+    /// # Arguments
+    ///
+    /// * `cpu` - Overcommit ratio of the CPU.
+    /// * `memory` - Overcommit ratio of the memory.
+    /// * `disk` - Overcommit ratio of the disk space.
+    ///
+    /// # Errors
+    ///
+    /// * A ratio is not a positive number.
+    pub fn new(cpu: f64, memory: f64, disk: f64) -> Result<Self> {
+        for (resource, ratio) in [("CPU", cpu), ("memory", memory), ("disk", disk)] {
+            if !ratio.is_finite() || ratio <= 0.0 {
+                bail!(
+                    "The {} overcommit ratio must be a positive number, got {}",
+                    resource,
+                    ratio
+                );
+            }
+        }
+
+        Ok(Self { cpu, memory, disk })
+    }
+}
+
+impl Default for OvercommitRatios {
+    fn default() -> Self {
+        Self {
+            cpu: 1.0,
+            memory: 1.0,
+            disk: 1.0,
+        }
+    }
+}
+
+/// The resources of a node left for new workloads.
+///
+/// Each resource is the lowest of what the node reports as free and of what is left of its
+/// capacity, scaled by the overcommit ratio, once the reservations of the instances placed on it
+/// are subtracted. The reported metrics lag behind the placements, while the reservations are
+/// known as soon as a workload is placed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvailableResources {
+    /// CPU share left, as a percentage of the node CPU capacity.
+    pub cpu: f64,
+    /// Memory left, in bytes.
+    pub memory: u64,
+    /// Total memory of the node, in bytes.
+    pub memory_total: u64,
+    /// Disk space left on a single disk, in bytes. `None` if the node does not report its disks.
+    pub disk: Option<u64>,
+}
+
+impl AvailableResources {
+    /// Compute the resources of a node agent left for new workloads.
+    ///
+    /// Returns `None` if the agent never reported its metrics.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent` - The node agent to evaluate.
+    /// * `reserved` - The resources reserved by the instances placed on the agent.
+    /// * `ratios` - The overcommit ratios of the resources.
+    pub fn new(
+        agent: &NodeAgent,
+        reserved: &WorkloadRequirements,
+        ratios: &OvercommitRatios,
+    ) -> Option<Self> {
+        Self::after_release(agent, reserved, &WorkloadRequirements::default(), ratios)
+    }
+
+    /// Compute the resources of a node agent left for new workloads once some of the instances
+    /// placed on it are evicted.
+    ///
+    /// The resources of the evicted instances are no longer reserved, and are assumed to be
+    /// freed on the node even though its metrics do not reflect it yet. Returns `None` if the
+    /// agent never reported its metrics.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent` - The node agent to evaluate.
+    /// * `reserved` - The resources reserved by the instances placed on the agent.
+    /// * `released` - The resources reserved by the evicted instances.
+    /// * `ratios` - The overcommit ratios of the resources.
+    pub fn after_release(
+        agent: &NodeAgent,
+        reserved: &WorkloadRequirements,
+        released: &WorkloadRequirements,
+        ratios: &OvercommitRatios,
+    ) -> Option<Self> {
+        let cpu = agent.cpu()?;
+        let memory = agent.memory()?;
+
+        let mut reserved = reserved.clone();
+        reserved.subtract(released);
+
+        let load = (cpu.load - released.cpu).max(0.0);
+        let free = memory
+            .free
+            .saturating_add(released.memory)
+            .min(memory.total);
+
+        let cpu_left = (100.0 * ratios.cpu - reserved.cpu).min(100.0 - load);
+        let memory_left = scaled(memory.total, ratios.memory).saturating_sub(reserved.memory);
+
+        let disks = agent.disks();
+        let disk = disks
+            .iter()
+            .map(|disk| disk.free.saturating_add(released.disk).min(disk.total))
+            .max()
+            .map(|largest| {
+                let capacity: u64 = disks.iter().map(|disk| disk.total).sum();
+
+                largest.min(scaled(capacity, ratios.disk).saturating_sub(reserved.disk))
+            });
+
+        Some(Self {
+            cpu: cpu_left.max(0.0),
+            memory: free.min(memory_left),
+            memory_total: memory.total,
+            disk,
+        })
+    }
+
+    /// Check whether there are enough resources left to run a workload. The disk space is only
+    /// checked if the node reports its disks.
+    ///
+    /// # Arguments
+    ///
+    /// * `requirements` - The resources required by the workload.
+    pub fn fits(&self, requirements: &WorkloadRequirements) -> bool {
+        self.cpu >= requirements.cpu
+            && self.memory >= requirements.memory
+            && (requirements.disk == 0 || self.disk.is_none_or(|disk| disk >= requirements.disk))
+    }
+}
+
+/// Scale a capacity by an overcommit ratio.
+///
+/// # Arguments
+///
+/// * `capacity` - The capacity to scale.
+/// * `ratio` - The overcommit ratio.
+fn scaled(capacity: u64, ratio: f64) -> u64 {
+    // The conversion saturates on overflow
+    (capacity as f64 * ratio) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_ratios_that_are_not_positive() {
+        assert_eq!(
+            OvercommitRatios::new(1.5, 1.0, 2.0).unwrap(),
+            OvercommitRatios {
+                cpu: 1.5,
+                memory: 1.0,
+                disk: 2.0,
+            }
+        );
+
+        for ratio in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(OvercommitRatios::new(ratio, 1.0, 1.0).is_err());
+            assert!(OvercommitRatios::new(1.0, ratio, 1.0).is_err());
+            assert!(OvercommitRatios::new(1.0, 1.0, ratio).is_err());
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Scoring strategies used to rank the node agents able to run a workload.
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::managers::node_agent::metrics::NodeAgent;
+
+use super::requirements::WorkloadRequirements;
+use super::reservation::AvailableResources;
+
+/// A strategy ranking the node agents that have enough resources to run a workload. The agent
+/// with the highest score is chosen.
+pub trait ScoringStrategy: Send + Sync {
+    /// Score a node agent for a workload.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent` - The node agent to score. Its metrics were reported and it has enough free
+    ///   resources for the workload.
+    /// * `available` - The resources of the node left for new workloads.
+    /// * `requirements` - The resources required by the workload.
+    fn score(
+        &self,
+        agent: &NodeAgent,
+        available: &AvailableResources,
+        requirements: &WorkloadRequirements,
+    ) -> f64;
+}
+
+/// The scoring strategies that can be selected from the command line or the configuration file.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StrategyKind {
+    /// Prefer the nodes with the most resources left, spreading workloads across the cluster.
+    Spread,
+    /// Prefer the nodes with the least resources left, keeping other nodes idle.
+    BinPack,
+    /// Prefer the nodes with the lowest CPU load.
+    LeastLoaded,
+}
+
+impl StrategyKind {
+    /// Create the scoring strategy matching this kind.
+    pub fn build(self) -> Box<dyn ScoringStrategy> {
+        match self {
+            Self::Spread => Box::new(SpreadStrategy {}),
+            Self::BinPack => Box::new(BinPackStrategy {}),
+            Self::LeastLoaded => Box::new(LeastLoadedStrategy {}),
+        }
+    }
+}
+
+/// Compute the share of CPU and memory a node would have left after running a workload, both
+/// between `0.0` and `1.0`.
+///
+/// # Arguments
+///
+/// * `available` - The resources of the node left for new workloads.
+/// * `requirements` - The resources required by the workload.
+fn remaining_share(
+    available: &AvailableResources,
+    requirements: &WorkloadRequirements,
+) -> (f64, f64) {
+    let cpu_left = (available.cpu - requirements.cpu) / 100.0;
+
+    let memory_left = if available.memory_total == 0 {
+        0.0
+    } else {
+        available.memory.saturating_sub(requirements.memory) as f64 / available.memory_total as f64
+    };
+
+    (cpu_left.clamp(0.0, 1.0), memory_left.clamp(0.0, 1.0))
+}
+
+/// Strategy spreading workloads on the nodes with the most CPU and memory left.
+pub struct SpreadStrategy {}
+
+impl ScoringStrategy for SpreadStrategy {
+    fn score(
+        &self,
+        _: &NodeAgent,
+        available: &AvailableResources,
+        requirements: &WorkloadRequirements,
+    ) -> f64 {
+        let (cpu_left, memory_left) = remaining_share(available, requirements);
+
+        (cpu_left + memory_left) / 2.0
+    }
+}
+
+/// Strategy packing workloads on the nodes with the least CPU and memory left.
+pub struct BinPackStrategy {}
+
+impl ScoringStrategy for BinPackStrategy {
+    fn score(
+        &self,
+        _: &NodeAgent,
+        available: &AvailableResources,
+        requirements: &WorkloadRequirements,
+    ) -> f64 {
+        let (cpu_left, memory_left) = remaining_share(available, requirements);
+
+        1.0 - (cpu_left + memory_left) / 2.0
+    }
+}
+
+/// Strategy placing workloads on the nodes with the most CPU left, which are the nodes with the
+/// lowest CPU load unless reservations leave them less CPU.
+pub struct LeastLoadedStrategy {}
+
+impl ScoringStrategy for LeastLoadedStrategy {
+    fn score(
+        &self,
+        _: &NodeAgent,
+        available: &AvailableResources,
+        _: &WorkloadRequirements,
+    ) -> f64 {
+        available.cpu
+    }
+}
@@ -0,0 +1,59 @@
+//! Virtual clock of the simulator, jumping from one event to the next.
+
+use std::time::{Duration, Instant};
+
+/// A clock advancing only when told to, so that hours of cluster activity are simulated in
+/// moments. Its time is expressed as an [`Instant`] for the components of the scheduler that
+/// take the current time as an argument.
+#[derive(Debug, Clone, Copy)]
+pub struct VirtualClock {
+    /// The instant at which the simulation started.
+    origin: Instant,
+    /// The time elapsed since the start of the simulation.
+    elapsed: Duration,
+}
+
+impl VirtualClock {
+    /// Create a new `VirtualClock`, at the start of the simulation.
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Get the current time.
+    pub fn now(&self) -> Instant {
+        self.origin + self.elapsed
+    }
+
+    /// Get the time elapsed since the start of the simulation.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Get the time elapsed between the start of the simulation and an instant, zero if the
+    /// instant is before the start.
+    ///
+    /// # Arguments
+    ///
+    /// * `instant` - The instant.
+    pub fn elapsed_at(&self, instant: Instant) -> Duration {
+        instant.saturating_duration_since(self.origin)
+    }
+
+    /// Move the clock forward. The clock never goes back in time.
+    ///
+    /// # Arguments
+    ///
+    /// * `elapsed` - The time elapsed since the start of the simulation.
+    pub fn advance_to(&mut self, elapsed: Duration) {
+        self.elapsed = self.elapsed.max(elapsed);
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
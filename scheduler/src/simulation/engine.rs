@@ -0,0 +1,831 @@
+//! Discrete-event simulation of the placement of a trace of workloads, driving the placer and
+//! the pending queue of the scheduler with a virtual clock.
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BinaryHeap, HashMap};
+use std::time::Duration;
+
+use orka_proto::scheduler_controller::Workload;
+use tokio::sync::mpsc;
+use tracing::{event, Level};
+
+use crate::managers::instance::manager::InstanceManager;
+use crate::managers::node_agent::manager::NodeAgentManager;
+use crate::managers::node_agent::metrics::{NodeCpu, NodeDisk, NodeMemory, NodeMetrics};
+use crate::managers::node_agent::properties::NodeProperties;
+use crate::managers::pending::queue::{PendingQueue, QueueOptions};
+use crate::placement::constraints::WorkloadConstraints;
+use crate::placement::errors::PlacementError;
+use crate::placement::placer::Placer;
+use crate::placement::requirements::WorkloadRequirements;
+use crate::placement::reservation::OvercommitRatios;
+use crate::placement::strategy::StrategyKind;
+
+use super::clock::VirtualClock;
+use super::report::{ResourceShares, StrategyReport, TimeAverage, WaitStatistics, WorkloadCounts};
+use super::trace::Trace;
+
+/// Number of bytes in a mebibyte, the unit of the memory and disk space in traces.
+const BYTES_PER_MEBIBYTE: u64 = 1024 * 1024;
+
+/// The options of the scheduler shared by all the simulations.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SimulationOptions {
+    /// The ratios by which the resources reserved on a node may exceed its capacity.
+    pub overcommit_ratios: OvercommitRatios,
+    /// The options of the queue of pending workloads.
+    pub queue: QueueOptions,
+}
+
+/// An event of the simulation.
+#[derive(Debug)]
+enum Event {
+    /// A node agent joins the cluster.
+    AgentJoins(SimulatedAgent),
+    /// A node agent leaves the cluster.
+    AgentLeaves(String),
+    /// The controller submits a workload.
+    WorkloadArrives(SimulatedWorkload),
+    /// A workload instance finishes running.
+    InstanceFinishes {
+        /// The ID of the instance.
+        instance_id: String,
+        /// The placement of the instance that finishes, ignored if it was placed again since.
+        placement: u64,
+    },
+}
+
+/// An event scheduled at a time of the simulation.
+#[derive(Debug)]
+struct ScheduledEvent {
+    /// The time of the event, since the start of the simulation.
+    at: Duration,
+    /// The order in which the event was scheduled, breaking ties between simultaneous events.
+    sequence: u64,
+    /// The event.
+    event: Event,
+}
+
+impl PartialEq for ScheduledEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ScheduledEvent {}
+
+impl PartialOrd for ScheduledEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScheduledEvent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.at
+            .cmp(&other.at)
+            .then(self.sequence.cmp(&other.sequence))
+    }
+}
+
+/// A simulated node agent.
+#[derive(Debug, Clone)]
+struct SimulatedAgent {
+    /// The ID of the agent.
+    id: String,
+    /// The properties declared by the agent when it joins the cluster.
+    properties: NodeProperties,
+    /// Total memory of the node, in bytes.
+    memory: u64,
+    /// Disk space of the node, in bytes, if the agent reports its disks.
+    disk: Option<u64>,
+    /// CPU load of the node not caused by the workloads.
+    base_load: f64,
+}
+
+/// A simulated workload.
+#[derive(Debug, Clone)]
+struct SimulatedWorkload {
+    /// The workload sent by the controller.
+    workload: Workload,
+    /// How long the workload runs once placed, forever if unset.
+    runs_for: Option<Duration>,
+    /// Share of the required CPU and memory the workload actually uses.
+    usage: f64,
+}
+
+/// The outcome of an attempt to place a workload.
+enum Attempt {
+    /// The workload was placed on an agent, evicting some instances.
+    Placed {
+        /// The ID of the agent.
+        agent_id: String,
+        /// The IDs of the evicted instances.
+        victims: Vec<String>,
+    },
+    /// No agent can run the workload yet.
+    Pending(PlacementError),
+    /// The workload was refused.
+    Rejected,
+}
+
+/// The state of the cluster between two events, accounted for the time it holds.
+#[derive(Debug, Clone, Copy, Default)]
+struct Snapshot {
+    /// The share of the capacity of the cluster reserved by the workloads.
+    utilization: ResourceShares,
+    /// The share of the free capacity of the cluster outside of its emptiest node.
+    fragmentation: ResourceShares,
+    /// The number of pending workloads.
+    pending: usize,
+}
+
+/// A simulation of a trace with a scoring strategy.
+pub struct Simulation {
+    /// The simulated scoring strategy.
+    strategy: StrategyKind,
+    /// The virtual clock of the simulation.
+    clock: VirtualClock,
+    /// The time at which the simulation stops, if any.
+    end: Option<Duration>,
+    /// The events not processed yet, the earliest first.
+    events: BinaryHeap<Reverse<ScheduledEvent>>,
+    /// The number of events scheduled so far.
+    scheduled: u64,
+
+    /// The placer of the scheduler.
+    placer: Placer,
+    /// The node agents of the cluster.
+    node_agent_manager: NodeAgentManager,
+    /// The placements of the workload instances.
+    instance_manager: InstanceManager,
+    /// The queue of the workloads waiting for a node agent.
+    pending_queue: PendingQueue,
+
+    /// The agents of the cluster, indexed by ID.
+    agents: HashMap<String, SimulatedAgent>,
+    /// The submitted workloads with the number of times they were placed, indexed by instance
+    /// ID.
+    workloads: HashMap<String, (SimulatedWorkload, u64)>,
+
+    /// What happened to the workloads.
+    counts: WorkloadCounts,
+    /// The number of failed placement attempts, by reason.
+    failed_attempts: BTreeMap<&'static str, u64>,
+    /// The time the placed workloads waited for a node, in seconds.
+    waits: Vec<f64>,
+    /// The averages over time of the reserved share of the CPU and memory.
+    utilization: (TimeAverage, TimeAverage),
+    /// The highest reserved share of the CPU and memory.
+    peak_utilization: ResourceShares,
+    /// The averages over time of the fragmentation of the CPU and memory.
+    fragmentation: (TimeAverage, TimeAverage),
+    /// The average over time of the number of pending workloads.
+    pending: TimeAverage,
+}
+
+impl Simulation {
+    /// Create a new `Simulation` of a trace, scheduling the events of its agents and workloads.
+    ///
+    /// # Arguments
+    ///
+    /// * `trace` - The trace to replay.
+    /// * `strategy` - The scoring strategy to simulate.
+    /// * `options` - The options of the scheduler.
+    pub fn new(trace: &Trace, strategy: StrategyKind, options: &SimulationOptions) -> Self {
+        let mut simulation = Self {
+            strategy,
+            clock: VirtualClock::new(),
+            end: trace.duration.map(Duration::from_secs_f64),
+            events: BinaryHeap::new(),
+            scheduled: 0,
+            placer: Placer::new(strategy.build(), options.overcommit_ratios),
+            node_agent_manager: NodeAgentManager::new(),
+            instance_manager: InstanceManager::new(),
+            pending_queue: PendingQueue::new(options.queue),
+            agents: HashMap::new(),
+            workloads: HashMap::new(),
+            counts: WorkloadCounts::default(),
+            failed_attempts: BTreeMap::new(),
+            waits: Vec::new(),
+            utilization: Default::default(),
+            peak_utilization: ResourceShares::default(),
+            fragmentation: Default::default(),
+            pending: TimeAverage::default(),
+        };
+
+        for group in &trace.agents {
+            for id in group.ids() {
+                let agent = SimulatedAgent {
+                    id: id.clone(),
+                    properties: group.properties(),
+                    memory: group.memory_mib.saturating_mul(BYTES_PER_MEBIBYTE),
+                    disk: group
+                        .disk_mib
+                        .map(|disk| disk.saturating_mul(BYTES_PER_MEBIBYTE)),
+                    base_load: group.base_load,
+                };
+
+                simulation.schedule(
+                    Duration::from_secs_f64(group.joins_at),
+                    Event::AgentJoins(agent),
+                );
+
+                if let Some(leaves_at) = group.leaves_at {
+                    simulation.schedule(Duration::from_secs_f64(leaves_at), Event::AgentLeaves(id));
+                }
+            }
+        }
+
+        for group in &trace.workloads {
+            for (arrives_at, workload) in group.arrivals() {
+                let workload = SimulatedWorkload {
+                    workload,
+                    runs_for: group.runs_for.map(Duration::from_secs_f64),
+                    usage: group.usage,
+                };
+
+                simulation.schedule(
+                    Duration::from_secs_f64(arrives_at),
+                    Event::WorkloadArrives(workload),
+                );
+            }
+        }
+
+        simulation
+    }
+
+    /// Schedule an event.
+    ///
+    /// # Arguments
+    ///
+    /// * `at` - The time of the event, since the start of the simulation.
+    /// * `event` - The event.
+    fn schedule(&mut self, at: Duration, event: Event) {
+        self.events.push(Reverse(ScheduledEvent {
+            at,
+            sequence: self.scheduled,
+            event,
+        }));
+        self.scheduled += 1;
+    }
+
+    /// Run the simulation until no event is left, or until the duration of the trace elapsed,
+    /// and report its outcome.
+    pub fn run(mut self) -> StrategyReport {
+        event!(
+            Level::INFO,
+            strategy = ?self.strategy,
+            events = self.events.len(),
+            "Starting the simulation"
+        );
+
+        let mut snapshot = self.snapshot();
+
+        loop {
+            // The pending workloads are retried or abandoned at their deadline, like the queue
+            // of the scheduler does
+            let next_event = self.events.peek().map(|Reverse(scheduled)| scheduled.at);
+            let next_retry = self
+                .pending_queue
+                .next_deadline()
+                .map(|deadline| self.clock.elapsed_at(deadline));
+
+            let Some(at) = next_event.into_iter().chain(next_retry).min() else {
+                break;
+            };
+            let at = at.max(self.clock.elapsed());
+
+            if self.end.is_some_and(|end| at > end) {
+                break;
+            }
+
+            self.advance(at, &snapshot);
+
+            while let Some(Reverse(scheduled)) = self.events.peek() {
+                if scheduled.at > at {
+                    break;
+                }
+
+                if let Some(Reverse(scheduled)) = self.events.pop() {
+                    self.handle(scheduled.event);
+                }
+            }
+
+            self.retry_pending();
+            snapshot = self.snapshot();
+        }
+
+        if let Some(end) = self.end {
+            self.advance(end, &snapshot);
+        }
+
+        self.counts.pending_at_end = self.pending_queue.depth() as u64;
+
+        event!(
+            Level::INFO,
+            strategy = ?self.strategy,
+            simulated_secs = self.clock.elapsed().as_secs(),
+            "Finished the simulation"
+        );
+
+        StrategyReport {
+            strategy: self.strategy,
+            simulated_seconds: self.clock.elapsed().as_secs_f64(),
+            workloads: self.counts,
+            failed_attempts: self.failed_attempts,
+            utilization: ResourceShares {
+                cpu: self.utilization.0.average(),
+                memory: self.utilization.1.average(),
+            },
+            peak_utilization: self.peak_utilization,
+            fragmentation: ResourceShares {
+                cpu: self.fragmentation.0.average(),
+                memory: self.fragmentation.1.average(),
+            },
+            mean_pending: self.pending.average(),
+            queue_wait_seconds: WaitStatistics::new(self.waits),
+        }
+    }
+
+    /// Move the clock forward, accounting for the state of the cluster during the elapsed time.
+    ///
+    /// # Arguments
+    ///
+    /// * `at` - The new time, since the start of the simulation.
+    /// * `snapshot` - The state of the cluster since the last event.
+    fn advance(&mut self, at: Duration, snapshot: &Snapshot) {
+        let seconds = at.saturating_sub(self.clock.elapsed()).as_secs_f64();
+
+        self.utilization.0.add(snapshot.utilization.cpu, seconds);
+        self.utilization.1.add(snapshot.utilization.memory, seconds);
+        self.fragmentation
+            .0
+            .add(snapshot.fragmentation.cpu, seconds);
+        self.fragmentation
+            .1
+            .add(snapshot.fragmentation.memory, seconds);
+        self.pending.add(snapshot.pending as f64, seconds);
+
+        self.clock.advance_to(at);
+    }
+
+    /// Process an event.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event.
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::AgentJoins(agent) => {
+                let id = agent.id.clone();
+
+                if let Err(err) =
+                    self.node_agent_manager
+                        .add_agent(&id, "", agent.properties.clone())
+                {
+                    event!(Level::WARN, error = %err, "Simulated agent could not join");
+                    return;
+                }
+
+                self.agents.insert(id.clone(), agent);
+                self.report_metrics(&id);
+            }
+            Event::AgentLeaves(id) => {
+                if self.node_agent_manager.remove_agent(&id).is_none() {
+                    return;
+                }
+                self.agents.remove(&id);
+
+                // The controller submits the instances of the node again
+                for instance_id in self.instance_manager.remove_agent_instances(&id) {
+                    self.counts.evicted += 1;
+                    self.resubmit(&instance_id);
+                }
+            }
+            Event::WorkloadArrives(workload) => {
+                self.counts.submitted += 1;
+
+                let instance_id = workload.workload.instance_id.clone();
+
+                if self.workloads.contains_key(&instance_id) {
+                    self.counts.rejected += 1;
+                    return;
+                }
+
+                self.workloads.insert(instance_id, (workload.clone(), 0));
+                self.submit(workload.workload);
+            }
+            Event::InstanceFinishes {
+                instance_id,
+                placement,
+            } => {
+                let is_current = self
+                    .workloads
+                    .get(&instance_id)
+                    .is_some_and(|(_, placements)| *placements == placement);
+
+                if !is_current {
+                    return;
+                }
+
+                if let Some(placed) = self.instance_manager.remove_instance(&instance_id) {
+                    self.counts.finished += 1;
+                    self.report_metrics(placed.agent_id());
+                    self.pending_queue.wake_at(self.clock.now());
+                }
+            }
+        }
+    }
+
+    /// Try to place a workload the way the scheduling service does: on the best agent with
+    /// enough resources left, or else by preempting lower-priority instances.
+    ///
+    /// # Arguments
+    ///
+    /// * `workload` - The workload to place.
+    fn try_place(&self, workload: &Workload) -> Attempt {
+        let instance_id = &workload.instance_id;
+        let requirements = WorkloadRequirements::from(workload);
+        let Ok(constraints) = WorkloadConstraints::try_from(workload) else {
+            return Attempt::Rejected;
+        };
+
+        let placement = match self.placer.place(
+            instance_id,
+            &self.node_agent_manager,
+            &self.instance_manager,
+            &requirements,
+            &constraints,
+        ) {
+            Ok(agent_id) => Ok((agent_id, Vec::new())),
+            Err(err @ PlacementError::InsufficientResources(_)) => self
+                .placer
+                .preempt(
+                    instance_id,
+                    &self.node_agent_manager,
+                    &self.instance_manager,
+                    &requirements,
+                    &constraints,
+                    workload.priority,
+                )
+                .map(|preemption| (preemption.agent_id, preemption.victims))
+                .ok_or(err),
+            Err(err) => Err(err),
+        };
+
+        let (agent_id, victims) = match placement {
+            Ok(placement) => placement,
+            Err(err) if err.is_transient() => return Attempt::Pending(err),
+            Err(_) => return Attempt::Rejected,
+        };
+
+        if self
+            .instance_manager
+            .add_instance(instance_id, &agent_id, requirements, workload.priority)
+            .is_err()
+        {
+            return Attempt::Rejected;
+        }
+
+        for victim in &victims {
+            self.instance_manager.remove_instance(victim);
+        }
+
+        Attempt::Placed { agent_id, victims }
+    }
+
+    /// Place a workload submitted by the controller, queueing it if no agent can run it yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `workload` - The workload to place.
+    fn submit(&mut self, workload: Workload) {
+        match self.try_place(&workload) {
+            Attempt::Placed { agent_id, victims } => {
+                self.start(&workload.instance_id, &agent_id, victims, Duration::ZERO)
+            }
+            Attempt::Pending(err) => {
+                self.record_failure(&err);
+
+                // Nobody listens to the statuses of the simulated workloads
+                let (sender, _) = mpsc::channel(1);
+
+                if self
+                    .pending_queue
+                    .push(workload, sender, err.to_string(), self.clock.now())
+                    .is_err()
+                {
+                    self.counts.rejected += 1;
+                }
+            }
+            Attempt::Rejected => self.counts.rejected += 1,
+        }
+    }
+
+    /// Submit again a workload whose instance was evicted.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - The ID of the evicted instance.
+    fn resubmit(&mut self, instance_id: &str) {
+        if let Some((workload, _)) = self.workloads.get(instance_id) {
+            let workload = workload.workload.clone();
+            self.submit(workload);
+        }
+    }
+
+    /// Retry the pending workloads that are due and abandon the ones that waited for too long.
+    fn retry_pending(&mut self) {
+        let now = self.clock.now();
+
+        self.counts.abandoned += self.pending_queue.take_expired(now).len() as u64;
+
+        for pending in self.pending_queue.take_due(now) {
+            match self.try_place(&pending.workload) {
+                Attempt::Placed { agent_id, victims } => {
+                    let instance_id = pending.instance_id().to_string();
+                    self.start(&instance_id, &agent_id, victims, pending.waited(now));
+                }
+                Attempt::Pending(err) => {
+                    self.record_failure(&err);
+
+                    if self
+                        .pending_queue
+                        .requeue(pending, err.to_string(), now)
+                        .is_err()
+                    {
+                        self.counts.rejected += 1;
+                    }
+                }
+                Attempt::Rejected => self.counts.rejected += 1,
+            }
+        }
+    }
+
+    /// Start a placed workload instance, scheduling its end and submitting the evicted instances
+    /// again.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - The ID of the placed instance.
+    /// * `agent_id` - The ID of the agent running the instance.
+    /// * `victims` - The IDs of the instances evicted from the agent.
+    /// * `waited` - How long the workload waited for a node.
+    fn start(&mut self, instance_id: &str, agent_id: &str, victims: Vec<String>, waited: Duration) {
+        self.counts.placed += 1;
+        self.waits.push(waited.as_secs_f64());
+
+        if let Some((workload, placements)) = self.workloads.get_mut(instance_id) {
+            *placements += 1;
+
+            if let Some(runs_for) = workload.runs_for {
+                let event = Event::InstanceFinishes {
+                    instance_id: instance_id.to_string(),
+                    placement: *placements,
+                };
+
+                self.schedule(self.clock.elapsed() + runs_for, event);
+            }
+        }
+
+        self.report_metrics(agent_id);
+
+        for victim in victims {
+            self.counts.preempted += 1;
+            self.resubmit(&victim);
+        }
+    }
+
+    /// Count a failed placement attempt.
+    ///
+    /// # Arguments
+    ///
+    /// * `err` - Why the workload could not be placed.
+    fn record_failure(&mut self, err: &PlacementError) {
+        let reason = match err {
+            PlacementError::NoAvailableNode(_) => "no_available_node",
+            PlacementError::InsufficientResources(_) => "insufficient_resources",
+            PlacementError::NoMatchingNode(_) => "no_matching_node",
+            PlacementError::InvalidConstraints(_) => "invalid_constraints",
+        };
+
+        *self.failed_attempts.entry(reason).or_default() += 1;
+    }
+
+    /// Send the metrics of a node agent, as the agent would after the instances placed on it
+    /// changed. The instances use their share of the resources they reserved.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the agent.
+    fn report_metrics(&mut self, agent_id: &str) {
+        let Some(agent) = self.agents.get(agent_id) else {
+            return;
+        };
+
+        let mut used = WorkloadRequirements::default();
+
+        for (instance_id, placement) in self.instance_manager.agent_instances(agent_id) {
+            let usage = self
+                .workloads
+                .get(&instance_id)
+                .map_or(1.0, |(workload, _)| workload.usage);
+            let resources = placement.resources();
+
+            used.add(&WorkloadRequirements {
+                cpu: resources.cpu * usage,
+                memory: (resources.memory as f64 * usage) as u64,
+                disk: resources.disk,
+            });
+        }
+
+        let metrics = NodeMetrics {
+            cpu: Some(NodeCpu {
+                load: (agent.base_load + used.cpu).min(100.0),
+                cores: Vec::new(),
+            }),
+            memory: Some(NodeMemory {
+                total: agent.memory,
+                free: agent.memory.saturating_sub(used.memory),
+            }),
+            disks: agent
+                .disk
+                .map(|total| NodeDisk {
+                    mount_point: "/".to_string(),
+                    total,
+                    free: total.saturating_sub(used.disk),
+                })
+                .into_iter()
+                .collect(),
+            running_instances: None,
+            ..Default::default()
+        };
+
+        match self
+            .node_agent_manager
+            .update_node_status(agent_id, metrics)
+        {
+            // The pending workloads may fit on the agent that joined
+            Ok(change) if change.gained_capacity => self.pending_queue.wake_at(self.clock.now()),
+            Ok(_) => {}
+            Err(err) => event!(Level::WARN, error = %err, "Unable to update a simulated agent"),
+        }
+    }
+
+    /// Measure the state of the cluster.
+    fn snapshot(&mut self) -> Snapshot {
+        let mut capacity = ResourceShares::default();
+        let mut reserved = ResourceShares::default();
+        let mut free = ResourceShares::default();
+        let mut largest_free = ResourceShares::default();
+
+        for (id, agent) in &self.agents {
+            let reservation = self.instance_manager.reserved(id);
+            let agent_free = ResourceShares {
+                cpu: (100.0 - reservation.cpu).max(0.0),
+                memory: agent.memory.saturating_sub(reservation.memory) as f64,
+            };
+
+            capacity.cpu += 100.0;
+            capacity.memory += agent.memory as f64;
+            reserved.cpu += reservation.cpu;
+            reserved.memory += reservation.memory as f64;
+            free.cpu += agent_free.cpu;
+            free.memory += agent_free.memory;
+            largest_free.cpu = largest_free.cpu.max(agent_free.cpu);
+            largest_free.memory = largest_free.memory.max(agent_free.memory);
+        }
+
+        let share = |part: f64, total: f64| if total > 0.0 { part / total } else { 0.0 };
+        let utilization = ResourceShares {
+            cpu: share(reserved.cpu, capacity.cpu),
+            memory: share(reserved.memory, capacity.memory),
+        };
+
+        self.peak_utilization.cpu = self.peak_utilization.cpu.max(utilization.cpu);
+        self.peak_utilization.memory = self.peak_utilization.memory.max(utilization.memory);
+
+        Snapshot {
+            utilization,
+            fragmentation: ResourceShares {
+                cpu: 1.0 - share(largest_free.cpu, free.cpu).min(1.0),
+                memory: 1.0 - share(largest_free.memory, free.memory).min(1.0),
+            },
+            pending: self.pending_queue.depth(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulation::trace::{AgentTrace, WorkloadTrace};
+
+    fn agent(id: &str, count: usize, memory_mib: u64) -> AgentTrace {
+        AgentTrace {
+            id: id.to_string(),
+            count,
+            memory_mib,
+            disk_mib: None,
+            base_load: 0.0,
+            labels: BTreeMap::new(),
+            taints: Vec::new(),
+            joins_at: 0.0,
+            leaves_at: None,
+        }
+    }
+
+    fn workload(id: &str, count: usize, arrives_at: f64, memory_mib: i32) -> WorkloadTrace {
+        WorkloadTrace {
+            id: id.to_string(),
+            count,
+            arrives_at,
+            interval: 0.0,
+            runs_for: None,
+            cpu: 10,
+            memory_mib,
+            disk_mib: 0,
+            usage: 1.0,
+            priority: 0,
+            node_selector: BTreeMap::new(),
+            tolerations: Vec::new(),
+        }
+    }
+
+    fn simulate(trace: &Trace, strategy: StrategyKind) -> StrategyReport {
+        Simulation::new(trace, strategy, &SimulationOptions::default()).run()
+    }
+
+    #[test]
+    fn bin_pack_leaves_room_for_large_workloads() {
+        let trace = Trace {
+            duration: None,
+            agents: vec![agent("node", 2, 4096)],
+            workloads: vec![
+                workload("small", 2, 0.0, 1024),
+                workload("large", 1, 1.0, 3584),
+            ],
+        };
+
+        let spread = simulate(&trace, StrategyKind::Spread);
+        assert_eq!(spread.workloads.placed, 2);
+        assert_eq!(spread.workloads.abandoned, 1);
+        assert!(spread.failed_attempts["insufficient_resources"] > 1);
+        // The large workload waits for the maximum wait of the queue
+        assert_eq!(spread.simulated_seconds, 301.0);
+        assert_eq!(spread.fragmentation.memory, 0.5);
+
+        let bin_pack = simulate(&trace, StrategyKind::BinPack);
+        assert_eq!(bin_pack.workloads.placed, 3);
+        assert!(bin_pack.failed_attempts.is_empty());
+        assert_eq!(bin_pack.peak_utilization.memory, 5632.0 / 8192.0);
+    }
+
+    #[test]
+    fn replaces_the_instances_of_agents_leaving_the_cluster() {
+        let mut leaving = agent("leaving", 1, 4096);
+        leaving.leaves_at = Some(20.0);
+        let mut late = agent("late", 1, 4096);
+        late.joins_at = 10.0;
+
+        let mut short = workload("short", 1, 0.0, 1024);
+        short.runs_for = Some(5.0);
+
+        let trace = Trace {
+            duration: Some(60.0),
+            agents: vec![leaving, late],
+            workloads: vec![workload("long", 1, 0.0, 2048), short],
+        };
+
+        let report = simulate(&trace, StrategyKind::Spread);
+        assert_eq!(report.simulated_seconds, 60.0);
+        assert_eq!(report.workloads.submitted, 2);
+        assert_eq!(report.workloads.finished, 1);
+        assert_eq!(report.workloads.evicted, 1);
+        assert_eq!(report.workloads.placed, 3);
+        assert_eq!(report.workloads.pending_at_end, 0);
+        // 3 GiB then 2 GiB out of 4 GiB, 2 GiB out of 8 GiB once the late agent joins, then
+        // 2 GiB out of 4 GiB once the first agent leaves
+        let expected = (0.75 * 5.0 + 0.5 * 5.0 + 0.25 * 10.0 + 0.5 * 40.0) / 60.0;
+        assert!((report.utilization.memory - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn workloads_wait_for_an_agent_to_join() {
+        let mut late = agent("late", 1, 4096);
+        late.joins_at = 10.0;
+
+        let trace = Trace {
+            duration: None,
+            agents: vec![late],
+            workloads: vec![workload("early", 1, 0.0, 1024)],
+        };
+
+        let report = simulate(&trace, StrategyKind::LeastLoaded);
+        assert_eq!(report.workloads.placed, 1);
+        assert_eq!(report.failed_attempts["no_available_node"], 4);
+        assert_eq!(report.queue_wait_seconds.max, 10.0);
+        assert_eq!(report.simulated_seconds, 10.0);
+    }
+}
@@ -0,0 +1,7 @@
+//! Offline simulation of the placement of workloads, used to compare the scoring strategies on
+//! a trace of the cluster without running node agents.
+
+pub mod clock;
+pub mod engine;
+pub mod report;
+pub mod trace;
@@ -0,0 +1,161 @@
+//! Report of the simulations, comparing the scoring strategies on the same trace.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::placement::strategy::StrategyKind;
+
+/// The report of the simulation of a trace with several scoring strategies.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    /// The reports of the strategies, in the order they were simulated.
+    pub strategies: Vec<StrategyReport>,
+}
+
+/// The outcome of the simulation of a trace with a scoring strategy.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StrategyReport {
+    /// The simulated scoring strategy.
+    pub strategy: StrategyKind,
+    /// The simulated time, in seconds.
+    pub simulated_seconds: f64,
+    /// What happened to the workloads.
+    pub workloads: WorkloadCounts,
+    /// The number of placement attempts that failed, by reason.
+    pub failed_attempts: BTreeMap<&'static str, u64>,
+    /// The share of the capacity of the cluster reserved by the workloads, averaged over time.
+    pub utilization: ResourceShares,
+    /// The highest share of the capacity of the cluster reserved by the workloads.
+    pub peak_utilization: ResourceShares,
+    /// The share of the free capacity of the cluster outside of its emptiest node, averaged
+    /// over time. Zero when all the free capacity is on a single node, close to one when it is
+    /// scattered in small pieces.
+    pub fragmentation: ResourceShares,
+    /// The number of workloads waiting for a node, averaged over time.
+    pub mean_pending: f64,
+    /// The time the placed workloads waited for a node, in seconds.
+    pub queue_wait_seconds: WaitStatistics,
+}
+
+/// What happened to the workloads during a simulation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WorkloadCounts {
+    /// The number of workloads submitted.
+    pub submitted: u64,
+    /// The number of placements, including the placements of evicted workloads.
+    pub placed: u64,
+    /// The number of workloads that finished running.
+    pub finished: u64,
+    /// The number of instances evicted to make room for a workload with a higher priority.
+    pub preempted: u64,
+    /// The number of instances evicted because their node left the cluster.
+    pub evicted: u64,
+    /// The number of workloads abandoned after waiting for too long.
+    pub abandoned: u64,
+    /// The number of workloads refused because of their invalid placement constraints.
+    pub rejected: u64,
+    /// The number of workloads still waiting for a node at the end of the simulation.
+    pub pending_at_end: u64,
+}
+
+/// A value for each resource of the nodes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ResourceShares {
+    /// The value for the CPU.
+    pub cpu: f64,
+    /// The value for the memory.
+    pub memory: f64,
+}
+
+/// Statistics of durations, in seconds. All zero if no duration was recorded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct WaitStatistics {
+    /// The number of durations.
+    pub count: usize,
+    /// The mean duration.
+    pub mean: f64,
+    /// The median duration.
+    pub p50: f64,
+    /// The 95th percentile of the durations.
+    pub p95: f64,
+    /// The longest duration.
+    pub max: f64,
+}
+
+impl WaitStatistics {
+    /// Compute the statistics of durations.
+    ///
+    /// # Arguments
+    ///
+    /// * `durations` - The durations, in seconds.
+    pub fn new(mut durations: Vec<f64>) -> Self {
+        if durations.is_empty() {
+            return Self::default();
+        }
+
+        durations.sort_by(f64::total_cmp);
+
+        // Nearest-rank percentiles
+        let percentile = |p: f64| {
+            let rank = (p * durations.len() as f64).ceil() as usize;
+            durations[rank.clamp(1, durations.len()) - 1]
+        };
+
+        Self {
+            count: durations.len(),
+            mean: durations.iter().sum::<f64>() / durations.len() as f64,
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            max: durations[durations.len() - 1],
+        }
+    }
+}
+
+/// Average of values changing over time, each value holding until the next one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeAverage {
+    /// The integral of the values over time.
+    weighted_sum: f64,
+    /// The time covered by the integral, in seconds.
+    elapsed: f64,
+}
+
+impl TimeAverage {
+    /// Account for a value held during some time.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The value.
+    /// * `seconds` - How long the value was held.
+    pub fn add(&mut self, value: f64, seconds: f64) {
+        self.weighted_sum += value * seconds;
+        self.elapsed += seconds;
+    }
+
+    /// Get the average of the values, zero if no time elapsed.
+    pub fn average(&self) -> f64 {
+        if self.elapsed > 0.0 {
+            self.weighted_sum / self.elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn computes_nearest_rank_percentiles() {
+        let statistics = WaitStatistics::new((1..=20).rev().map(f64::from).collect());
+
+        assert_eq!(statistics.count, 20);
+        assert_eq!(statistics.mean, 10.5);
+        assert_eq!(statistics.p50, 10.0);
+        assert_eq!(statistics.p95, 19.0);
+        assert_eq!(statistics.max, 20.0);
+        assert_eq!(WaitStatistics::new(Vec::new()), WaitStatistics::default());
+    }
+}
@@ -0,0 +1,328 @@
+//! Trace of synthetic node agents and workload arrivals replayed by the simulator.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use orka_proto::scheduler_controller::workload::{toleration, Resources, Toleration};
+use orka_proto::scheduler_controller::Workload;
+use serde::{Deserialize, Serialize};
+
+use crate::managers::node_agent::properties::{NodeProperties, NodeTaint, TaintEffect};
+
+/// A trace of the cluster: the agents joining and leaving it, and the workloads submitted to it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Trace {
+    /// Seconds after which the simulation stops. Defaults to the time the last event happens.
+    #[serde(default)]
+    pub duration: Option<f64>,
+    /// The node agents of the cluster.
+    #[serde(default)]
+    pub agents: Vec<AgentTrace>,
+    /// The workloads submitted to the scheduler.
+    #[serde(default)]
+    pub workloads: Vec<WorkloadTrace>,
+}
+
+/// A group of identical node agents.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentTrace {
+    /// The ID of the agent, suffixed by its index in the group if the group has several agents.
+    pub id: String,
+    /// The number of agents in the group.
+    #[serde(default = "one")]
+    pub count: usize,
+    /// Total memory of the node, in mebibytes.
+    pub memory_mib: u64,
+    /// Disk space of the node, in mebibytes. The disk space is not checked if unset.
+    #[serde(default)]
+    pub disk_mib: Option<u64>,
+    /// CPU load of the node not caused by the workloads, as a percentage of its capacity.
+    #[serde(default)]
+    pub base_load: f64,
+    /// The labels of the node.
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// The taints of the node.
+    #[serde(default)]
+    pub taints: Vec<NodeTaint>,
+    /// Seconds after which the agents join the cluster.
+    #[serde(default)]
+    pub joins_at: f64,
+    /// Seconds after which the agents leave the cluster, evicting their instances. The agents
+    /// stay until the end of the simulation if unset.
+    #[serde(default)]
+    pub leaves_at: Option<f64>,
+}
+
+/// A group of workloads with the same requirements, arriving one after the other.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WorkloadTrace {
+    /// The ID of the workload instance, suffixed by its index in the group if the group has
+    /// several workloads.
+    pub id: String,
+    /// The number of workloads in the group.
+    #[serde(default = "one")]
+    pub count: usize,
+    /// Seconds after which the first workload of the group arrives.
+    #[serde(default)]
+    pub arrives_at: f64,
+    /// Seconds between the arrivals of two workloads of the group.
+    #[serde(default)]
+    pub interval: f64,
+    /// Seconds during which a workload runs once placed. Workloads run until the end of the
+    /// simulation if unset.
+    #[serde(default)]
+    pub runs_for: Option<f64>,
+    /// CPU share required by the workload, as a percentage of the node CPU capacity.
+    #[serde(default)]
+    pub cpu: i32,
+    /// Memory required by the workload, in mebibytes.
+    #[serde(default)]
+    pub memory_mib: i32,
+    /// Disk space required by the workload, in mebibytes.
+    #[serde(default)]
+    pub disk_mib: i32,
+    /// Share of the required CPU and memory the workload actually uses on its node, between
+    /// `0.0` and `1.0`.
+    #[serde(default = "full_usage")]
+    pub usage: f64,
+    /// The priority of the workload, workloads with a higher priority may preempt it.
+    #[serde(default)]
+    pub priority: i32,
+    /// The labels a node must have, with the same values.
+    #[serde(default)]
+    pub node_selector: BTreeMap<String, String>,
+    /// The taints tolerated by the workload.
+    #[serde(default)]
+    pub tolerations: Vec<TolerationTrace>,
+}
+
+/// A toleration of a workload.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TolerationTrace {
+    /// The key of the tolerated taints, an empty key tolerates every taint.
+    #[serde(default)]
+    pub key: String,
+    /// The value of the tolerated taints, any value is tolerated if unset.
+    #[serde(default)]
+    pub value: Option<String>,
+    /// The effect of the tolerated taints, every effect is tolerated if unset.
+    #[serde(default)]
+    pub effect: Option<TaintEffect>,
+}
+
+/// Default number of agents or workloads in a group.
+fn one() -> usize {
+    1
+}
+
+/// Default share of the required resources used by a workload.
+fn full_usage() -> f64 {
+    1.0
+}
+
+/// Name the members of a group, suffixing the ID with the index of the member if the group has
+/// several members.
+///
+/// # Arguments
+///
+/// * `id` - The ID of the group.
+/// * `count` - The number of members of the group.
+fn member_ids(id: &str, count: usize) -> impl Iterator<Item = String> + '_ {
+    (0..count).map(move |index| match count {
+        1 => id.to_string(),
+        _ => format!("{}-{}", id, index),
+    })
+}
+
+impl Trace {
+    /// Load a trace from a file, in the YAML format if its extension is `.yaml` or `.yml` and in
+    /// the JSON format otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to the trace file.
+    ///
+    /// # Errors
+    ///
+    /// * The file could not be read, or is not a valid trace.
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Unable to read the trace file: {}", path.display()))?;
+
+        let parse = || -> Result<Self> {
+            let trace: Self = match path.extension().and_then(|extension| extension.to_str()) {
+                Some("yaml" | "yml") => serde_yaml::from_str(&content)?,
+                _ => serde_json::from_str(&content)?,
+            };
+
+            trace.validate()?;
+            Ok(trace)
+        };
+
+        parse().with_context(|| format!("Invalid trace file: {}", path.display()))
+    }
+
+    /// Check that the times and the resources of the trace are consistent.
+    ///
+    /// # Errors
+    ///
+    /// * A time is negative or not a number.
+    /// * An agent leaves before it joins.
+    /// * A workload uses more than what it requires.
+    /// * Two agents or two workloads have the same ID.
+    pub fn validate(&self) -> Result<()> {
+        let mut agent_ids = HashSet::new();
+
+        for id in self.agents.iter().flat_map(AgentTrace::ids) {
+            if !agent_ids.insert(id.clone()) {
+                bail!("Several agents have the ID `{}`", id);
+            }
+        }
+
+        let mut instance_ids = HashSet::new();
+
+        for (_, workload) in self.workloads.iter().flat_map(WorkloadTrace::arrivals) {
+            if !instance_ids.insert(workload.instance_id.clone()) {
+                bail!("Several workloads have the ID `{}`", workload.instance_id);
+            }
+        }
+
+        let check_time = |what: &str, id: &str, time: f64| {
+            if !time.is_finite() || time < 0.0 {
+                bail!(
+                    "The {} of `{}` must be a positive number of seconds",
+                    what,
+                    id
+                );
+            }
+
+            Ok(())
+        };
+
+        if let Some(duration) = self.duration {
+            check_time("duration", "the trace", duration)?;
+        }
+
+        for agent in &self.agents {
+            check_time("join time", &agent.id, agent.joins_at)?;
+
+            if let Some(leaves_at) = agent.leaves_at {
+                check_time("leave time", &agent.id, leaves_at)?;
+
+                if leaves_at < agent.joins_at {
+                    bail!("The agent `{}` leaves before it joins", agent.id);
+                }
+            }
+
+            if !(0.0..=100.0).contains(&agent.base_load) {
+                bail!(
+                    "The base load of the agent `{}` must be between 0 and 100",
+                    agent.id
+                );
+            }
+        }
+
+        for workload in &self.workloads {
+            check_time("arrival time", &workload.id, workload.arrives_at)?;
+            check_time("arrival interval", &workload.id, workload.interval)?;
+
+            if let Some(runs_for) = workload.runs_for {
+                check_time("run time", &workload.id, runs_for)?;
+            }
+
+            if !(0.0..=1.0).contains(&workload.usage) {
+                bail!(
+                    "The usage of the workload `{}` must be between 0 and 1",
+                    workload.id
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl AgentTrace {
+    /// Get the IDs of the agents of the group.
+    pub fn ids(&self) -> impl Iterator<Item = String> + '_ {
+        member_ids(&self.id, self.count)
+    }
+
+    /// Get the properties declared by the agents of the group when they join the cluster.
+    pub fn properties(&self) -> NodeProperties {
+        NodeProperties {
+            labels: self.labels.clone(),
+            taints: self.taints.clone(),
+        }
+    }
+}
+
+impl WorkloadTrace {
+    /// Get the workloads of the group, with their arrival time in seconds.
+    pub fn arrivals(&self) -> impl Iterator<Item = (f64, Workload)> + '_ {
+        member_ids(&self.id, self.count)
+            .enumerate()
+            .map(|(index, id)| {
+                (
+                    self.arrives_at + self.interval * index as f64,
+                    self.workload(id),
+                )
+            })
+    }
+
+    /// Build the workload sent by the controller for a member of the group.
+    ///
+    /// # Arguments
+    ///
+    /// * `instance_id` - The ID of the workload instance.
+    fn workload(&self, instance_id: String) -> Workload {
+        Workload {
+            instance_id,
+            resource_limits: Some(Resources {
+                cpu: Some(self.cpu),
+                memory: Some(self.memory_mib),
+                disk: Some(self.disk_mib),
+            }),
+            node_selector: self
+                .node_selector
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            tolerations: self.tolerations.iter().map(to_toleration).collect(),
+            priority: self.priority,
+            ..Default::default()
+        }
+    }
+}
+
+/// Convert a toleration of the trace to the toleration sent by the controller.
+///
+/// # Arguments
+///
+/// * `toleration` - The toleration of the trace.
+fn to_toleration(toleration: &TolerationTrace) -> Toleration {
+    let operator = match toleration.value {
+        Some(_) => toleration::Operator::Equal,
+        None => toleration::Operator::Exists,
+    };
+
+    let effect = match toleration.effect {
+        None => toleration::Effect::All,
+        Some(TaintEffect::NoSchedule) => toleration::Effect::NoSchedule,
+        Some(TaintEffect::PreferNoSchedule) => toleration::Effect::PreferNoSchedule,
+    };
+
+    Toleration {
+        key: toleration.key.clone(),
+        operator: operator as i32,
+        value: toleration.value.clone().unwrap_or_default(),
+        effect: effect as i32,
+    }
+}
@@ -0,0 +1,212 @@
+//! Cluster state rebuilt from the state log.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::managers::node_agent::metrics::NodeMetrics;
+use crate::managers::node_agent::properties::NodeProperties;
+use crate::placement::requirements::WorkloadRequirements;
+
+/// A change of the cluster state, as recorded in the state log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StateEvent {
+    /// A node agent joined the cluster.
+    AgentJoined {
+        /// The ID of the agent.
+        id: String,
+        /// The address of the agent's `WorkloadService`.
+        address: String,
+        /// The labels and taints declared by the agent.
+        #[serde(flatten)]
+        properties: NodeProperties,
+    },
+
+    /// A node agent left or was removed from the cluster.
+    AgentLeft {
+        /// The ID of the agent.
+        id: String,
+    },
+
+    /// A node agent reported the metrics of its node.
+    AgentMetrics {
+        /// The ID of the agent.
+        id: String,
+        /// The metrics of the node. Records written before the extended metrics were reported
+        /// only hold the CPU and memory metrics.
+        #[serde(flatten)]
+        metrics: NodeMetrics,
+    },
+
+    /// A node agent was cordoned or uncordoned by an administrator.
+    AgentCordoned {
+        /// The ID of the agent.
+        id: String,
+        /// Whether the agent is cordoned.
+        cordoned: bool,
+    },
+
+    /// A workload instance was placed on a node.
+    InstancePlaced {
+        /// The ID of the instance.
+        id: String,
+        /// The ID of the node agent running the instance.
+        agent_id: String,
+        /// The resources reserved for the instance on the node.
+        #[serde(default)]
+        resources: WorkloadRequirements,
+        /// The priority of the instance's workload.
+        #[serde(default)]
+        priority: i32,
+    },
+
+    /// A workload instance was forgotten.
+    InstanceRemoved {
+        /// The ID of the instance.
+        id: String,
+    },
+}
+
+/// A node agent, as persisted in the cluster state.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PersistedAgent {
+    /// The address of the agent's `WorkloadService`.
+    pub address: String,
+    /// The labels and taints declared by the agent.
+    pub properties: NodeProperties,
+    /// The last metrics reported by the agent.
+    pub metrics: NodeMetrics,
+    /// Whether the agent is cordoned.
+    pub cordoned: bool,
+}
+
+/// A workload instance, as persisted in the cluster state.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PersistedInstance {
+    /// The ID of the node agent running the instance.
+    pub agent_id: String,
+    /// The resources reserved for the instance on the node.
+    pub resources: WorkloadRequirements,
+    /// The priority of the instance's workload.
+    pub priority: i32,
+}
+
+/// The state of the cluster, as known when the scheduler last ran.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClusterState {
+    /// The node agents of the cluster, indexed by agent ID.
+    agents: BTreeMap<String, PersistedAgent>,
+
+    /// The workload instances of the cluster, indexed by instance ID.
+    instances: BTreeMap<String, PersistedInstance>,
+}
+
+impl ClusterState {
+    /// Apply a change to the state.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The change to apply.
+    pub fn apply(&mut self, event: StateEvent) {
+        match event {
+            StateEvent::AgentJoined {
+                id,
+                address,
+                properties,
+            } => {
+                let agent = self.agents.entry(id).or_default();
+                agent.address = address;
+                agent.properties = properties;
+            }
+            StateEvent::AgentLeft { id } => {
+                self.agents.remove(&id);
+            }
+            StateEvent::AgentMetrics { id, metrics } => {
+                if let Some(agent) = self.agents.get_mut(&id) {
+                    agent.metrics = metrics;
+                }
+            }
+            StateEvent::AgentCordoned { id, cordoned } => {
+                if let Some(agent) = self.agents.get_mut(&id) {
+                    agent.cordoned = cordoned;
+                }
+            }
+            StateEvent::InstancePlaced {
+                id,
+                agent_id,
+                resources,
+                priority,
+            } => {
+                self.instances.insert(
+                    id,
+                    PersistedInstance {
+                        agent_id,
+                        resources,
+                        priority,
+                    },
+                );
+            }
+            StateEvent::InstanceRemoved { id } => {
+                self.instances.remove(&id);
+            }
+        }
+    }
+
+    /// Get the smallest list of changes leading to this state.
+    pub fn events(&self) -> Vec<StateEvent> {
+        let mut events = Vec::new();
+
+        for (id, agent) in &self.agents {
+            events.push(StateEvent::AgentJoined {
+                id: id.clone(),
+                address: agent.address.clone(),
+                properties: agent.properties.clone(),
+            });
+
+            if agent.metrics != NodeMetrics::default() {
+                events.push(StateEvent::AgentMetrics {
+                    id: id.clone(),
+                    metrics: agent.metrics.clone(),
+                });
+            }
+
+            if agent.cordoned {
+                events.push(StateEvent::AgentCordoned {
+                    id: id.clone(),
+                    cordoned: true,
+                });
+            }
+        }
+
+        for (id, instance) in &self.instances {
+            events.push(StateEvent::InstancePlaced {
+                id: id.clone(),
+                agent_id: instance.agent_id.clone(),
+                resources: instance.resources.clone(),
+                priority: instance.priority,
+            });
+        }
+
+        events
+    }
+
+    /// Get the node agents of the cluster and their IDs.
+    pub fn agents(&self) -> impl Iterator<Item = (&String, &PersistedAgent)> {
+        self.agents.iter()
+    }
+
+    /// Get a node agent of the cluster.
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The ID of the agent.
+    pub fn agent(&self, id: &str) -> Option<&PersistedAgent> {
+        self.agents.get(id)
+    }
+
+    /// Get the workload instances of the cluster and their IDs.
+    pub fn instances(&self) -> impl Iterator<Item = (&String, &PersistedInstance)> {
+        self.instances.iter()
+    }
+}
@@ -0,0 +1,672 @@
+//! Append-only log recording the changes of the cluster state.
+
+use std::{
+    collections::BTreeSet,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError, TrySendError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use tracing::{event, Level};
+
+use super::cluster::{ClusterState, StateEvent};
+
+/// Number of records after which the log is compacted.
+const COMPACTION_THRESHOLD: usize = 10_000;
+
+/// Number of requests waiting for the writer before recording a change blocks.
+const WRITER_BACKLOG: usize = 4096;
+
+/// Interval at which the last metrics of the agents are written to the log.
+const METRICS_FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The state log, stored in the data directory as one JSON record per line. The log is
+/// compacted when it is opened and whenever it grows too large, so that it only holds the
+/// records needed to rebuild the current state.
+///
+/// Records are written by a dedicated thread, so that recording a change rarely blocks on the
+/// disk nor contends with other writers. The queue of the writer is bounded: recording a change
+/// waits for the writer once it is full, except for the metrics of the agents, which are dropped
+/// since the next status of the agent replaces them anyway. The metrics are only written for
+/// the agents whose metrics changed, at most once per interval. Dropping the log waits for the
+/// pending records to be written.
+pub struct StateLog {
+    /// The sender of the requests to the writer, only `None` while the log is dropped.
+    sender: Option<mpsc::SyncSender<WriterRequest>>,
+
+    /// The thread writing the records, only `None` while the log is dropped.
+    writer: Option<JoinHandle<()>>,
+}
+
+/// A request sent to the thread writing the log.
+enum WriterRequest {
+    /// Write a record.
+    Record(StateEvent),
+
+    /// Write the log to the disk, then send the outcome back.
+    Persist(mpsc::Sender<Result<()>>),
+}
+
+/// The writing side of the state log.
+struct LogWriter {
+    /// File that contains the log.
+    path: PathBuf,
+
+    /// The log file, opened for appending.
+    file: BufWriter<File>,
+
+    /// The state described by the log, including the metrics not written yet.
+    state: ClusterState,
+
+    /// The IDs of the agents whose metrics changed since they were last written.
+    changed_metrics: BTreeSet<String>,
+
+    /// Number of records in the log file.
+    records: usize,
+}
+
+impl StateLog {
+    /// Open the state log of a data directory, returning it with the state it describes.
+    ///
+    /// Records that cannot be parsed, such as a record truncated by a crash, are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `data_dir` - The data directory of the scheduler.
+    ///
+    /// # Errors
+    ///
+    /// * The log could not be read, compacted or opened for appending.
+    /// * The writing thread could not be started.
+    pub fn open(data_dir: &Path) -> Result<(Self, ClusterState)> {
+        let path = data_dir.join("state.log");
+        let state = read_state(&path)?;
+
+        let (file, records) = compact(&path, &state)?;
+
+        event!(
+            Level::DEBUG,
+            path = %path.display(),
+            records,
+            "Opened the state log"
+        );
+
+        let writer = LogWriter {
+            path,
+            file: BufWriter::new(file),
+            state: state.clone(),
+            changed_metrics: BTreeSet::new(),
+            records,
+        };
+
+        let (sender, receiver) = mpsc::sync_channel(WRITER_BACKLOG);
+
+        let writer = thread::Builder::new()
+            .name("state-log".to_string())
+            .spawn(move || writer.run(receiver))
+            .with_context(|| "Unable to start the state log writer")?;
+
+        let log = Self {
+            sender: Some(sender),
+            writer: Some(writer),
+        };
+
+        Ok((log, state))
+    }
+
+    /// Record a change of the cluster state. Failures are logged but not returned, the
+    /// in-memory state stays authoritative while the scheduler runs.
+    ///
+    /// # Arguments
+    ///
+    /// * `state_event` - The change to record.
+    pub fn append(&self, state_event: StateEvent) {
+        let sent = self
+            .sender
+            .as_ref()
+            .is_some_and(|sender| match state_event {
+                // The metrics can be dropped, the next status of the agent replaces them anyway
+                StateEvent::AgentMetrics { .. } => {
+                    match sender.try_send(WriterRequest::Record(state_event)) {
+                        Ok(()) => true,
+                        Err(TrySendError::Full(_)) => {
+                            event!(
+                                Level::DEBUG,
+                                "The state log writer is busy, dropping the metrics of an agent"
+                            );
+                            true
+                        }
+                        Err(TrySendError::Disconnected(_)) => false,
+                    }
+                }
+                state_event => sender.send(WriterRequest::Record(state_event)).is_ok(),
+            });
+
+        if !sent {
+            event!(
+                Level::WARN,
+                "The state log writer stopped, the change could not be recorded"
+            );
+        }
+    }
+}
+
+impl StateLog {
+    /// Write the recorded changes to the disk, waiting until they are synced. The log is
+    /// compacted on the way, so that the next start restores the state quickly.
+    ///
+    /// # Errors
+    ///
+    /// * The writer stopped.
+    /// * The log could not be written or synced to the disk.
+    pub fn persist(&self) -> Result<()> {
+        let (reply, outcome) = mpsc::channel();
+
+        self.sender
+            .as_ref()
+            .and_then(|sender| sender.send(WriterRequest::Persist(reply)).ok())
+            .with_context(|| "The state log writer stopped")?;
+
+        outcome
+            .recv()
+            .with_context(|| "The state log writer stopped")?
+    }
+}
+
+impl Drop for StateLog {
+    fn drop(&mut self) {
+        // Closing the channel stops the writer once it wrote the pending records
+        drop(self.sender.take());
+
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                event!(Level::WARN, "The state log writer panicked");
+            }
+        }
+    }
+}
+
+impl LogWriter {
+    /// Serve the requests sent to the writer until the log is dropped, writing the changed
+    /// metrics of the agents periodically and once more before stopping.
+    ///
+    /// # Arguments
+    ///
+    /// * `receiver` - The receiver of the requests.
+    fn run(mut self, receiver: mpsc::Receiver<WriterRequest>) {
+        let mut next_metrics_flush = Instant::now() + METRICS_FLUSH_INTERVAL;
+
+        loop {
+            let timeout = next_metrics_flush.saturating_duration_since(Instant::now());
+
+            match receiver.recv_timeout(timeout) {
+                Ok(request) => {
+                    self.handle(request);
+
+                    // Handle the waiting requests before flushing their records at once
+                    for request in receiver.try_iter().take(WRITER_BACKLOG) {
+                        self.handle(request);
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+
+            if Instant::now() >= next_metrics_flush {
+                self.write_metrics();
+                next_metrics_flush = Instant::now() + METRICS_FLUSH_INTERVAL;
+            }
+
+            self.flush();
+        }
+
+        self.write_metrics();
+        self.flush();
+    }
+
+    /// Handle a request sent to the writer.
+    ///
+    /// # Arguments
+    ///
+    /// * `request` - The request.
+    fn handle(&mut self, request: WriterRequest) {
+        match request {
+            // The metrics are only applied, they are written later with the latest ones
+            WriterRequest::Record(StateEvent::AgentMetrics { id, metrics }) => {
+                self.changed_metrics.insert(id.clone());
+                self.state.apply(StateEvent::AgentMetrics { id, metrics });
+            }
+            WriterRequest::Record(state_event) => self.write(state_event),
+            WriterRequest::Persist(reply) => {
+                let _ = reply.send(self.persist());
+            }
+        }
+    }
+
+    /// Write a record to the log, compacting it if it grew too large.
+    ///
+    /// # Arguments
+    ///
+    /// * `state_event` - The change to record.
+    fn write(&mut self, state_event: StateEvent) {
+        if self.append(&state_event) {
+            self.state.apply(state_event);
+            self.compact_if_needed();
+        }
+    }
+
+    /// Write the last metrics of the agents whose metrics changed since they were last written.
+    fn write_metrics(&mut self) {
+        for id in std::mem::take(&mut self.changed_metrics) {
+            // The agent may have left the cluster in the meantime
+            let Some(agent) = self.state.agent(&id) else {
+                continue;
+            };
+
+            let state_event = StateEvent::AgentMetrics {
+                metrics: agent.metrics.clone(),
+                id,
+            };
+            self.append(&state_event);
+        }
+
+        self.compact_if_needed();
+    }
+
+    /// Append a record to the log file, returning whether it was written.
+    ///
+    /// # Arguments
+    ///
+    /// * `state_event` - The change to record.
+    fn append(&mut self, state_event: &StateEvent) -> bool {
+        let result = serde_json::to_string(state_event)
+            .map_err(anyhow::Error::from)
+            .and_then(|record| Ok(writeln!(self.file, "{}", record)?));
+
+        if let Err(err) = result {
+            event!(
+                Level::WARN,
+                error = %err,
+                path = %self.path.display(),
+                "Unable to write to the state log"
+            );
+            return false;
+        }
+
+        self.records += 1;
+        true
+    }
+
+    /// Write the buffered records to the log file.
+    fn flush(&mut self) {
+        if let Err(err) = self.file.flush() {
+            event!(
+                Level::WARN,
+                error = %err,
+                path = %self.path.display(),
+                "Unable to write to the state log"
+            );
+        }
+    }
+
+    /// Compact the log if it grew too large.
+    fn compact_if_needed(&mut self) {
+        if self.records < COMPACTION_THRESHOLD {
+            return;
+        }
+
+        match compact(&self.path, &self.state) {
+            Ok((file, records)) => {
+                event!(Level::DEBUG, records, "Compacted the state log");
+
+                self.file = BufWriter::new(file);
+                self.changed_metrics.clear();
+                self.records = records;
+            }
+            Err(err) => event!(
+                Level::WARN,
+                error = ?err,
+                "Unable to compact the state log"
+            ),
+        }
+    }
+}
+
+impl LogWriter {
+    /// Replace the log with the smallest log describing the state, synced to the disk.
+    ///
+    /// # Errors
+    ///
+    /// * The log could not be written, synced to the disk or opened for appending.
+    fn persist(&mut self) -> Result<()> {
+        let (file, records) = compact(&self.path, &self.state)?;
+
+        event!(Level::DEBUG, records, "Persisted the state log");
+
+        self.file = BufWriter::new(file);
+        self.changed_metrics.clear();
+        self.records = records;
+        Ok(())
+    }
+}
+
+/// Rebuild the state described by a log file. A missing file describes an empty state.
+///
+/// # Arguments
+///
+/// * `path` - The path to the log file.
+///
+/// # Errors
+///
+/// * The log file could not be read.
+fn read_state(path: &Path) -> Result<ClusterState> {
+    let mut state = ClusterState::default();
+
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(state),
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Unable to open the state log: {}", path.display()))
+        }
+    };
+
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line =
+            line.with_context(|| format!("Unable to read the state log: {}", path.display()))?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str(&line) {
+            Ok(state_event) => state.apply(state_event),
+            Err(err) => event!(
+                Level::WARN,
+                line = index + 1,
+                error = %err,
+                "Skipping invalid record of the state log"
+            ),
+        }
+    }
+
+    Ok(state)
+}
+
+/// Replace a log file with the smallest log describing a state, returning the new file opened
+/// for appending and its number of records.
+///
+/// # Arguments
+///
+/// * `path` - The path to the log file.
+/// * `state` - The state the log must describe.
+///
+/// # Errors
+///
+/// * The new log file could not be written, synced to the disk or opened for appending.
+fn compact(path: &Path, state: &ClusterState) -> Result<(File, usize)> {
+    let mut tmp_path = path.to_path_buf();
+    tmp_path.as_mut_os_string().push(".tmp");
+
+    let events = state.events();
+    let mut data = String::new();
+
+    for state_event in &events {
+        data.push_str(&serde_json::to_string(state_event)?);
+        data.push('\n');
+    }
+
+    let mut tmp_file = File::create(&tmp_path)
+        .with_context(|| format!("Unable to create the state log: {}", tmp_path.display()))?;
+
+    tmp_file
+        .write_all(data.as_bytes())
+        .and_then(|_| tmp_file.sync_data())
+        .with_context(|| format!("Unable to write the state log: {}", tmp_path.display()))?;
+
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Unable to replace the state log: {}", path.display()))?;
+
+    let file = File::options()
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Unable to open the state log: {}", path.display()))?;
+
+    Ok((file, events.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::node_agent::metrics::{NodeCpu, NodeMetrics};
+    use crate::managers::node_agent::properties::{NodeProperties, NodeTaint, TaintEffect};
+    use crate::placement::requirements::WorkloadRequirements;
+
+    /// Create an empty data directory for a test.
+    fn data_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "orka-scheduler-state-{}-{}",
+            name,
+            std::process::id()
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn restores_the_recorded_state() {
+        let dir = data_dir("restore");
+
+        let (log, state) = StateLog::open(&dir).unwrap();
+        assert_eq!(state, ClusterState::default());
+
+        let properties = NodeProperties {
+            labels: [("zone".to_string(), "eu-west".to_string())].into(),
+            taints: vec![NodeTaint {
+                key: "database".to_string(),
+                value: String::new(),
+                effect: TaintEffect::NoSchedule,
+            }],
+        };
+
+        log.append(StateEvent::AgentJoined {
+            id: "a".to_string(),
+            address: "10.0.0.1:50052".to_string(),
+            properties: properties.clone(),
+        });
+        log.append(StateEvent::AgentJoined {
+            id: "b".to_string(),
+            address: "10.0.0.2:50052".to_string(),
+            properties: NodeProperties::default(),
+        });
+        log.append(StateEvent::AgentMetrics {
+            id: "a".to_string(),
+            metrics: NodeMetrics {
+                cpu: Some(NodeCpu {
+                    load: 42.0,
+                    cores: vec![40.0, 44.0],
+                }),
+                running_instances: Some(1),
+                ..Default::default()
+            },
+        });
+        let resources = WorkloadRequirements {
+            cpu: 25.0,
+            memory: 512,
+            disk: 0,
+        };
+
+        log.append(StateEvent::InstancePlaced {
+            id: "i1".to_string(),
+            agent_id: "a".to_string(),
+            resources: resources.clone(),
+            priority: 10,
+        });
+        log.append(StateEvent::InstancePlaced {
+            id: "i2".to_string(),
+            agent_id: "b".to_string(),
+            resources: WorkloadRequirements::default(),
+            priority: 0,
+        });
+        log.append(StateEvent::AgentCordoned {
+            id: "a".to_string(),
+            cordoned: true,
+        });
+        log.append(StateEvent::AgentLeft {
+            id: "b".to_string(),
+        });
+        log.append(StateEvent::InstanceRemoved {
+            id: "i2".to_string(),
+        });
+        drop(log);
+
+        let (_, state) = StateLog::open(&dir).unwrap();
+
+        let agents: Vec<_> = state.agents().collect();
+        assert_eq!(agents.len(), 1);
+        assert_eq!(agents[0].0, "a");
+        assert_eq!(agents[0].1.address, "10.0.0.1:50052");
+        assert_eq!(agents[0].1.properties, properties);
+        assert_eq!(
+            agents[0].1.metrics.cpu.as_ref().map(|cpu| cpu.load),
+            Some(42.0)
+        );
+        assert_eq!(agents[0].1.metrics.running_instances, Some(1));
+        assert!(agents[0].1.cordoned);
+
+        let instances: Vec<_> = state.instances().collect();
+        assert_eq!(instances.len(), 1);
+        assert_eq!(instances[0].0, "i1");
+        assert_eq!(instances[0].1.agent_id, "a");
+        assert_eq!(instances[0].1.resources, resources);
+        assert_eq!(instances[0].1.priority, 10);
+
+        // The log was compacted when it was opened again
+        let records = fs::read_to_string(dir.join("state.log")).unwrap();
+        assert_eq!(records.lines().count(), 4);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn persists_the_recorded_changes_while_open() {
+        let dir = data_dir("persist");
+
+        let (log, _) = StateLog::open(&dir).unwrap();
+
+        log.append(StateEvent::AgentJoined {
+            id: "a".to_string(),
+            address: String::new(),
+            properties: NodeProperties::default(),
+        });
+        log.append(StateEvent::AgentJoined {
+            id: "b".to_string(),
+            address: String::new(),
+            properties: NodeProperties::default(),
+        });
+        log.append(StateEvent::AgentLeft {
+            id: "b".to_string(),
+        });
+        log.persist().unwrap();
+
+        // The log is compacted and readable before the scheduler exits
+        let state = read_state(&dir.join("state.log")).unwrap();
+        assert_eq!(state.agents().count(), 1);
+
+        let records = fs::read_to_string(dir.join("state.log")).unwrap();
+        assert_eq!(records.lines().count(), 1);
+
+        drop(log);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_the_last_metrics_of_the_agents_only() {
+        let dir = data_dir("metrics");
+
+        let (log, _) = StateLog::open(&dir).unwrap();
+
+        log.append(StateEvent::AgentJoined {
+            id: "a".to_string(),
+            address: String::new(),
+            properties: NodeProperties::default(),
+        });
+
+        for load in 1..=100 {
+            log.append(StateEvent::AgentMetrics {
+                id: "a".to_string(),
+                metrics: NodeMetrics {
+                    cpu: Some(NodeCpu {
+                        load: f64::from(load),
+                        cores: Vec::new(),
+                    }),
+                    ..Default::default()
+                },
+            });
+        }
+        drop(log);
+
+        let records = fs::read_to_string(dir.join("state.log")).unwrap();
+        assert_eq!(records.lines().count(), 2);
+
+        let state = read_state(&dir.join("state.log")).unwrap();
+        assert_eq!(
+            state
+                .agent("a")
+                .and_then(|agent| agent.metrics.cpu.as_ref())
+                .map(|cpu| cpu.load),
+            Some(100.0)
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn skips_truncated_records() {
+        let dir = data_dir("truncated");
+
+        fs::write(
+            dir.join("state.log"),
+            "{\"event\":\"agent_joined\",\"id\":\"a\",\"address\":\"\"}\n{\"event\":\"agent_jo",
+        )
+        .unwrap();
+
+        let (_, state) = StateLog::open(&dir).unwrap();
+        assert_eq!(state.agents().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reads_metrics_recorded_without_the_extended_fields() {
+        let dir = data_dir("legacy-metrics");
+
+        fs::write(
+            dir.join("state.log"),
+            concat!(
+                "{\"event\":\"agent_joined\",\"id\":\"a\",\"address\":\"\"}\n",
+                "{\"event\":\"agent_metrics\",\"id\":\"a\",\"cpu\":{\"load\":12.5},",
+                "\"memory\":{\"total\":8,\"free\":4}}\n",
+            ),
+        )
+        .unwrap();
+
+        let (_, state) = StateLog::open(&dir).unwrap();
+        let (_, agent) = state.agents().next().unwrap();
+
+        assert_eq!(agent.metrics.cpu.as_ref().map(|cpu| cpu.load), Some(12.5));
+        assert_eq!(
+            agent.metrics.memory.as_ref().map(|memory| memory.free),
+            Some(4)
+        );
+        assert!(agent.metrics.disks.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,4 @@
+//! Persistence of the cluster state across restarts of the scheduler.
+
+pub mod cluster;
+pub mod log;
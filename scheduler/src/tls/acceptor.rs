@@ -0,0 +1,141 @@
+//! Acceptance of the TLS connections of the gRPC server.
+
+use std::{io::BufReader, net::SocketAddr, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{
+    rustls::{
+        server::AllowAnyAnonymousOrAuthenticatedClient, Certificate, RootCertStore, ServerConfig,
+    },
+    server::TlsStream,
+    TlsAcceptor,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::{event, Level};
+
+use super::resolver::ReloadableCertResolver;
+
+/// Protocol negotiated with the clients, gRPC requires HTTP/2.
+const ALPN_H2: &[u8] = b"h2";
+
+/// Time given to a client to complete the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of established connections waiting to be picked up by the gRPC server.
+const ACCEPT_BACKLOG: usize = 64;
+
+/// Time to wait before accepting connections again after a failure, for example when the
+/// process ran out of file descriptors.
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
+/// Build the TLS configuration of the gRPC server.
+///
+/// Clients may present a certificate issued by the scheduler certificate authority. It is
+/// optional so that new agents can enroll, but every other RPC is refused without it by the
+/// [`ClientCertificateLayer`](super::guard::ClientCertificateLayer).
+///
+/// # Arguments
+///
+/// * `resolver` - The resolver providing the identity of the scheduler.
+/// * `ca_cert_data` - The certificate of the certificate authority, in PEM format.
+///
+/// # Errors
+///
+/// * The certificate authority could not be parsed.
+pub fn server_config(
+    resolver: Arc<ReloadableCertResolver>,
+    ca_cert_data: &str,
+) -> Result<ServerConfig> {
+    let mut roots = RootCertStore::empty();
+
+    for cert in rustls_pemfile::certs(&mut BufReader::new(ca_cert_data.as_bytes()))
+        .with_context(|| "Unable to parse the certificate authority")?
+    {
+        roots
+            .add(&Certificate(cert))
+            .with_context(|| "Unable to trust the certificate authority")?;
+    }
+
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed())
+        .with_cert_resolver(resolver);
+    config.alpn_protocols.push(ALPN_H2.to_vec());
+
+    Ok(config)
+}
+
+/// Listen for TCP connections and perform their TLS handshake, returning the stream of
+/// established connections to serve.
+///
+/// The handshakes run in the background, so that a slow client cannot delay the others. Failed
+/// handshakes are logged and dropped.
+///
+/// # Arguments
+///
+/// * `bind_address` - The address to listen on.
+/// * `config` - The TLS configuration of the server.
+///
+/// # Errors
+///
+/// * The address could not be bound.
+pub async fn tls_incoming(
+    bind_address: SocketAddr,
+    config: ServerConfig,
+) -> Result<ReceiverStream<std::io::Result<TlsStream<TcpStream>>>> {
+    let listener = TcpListener::bind(bind_address)
+        .await
+        .with_context(|| format!("Unable to bind the gRPC server to {}", bind_address))?;
+
+    let acceptor = TlsAcceptor::from(Arc::new(config));
+    let (sender, receiver) = mpsc::channel(ACCEPT_BACKLOG);
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer_address) = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(err) => {
+                    event!(Level::WARN, error = %err, "Failed to accept TCP connection");
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+
+            // The server is shutting down if the receiver was dropped
+            if sender.is_closed() {
+                break;
+            }
+
+            let acceptor = acceptor.clone();
+            let sender = sender.clone();
+
+            tokio::spawn(async move {
+                let stream =
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => stream,
+                        Ok(Err(err)) => {
+                            event!(
+                                Level::DEBUG,
+                                %peer_address,
+                                error = %err,
+                                "TLS handshake failed"
+                            );
+                            return;
+                        }
+                        Err(_) => {
+                            event!(Level::DEBUG, %peer_address, "TLS handshake timed out");
+                            return;
+                        }
+                    };
+
+                let _ = sender.send(Ok(stream)).await;
+            });
+        }
+    });
+
+    Ok(ReceiverStream::new(receiver))
+}
@@ -0,0 +1,48 @@
+//! Subcommands managing the credentials of the operators.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use super::{
+    issuer::CertificateIssuer,
+    manager::{write_pem_pair, TlsManager},
+};
+
+/// Issue a client certificate granting access to the admin service, writing it and its private
+/// key to `<name>.pem` and `<name>.key` in a directory.
+///
+/// # Arguments
+///
+/// * `tls_manager` - The TLS manager of the scheduler, holding the certificate authority.
+/// * `name` - The name of the operator.
+/// * `out_dir` - The directory to write the certificate and private key to.
+///
+/// # Errors
+///
+/// * The certificate authority could not be read, it is created when the scheduler first starts.
+/// * The certificate could not be issued.
+/// * The certificate or private key could not be written, for example because they exist.
+pub fn run_admin_certificate_command(
+    mut tls_manager: TlsManager,
+    name: &str,
+    out_dir: &Path,
+) -> Result<()> {
+    tls_manager
+        .populate_ca_secrets()
+        .with_context(|| "Unable to load the certificate authority of the scheduler")?;
+
+    let (cert_data, key_data) =
+        CertificateIssuer::new(&tls_manager)?.issue_admin_certificate(name)?;
+
+    let cert_path = out_dir.join(format!("{}.pem", name));
+    let key_path = out_dir.join(format!("{}.key", name));
+
+    write_pem_pair(&cert_path, &cert_data, &key_path, &key_data)
+        .with_context(|| "Unable to write the admin certificate")?;
+
+    println!("{}", cert_path.display());
+    println!("{}", key_path.display());
+
+    Ok(())
+}
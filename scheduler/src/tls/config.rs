@@ -3,12 +3,20 @@
 use std::{
     fs,
     io::ErrorKind,
+    net::IpAddr,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use anyhow::{Context, Result};
+use clap::ValueEnum;
+use rcgen::{SignatureAlgorithm, PKCS_ECDSA_P256_SHA256, PKCS_ECDSA_P384_SHA384, PKCS_ED25519};
+use serde::{Deserialize, Serialize};
 use tracing::{event, Level};
 
+/// DNS name always included in the subject alternative names of the generated certificate.
+const DEFAULT_DNS_NAME: &str = "localhost";
+
 /// Configuration for TLS management.
 #[derive(Debug)]
 pub struct TlsConfig {
@@ -17,6 +25,134 @@ pub struct TlsConfig {
 
     /// Whether keypair and certificate can be generated for TLS.
     can_generate_secrets: bool,
+
+    /// Parameters of the generated certificates.
+    certificate_options: CertificateOptions,
+}
+
+/// Parameters of the certificates generated for TLS.
+#[derive(Debug, Clone)]
+pub struct CertificateOptions {
+    /// DNS names included in the subject alternative names of the scheduler certificate.
+    dns_names: Vec<String>,
+
+    /// IP addresses included in the subject alternative names of the scheduler certificate.
+    ip_addresses: Vec<IpAddr>,
+
+    /// Algorithm of the generated keypairs.
+    key_algorithm: KeyAlgorithm,
+
+    /// How long the scheduler certificate is valid for.
+    validity: Duration,
+
+    /// What to do when an existing scheduler certificate lacks some subject alternative names.
+    san_mismatch_policy: SanMismatchPolicy,
+}
+
+/// The algorithms that can be used for the generated keypairs.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyAlgorithm {
+    /// ECDSA on the P-256 curve, signing with SHA-256.
+    EcdsaP256,
+    /// ECDSA on the P-384 curve, signing with SHA-384.
+    EcdsaP384,
+    /// Ed25519.
+    Ed25519,
+}
+
+/// What to do when an existing scheduler certificate lacks some of the requested subject
+/// alternative names.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SanMismatchPolicy {
+    /// Keep the certificate and log a warning.
+    Warn,
+    /// Replace the certificate with a new one, keeping a backup of the previous files. Fails if
+    /// TLS secret generation is disabled.
+    Regenerate,
+}
+
+impl KeyAlgorithm {
+    /// Get the signature algorithm matching this key algorithm.
+    pub fn signature_algorithm(self) -> &'static SignatureAlgorithm {
+        match self {
+            Self::EcdsaP256 => &PKCS_ECDSA_P256_SHA256,
+            Self::EcdsaP384 => &PKCS_ECDSA_P384_SHA384,
+            Self::Ed25519 => &PKCS_ED25519,
+        }
+    }
+}
+
+impl CertificateOptions {
+    /// Create parameters for the certificates generated for TLS.
+    ///
+    /// # Arguments
+    ///
+    /// * `dns_names` - Extra DNS names of the scheduler certificate, `localhost` is always
+    ///   included.
+    /// * `ip_addresses` - IP addresses of the scheduler certificate.
+    /// * `key_algorithm` - Algorithm of the generated keypairs.
+    /// * `validity` - How long the scheduler certificate is valid for.
+    /// * `san_mismatch_policy` - What to do when an existing scheduler certificate lacks some
+    ///   subject alternative names.
+    pub fn new(
+        dns_names: &[String],
+        ip_addresses: &[IpAddr],
+        key_algorithm: KeyAlgorithm,
+        validity: Duration,
+        san_mismatch_policy: SanMismatchPolicy,
+    ) -> Self {
+        let mut all_dns_names = vec![DEFAULT_DNS_NAME.to_string()];
+
+        for name in dns_names {
+            if !all_dns_names.contains(name) {
+                all_dns_names.push(name.clone());
+            }
+        }
+
+        let mut all_ip_addresses = Vec::new();
+
+        for address in ip_addresses {
+            if !all_ip_addresses.contains(address) {
+                all_ip_addresses.push(*address);
+            }
+        }
+
+        Self {
+            dns_names: all_dns_names,
+            ip_addresses: all_ip_addresses,
+            key_algorithm,
+            validity,
+            san_mismatch_policy,
+        }
+    }
+
+    /// Get the DNS names of the scheduler certificate.
+    pub fn dns_names(&self) -> &[String] {
+        &self.dns_names
+    }
+
+    /// Get the IP addresses of the scheduler certificate.
+    pub fn ip_addresses(&self) -> &[IpAddr] {
+        &self.ip_addresses
+    }
+
+    /// Get the algorithm of the generated keypairs.
+    pub fn key_algorithm(&self) -> KeyAlgorithm {
+        self.key_algorithm
+    }
+
+    /// Get how long the scheduler certificate is valid for.
+    pub fn validity(&self) -> Duration {
+        self.validity
+    }
+
+    /// Get what to do when an existing scheduler certificate lacks some subject alternative
+    /// names.
+    pub fn san_mismatch_policy(&self) -> SanMismatchPolicy {
+        self.san_mismatch_policy
+    }
 }
 
 /// Directory and file paths for storing TLS management data.
@@ -30,6 +166,12 @@ pub struct TlsPaths {
 
     /// File that contains the private key.
     private_key_file: PathBuf,
+
+    /// File that contains the certificate of the certificate authority.
+    ca_cert_file: PathBuf,
+
+    /// File that contains the private key of the certificate authority.
+    ca_private_key_file: PathBuf,
 }
 
 impl TlsConfig {
@@ -38,12 +180,18 @@ impl TlsConfig {
     /// # Arguments
     ///
     /// * `base_dir` - The base directory for storing TLS data.
-    /// * `can_generate_secrets` - Whether to automatically generate keypair and certificate for TLS if
-    ///                            not present in the data directory.
-    pub fn new(base_dir: &Path, can_generate_secrets: bool) -> Self {
+    /// * `can_generate_secrets` - Whether to automatically generate keypairs and certificates for
+    ///   TLS and the certificate authority if not present in the data directory.
+    /// * `certificate_options` - Parameters of the generated certificates.
+    pub fn new(
+        base_dir: &Path,
+        can_generate_secrets: bool,
+        certificate_options: CertificateOptions,
+    ) -> Self {
         Self {
             paths: TlsPaths::new(base_dir),
             can_generate_secrets,
+            certificate_options,
         }
     }
 
@@ -67,6 +215,11 @@ impl TlsConfig {
     pub fn can_generate_secrets(&self) -> bool {
         self.can_generate_secrets
     }
+
+    /// Get the parameters of the generated certificates.
+    pub fn certificate_options(&self) -> &CertificateOptions {
+        &self.certificate_options
+    }
 }
 
 impl TlsPaths {
@@ -81,6 +234,8 @@ impl TlsPaths {
         Self {
             cert_file: base_dir.join("scheduler.pem"),
             private_key_file: base_dir.join("scheduler.key"),
+            ca_cert_file: base_dir.join("ca.pem"),
+            ca_private_key_file: base_dir.join("ca.key"),
             base_dir,
         }
     }
@@ -116,4 +271,14 @@ impl TlsPaths {
     pub fn private_key_file(&self) -> &PathBuf {
         &self.private_key_file
     }
+
+    /// Get the path to the X509 certificate file of the certificate authority.
+    pub fn ca_cert_file(&self) -> &PathBuf {
+        &self.ca_cert_file
+    }
+
+    /// Get the path to the private key file of the certificate authority.
+    pub fn ca_private_key_file(&self) -> &PathBuf {
+        &self.ca_private_key_file
+    }
 }
@@ -0,0 +1,32 @@
+//! TLS errors.
+
+use thiserror::Error;
+
+/// Identity error enum to have self-explanatory and compact errors.
+#[derive(Error, Debug)]
+pub enum IdentityError {
+    /// The peer did not present a client certificate.
+    #[error("No client certificate was presented")]
+    MissingCertificate,
+
+    /// The client certificate presented by the peer could not be parsed.
+    #[error("Invalid client certificate: {0}")]
+    InvalidCertificate(String),
+
+    /// The client certificate presented by the peer has no common name.
+    #[error("The client certificate has no common name")]
+    MissingCommonName,
+
+    /// The client certificate was not issued to an operator of the cluster.
+    #[error("The client certificate of `{0}` does not grant access to the admin service")]
+    NotAdmin(String),
+
+    /// The common name of the client certificate does not match the identity claimed by the peer.
+    #[error("The client certificate was issued to `{actual}`, not to `{expected}`")]
+    Mismatch {
+        /// The identity claimed by the peer.
+        expected: String,
+        /// The common name of the client certificate.
+        actual: String,
+    },
+}
@@ -0,0 +1,274 @@
+//! Authentication of the gRPC requests by the client certificate of their peer.
+
+use std::task::{Context, Poll};
+
+use tonic::{
+    body::BoxBody,
+    codegen::{http, BoxFuture},
+    transport::server::{TcpConnectInfo, TlsConnectInfo},
+    Status,
+};
+use tower::{Layer, Service};
+use tracing::{event, Level};
+
+use super::errors::IdentityError;
+
+/// Path of the only RPC callable without a client certificate, so that new agents can enroll.
+const ENROLL_PATH: &str = "/scheduler.agent.LifecycleService/Enroll";
+
+/// Layer refusing the gRPC requests whose peer did not present a client certificate.
+///
+/// The TLS handshake already verified that the presented certificates were issued by the
+/// scheduler certificate authority, but presenting one is optional so that new agents can
+/// enroll. Every other RPC is refused here, before reaching the services, which may further
+/// check the identity of the certificate.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientCertificateLayer {
+    /// Whether the requests must come with a client certificate, which is only possible with TLS.
+    required: bool,
+}
+
+impl ClientCertificateLayer {
+    /// Create a new `ClientCertificateLayer`.
+    ///
+    /// # Arguments
+    ///
+    /// * `required` - Whether the requests must come with a client certificate.
+    pub fn new(required: bool) -> Self {
+        Self { required }
+    }
+}
+
+impl<S> Layer<S> for ClientCertificateLayer {
+    type Service = ClientCertificateGuard<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientCertificateGuard {
+            inner,
+            required: self.required,
+        }
+    }
+}
+
+/// Service refusing the gRPC requests whose peer did not present a client certificate, built by
+/// [`ClientCertificateLayer`].
+#[derive(Debug, Clone)]
+pub struct ClientCertificateGuard<S> {
+    /// The services serving the accepted requests.
+    inner: S,
+
+    /// Whether the requests must come with a client certificate.
+    required: bool,
+}
+
+impl<S, B> Service<http::Request<B>> for ClientCertificateGuard<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        if self.required && request.uri().path() != ENROLL_PATH && !has_peer_certificate(&request) {
+            event!(
+                Level::WARN,
+                path = request.uri().path(),
+                "Refusing gRPC request without a client certificate"
+            );
+
+            let response = Status::from(IdentityError::MissingCertificate).to_http();
+            return Box::pin(async move { Ok(response) });
+        }
+
+        Box::pin(self.inner.call(request))
+    }
+}
+
+/// Check whether the peer of a request presented a client certificate during the TLS handshake.
+///
+/// # Arguments
+///
+/// * `request` - The HTTP request received from the peer.
+fn has_peer_certificate<B>(request: &http::Request<B>) -> bool {
+    request
+        .extensions()
+        .get::<TlsConnectInfo<TcpConnectInfo>>()
+        .and_then(|info| info.peer_certs())
+        .is_some_and(|certs| !certs.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::SocketAddr, sync::Arc};
+
+    use orka_proto::{
+        scheduler_agent::{
+            lifecycle_service_client::LifecycleServiceClient,
+            lifecycle_service_server::LifecycleServiceServer, EnrollmentRequest,
+        },
+        scheduler_controller::{
+            scheduling_service_client::SchedulingServiceClient,
+            scheduling_service_server::SchedulingServiceServer, SchedulingRequest,
+            WorkloadInstance,
+        },
+    };
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa};
+    use tonic::{
+        transport::{self, Channel, ClientTlsConfig, Identity, Server},
+        Code,
+    };
+
+    use super::*;
+    use crate::admission::policy::AdmissionPolicy;
+    use crate::enrollment::token::TokenStore;
+    use crate::grpc::agent_lifecycle_service::AgentLifecycleSvc;
+    use crate::grpc::controller_scheduling_service::{ControllerSchedulingSvc, StatusSenders};
+    use crate::grpc::shutdown::Drain;
+    use crate::managers::events::broadcaster::ClusterEvents;
+    use crate::managers::instance::manager::InstanceManager;
+    use crate::managers::node_agent::client_pool::AgentClientPool;
+    use crate::managers::node_agent::manager::NodeAgentManager;
+    use crate::managers::pending::queue::{PendingQueue, QueueOptions};
+    use crate::metrics::registry::SchedulerMetrics;
+    use crate::placement::placer::Placer;
+    use crate::placement::reservation::OvercommitRatios;
+    use crate::placement::strategy::StrategyKind;
+    use crate::tls::acceptor::{server_config, tls_incoming};
+    use crate::tls::resolver::ReloadableCertResolver;
+
+    /// Serve the lifecycle and scheduling services over TLS on the loopback interface, returning
+    /// the address of the server and the certificate authority, which also issued the client
+    /// certificates.
+    async fn serve() -> (SocketAddr, Certificate) {
+        let mut ca_params = CertificateParams::new(Vec::new());
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = Certificate::from_params(ca_params).unwrap();
+
+        let server_cert =
+            Certificate::from_params(CertificateParams::new(vec!["localhost".to_string()]))
+                .unwrap();
+        let resolver = ReloadableCertResolver::new(
+            &server_cert.serialize_pem_with_signer(&ca).unwrap(),
+            &server_cert.serialize_private_key_pem(),
+        )
+        .unwrap();
+        let config = server_config(Arc::new(resolver), &ca.serialize_pem().unwrap()).unwrap();
+
+        // Reserve a free port, then let the acceptor bind it
+        let address = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let incoming = tls_incoming(address, config).await.unwrap();
+
+        let manager = Arc::new(NodeAgentManager::new());
+        let client_pool = Arc::new(AgentClientPool::new());
+        let metrics = Arc::new(SchedulerMetrics::new().unwrap());
+        let drain = Arc::new(Drain::new());
+        let cluster_events = Arc::new(ClusterEvents::new());
+        let instance_manager = Arc::new(InstanceManager::new());
+        let pending_queue = Arc::new(PendingQueue::new(QueueOptions::default()));
+
+        let data_dir =
+            std::env::temp_dir().join(format!("orka-scheduler-guard-{}", std::process::id()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+
+        let lifecycle_svc = AgentLifecycleSvc::new(
+            Arc::clone(&manager),
+            Arc::clone(&client_pool),
+            Arc::clone(&instance_manager),
+            Arc::new(StatusSenders::new()),
+            Arc::clone(&pending_queue),
+            Arc::new(TokenStore::new(&data_dir)),
+            None,
+            true,
+            Arc::clone(&metrics),
+            Arc::clone(&drain),
+            Arc::clone(&cluster_events),
+            AdmissionPolicy::default(),
+        );
+        let scheduling_svc = ControllerSchedulingSvc::new(
+            manager,
+            client_pool,
+            instance_manager,
+            Placer::new(StrategyKind::Spread.build(), OvercommitRatios::default()),
+            pending_queue,
+            metrics,
+            drain,
+            cluster_events,
+        );
+
+        let router = Server::builder()
+            .layer(ClientCertificateLayer::new(true))
+            .add_service(LifecycleServiceServer::new(lifecycle_svc))
+            .add_service(SchedulingServiceServer::new(scheduling_svc));
+        tokio::spawn(router.serve_with_incoming(incoming));
+
+        (address, ca)
+    }
+
+    /// Connect to the server, presenting a client certificate issued by `ca` if `authenticated`.
+    async fn connect(address: SocketAddr, ca: &Certificate, authenticated: bool) -> Channel {
+        let mut tls = ClientTlsConfig::new()
+            .ca_certificate(transport::Certificate::from_pem(
+                ca.serialize_pem().unwrap(),
+            ))
+            .domain_name("localhost");
+
+        if authenticated {
+            let client_cert = Certificate::from_params(CertificateParams::new(Vec::new())).unwrap();
+            tls = tls.identity(Identity::from_pem(
+                client_cert.serialize_pem_with_signer(ca).unwrap(),
+                client_cert.serialize_private_key_pem(),
+            ));
+        }
+
+        Channel::from_shared(format!("https://{}", address))
+            .unwrap()
+            .tls_config(tls)
+            .unwrap()
+            .connect()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn refuses_anonymous_clients_except_for_enrollment() {
+        let (address, ca) = serve().await;
+
+        let channel = connect(address, &ca, false).await;
+
+        let status = SchedulingServiceClient::new(channel.clone())
+            .schedule(SchedulingRequest::default())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        // The enrollment reaches the service, which refuses it as no issuer is configured
+        let status = LifecycleServiceClient::new(channel)
+            .enroll(EnrollmentRequest {
+                id: "node-1".to_string(),
+                ..Default::default()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+
+        // Authenticated clients reach the services
+        let channel = connect(address, &ca, true).await;
+
+        let status = SchedulingServiceClient::new(channel)
+            .stop(WorkloadInstance {
+                instance_id: "unknown".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+    }
+}
@@ -0,0 +1,269 @@
+//! Identification of gRPC peers from their TLS client certificate.
+
+use tonic::Request;
+use x509_parser::{certificate::X509Certificate, parse_x509_certificate};
+
+use super::errors::IdentityError;
+
+/// Organizational unit of the client certificates issued to the operators of the cluster. The
+/// certificates of the node agents are issued to another unit, whatever they request.
+pub const ADMIN_ORGANIZATIONAL_UNIT: &str = "Admin";
+
+/// Parse the client certificate presented by the peer of a request, and read it.
+///
+/// # Arguments
+///
+/// * `request` - The gRPC request received from the peer.
+/// * `read` - The function reading the parsed certificate.
+///
+/// # Errors
+///
+/// * The peer did not present a client certificate.
+/// * The client certificate could not be parsed, or `read` failed.
+fn read_peer_certificate<T, R>(
+    request: &Request<T>,
+    read: impl FnOnce(&X509Certificate) -> Result<R, IdentityError>,
+) -> Result<R, IdentityError> {
+    let certs = request
+        .peer_certs()
+        .ok_or(IdentityError::MissingCertificate)?;
+
+    // The first certificate of the chain is the one of the peer
+    let cert = certs.first().ok_or(IdentityError::MissingCertificate)?;
+
+    let (_, cert) = parse_x509_certificate(cert.get_ref())
+        .map_err(|err| IdentityError::InvalidCertificate(err.to_string()))?;
+
+    read(&cert)
+}
+
+/// Get the common name of a certificate.
+///
+/// # Arguments
+///
+/// * `cert` - The certificate.
+///
+/// # Errors
+///
+/// * The certificate has no common name, or it is not valid.
+fn common_name(cert: &X509Certificate) -> Result<String, IdentityError> {
+    let common_name = cert
+        .subject()
+        .iter_common_name()
+        .next()
+        .ok_or(IdentityError::MissingCommonName)?
+        .as_str()
+        .map_err(|err| IdentityError::InvalidCertificate(err.to_string()))?;
+
+    Ok(common_name.to_string())
+}
+
+/// Get the common name of the client certificate presented by the peer of a request.
+///
+/// # Arguments
+///
+/// * `request` - The gRPC request received from the peer.
+///
+/// # Errors
+///
+/// * The peer did not present a client certificate.
+/// * The client certificate could not be parsed or has no common name.
+pub fn peer_common_name<T>(request: &Request<T>) -> Result<String, IdentityError> {
+    read_peer_certificate(request, common_name)
+}
+
+/// Check that the client certificate presented by the peer of a request was issued to an
+/// operator of the cluster, returning the name of the operator.
+///
+/// # Arguments
+///
+/// * `request` - The gRPC request received from the peer.
+///
+/// # Errors
+///
+/// * The client certificate could not be read.
+/// * The client certificate was not issued to the admin organizational unit.
+pub fn verify_admin_identity<T>(request: &Request<T>) -> Result<String, IdentityError> {
+    read_peer_certificate(request, |cert| {
+        let name = common_name(cert)?;
+
+        let is_admin = cert
+            .subject()
+            .iter_organizational_unit()
+            .any(|unit| unit.as_str() == Ok(ADMIN_ORGANIZATIONAL_UNIT));
+
+        if !is_admin {
+            return Err(IdentityError::NotAdmin(name));
+        }
+
+        Ok(name)
+    })
+}
+
+/// Check that the client certificate presented by the peer of a request was issued to the
+/// identity it claims.
+///
+/// # Arguments
+///
+/// * `request` - The gRPC request received from the peer.
+/// * `expected_id` - The identity claimed by the peer.
+///
+/// # Errors
+///
+/// * The client certificate could not be read.
+/// * The common name of the client certificate is not `expected_id`.
+pub fn verify_peer_identity<T>(
+    request: &Request<T>,
+    expected_id: &str,
+) -> Result<(), IdentityError> {
+    let common_name = peer_common_name(request)?;
+
+    if common_name != expected_id {
+        return Err(IdentityError::Mismatch {
+            expected: expected_id.to_string(),
+            actual: common_name,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::{
+        rustls::{
+            self, server::AllowAnyAuthenticatedClient, ClientConfig, PrivateKey, RootCertStore,
+            ServerConfig,
+        },
+        TlsAcceptor, TlsConnector,
+    };
+    use tonic::transport::server::Connected;
+
+    use super::*;
+
+    /// Create a certificate signed by a certificate authority.
+    fn signed_certificate(
+        common_name: &str,
+        unit: &str,
+        ca: &Certificate,
+    ) -> (rustls::Certificate, PrivateKey) {
+        let mut params = CertificateParams::new(vec!["localhost".to_string()]);
+        params
+            .distinguished_name
+            .push(DnType::OrganizationalUnitName, unit);
+        params
+            .distinguished_name
+            .push(DnType::CommonName, common_name);
+
+        let cert = Certificate::from_params(params).unwrap();
+
+        (
+            rustls::Certificate(cert.serialize_der_with_signer(ca).unwrap()),
+            PrivateKey(cert.serialize_private_key_der()),
+        )
+    }
+
+    /// Build a request as received by the gRPC server from a client authenticated with a
+    /// certificate issued to `common_name` in `unit`, after a TLS handshake over the loopback
+    /// interface.
+    async fn authenticated_request(common_name: &str, unit: &str) -> Request<()> {
+        let mut ca_params = CertificateParams::new(Vec::new());
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = Certificate::from_params(ca_params).unwrap();
+
+        let mut roots = RootCertStore::empty();
+        roots
+            .add(&rustls::Certificate(ca.serialize_der().unwrap()))
+            .unwrap();
+
+        let (server_cert, server_key) = signed_certificate("scheduler", "Scheduler", &ca);
+        let server_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots.clone()).boxed())
+            .with_single_cert(vec![server_cert], server_key)
+            .unwrap();
+
+        let (client_cert, client_key) = signed_certificate(common_name, unit, &ca);
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_client_auth_cert(vec![client_cert], client_key)
+            .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(address).await.unwrap();
+            TlsConnector::from(Arc::new(client_config))
+                .connect("localhost".try_into().unwrap(), stream)
+                .await
+                .unwrap()
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let stream = TlsAcceptor::from(Arc::new(server_config))
+            .accept(stream)
+            .await
+            .unwrap();
+        let _client = client.await.unwrap();
+
+        let mut request = Request::new(());
+        request.extensions_mut().insert(stream.connect_info());
+        request
+    }
+
+    #[tokio::test]
+    async fn accepts_a_matching_common_name() {
+        let request = authenticated_request("node-1", "Agent").await;
+
+        assert_eq!(peer_common_name(&request).unwrap(), "node-1");
+        assert!(verify_peer_identity(&request, "node-1").is_ok());
+    }
+
+    #[tokio::test]
+    async fn refuses_a_mismatched_common_name() {
+        let request = authenticated_request("node-1", "Agent").await;
+
+        assert!(matches!(
+            verify_peer_identity(&request, "node-2"),
+            Err(IdentityError::Mismatch { expected, actual })
+                if expected == "node-2" && actual == "node-1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn accepts_admin_certificates_only() {
+        let request = authenticated_request("alice", ADMIN_ORGANIZATIONAL_UNIT).await;
+        assert_eq!(verify_admin_identity(&request).unwrap(), "alice");
+
+        let request = authenticated_request("node-1", "Agent").await;
+        assert!(matches!(
+            verify_admin_identity(&request),
+            Err(IdentityError::NotAdmin(name)) if name == "node-1"
+        ));
+
+        assert!(matches!(
+            verify_admin_identity(&Request::new(())),
+            Err(IdentityError::MissingCertificate)
+        ));
+    }
+
+    #[test]
+    fn refuses_a_missing_peer_certificate() {
+        let request = Request::new(());
+
+        assert!(matches!(
+            peer_common_name(&request),
+            Err(IdentityError::MissingCertificate)
+        ));
+        assert!(matches!(
+            verify_peer_identity(&request, "node-1"),
+            Err(IdentityError::MissingCertificate)
+        ));
+    }
+}
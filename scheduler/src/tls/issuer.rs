@@ -0,0 +1,213 @@
+//! Issuance of node agent certificates by the scheduler certificate authority.
+
+use anyhow::{Context, Result};
+use rcgen::{
+    Certificate, CertificateParams, CertificateSigningRequest, DistinguishedName, DnType,
+    ExtendedKeyUsagePurpose, IsCa,
+};
+use time::{Duration, OffsetDateTime};
+
+use crate::enrollment::errors::EnrollmentError;
+
+use super::{identity::ADMIN_ORGANIZATIONAL_UNIT, manager::TlsManager};
+
+/// Number of days node agent certificates are valid for.
+const AGENT_CERT_VALIDITY_DAYS: i64 = 365;
+
+/// Number of days the certificates of the operators are valid for.
+const ADMIN_CERT_VALIDITY_DAYS: i64 = 90;
+
+/// Issuer of the client certificates presented by node agents.
+pub struct CertificateIssuer {
+    /// The certificate authority signing the certificates.
+    ca: Certificate,
+
+    /// X509 certificate data of the certificate authority.
+    ca_cert_data: String,
+}
+
+impl CertificateIssuer {
+    /// Create a certificate issuer from the certificate authority of a TLS manager.
+    ///
+    /// # Arguments
+    ///
+    /// * `tls_manager` - The TLS manager holding the certificate authority.
+    ///
+    /// # Errors
+    ///
+    /// * The certificate authority could not be loaded.
+    pub fn new(tls_manager: &TlsManager) -> Result<Self> {
+        let ca_cert_data = tls_manager
+            .ca_cert_data()
+            .with_context(|| "The certificate authority data is missing")?
+            .clone();
+
+        Ok(Self {
+            ca: tls_manager.certificate_authority()?,
+            ca_cert_data,
+        })
+    }
+
+    /// Sign the certificate signing request of a node agent. Only the public key of the request
+    /// is kept: whatever subject and extensions it asks for, the certificate is issued to the
+    /// agent ID so that it can be used to join the cluster, and nothing else.
+    ///
+    /// # Arguments
+    ///
+    /// * `agent_id` - The ID of the node agent.
+    /// * `csr_data` - The certificate signing request, in PEM format.
+    ///
+    /// # Errors
+    ///
+    /// * The certificate signing request could not be parsed or signed.
+    pub fn issue_agent_certificate(
+        &self,
+        agent_id: &str,
+        csr_data: &str,
+    ) -> Result<String, EnrollmentError> {
+        let mut csr = CertificateSigningRequest::from_pem(csr_data)
+            .map_err(|err| EnrollmentError::InvalidCsr(err.to_string()))?;
+
+        let mut dn = DistinguishedName::new();
+        dn.push(DnType::OrganizationName, "Orka");
+        dn.push(DnType::OrganizationalUnitName, "Agent");
+        dn.push(DnType::CommonName, agent_id);
+
+        // Start from blank parameters, dropping the subject alternative names and any other
+        // extension requested by the agent
+        let mut params = CertificateParams::default();
+        params.alg = csr.params.alg;
+        params.distinguished_name = dn;
+        params.is_ca = IsCa::NoCa;
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        params.not_before = OffsetDateTime::now_utc() - Duration::days(1);
+        params.not_after = OffsetDateTime::now_utc() + Duration::days(AGENT_CERT_VALIDITY_DAYS);
+        csr.params = params;
+
+        csr.serialize_pem_with_signer(&self.ca)
+            .map_err(|err| EnrollmentError::InvalidCsr(err.to_string()))
+    }
+
+    /// Generate a keypair and a client certificate granting access to the admin service, for an
+    /// operator of the cluster. Node agents cannot obtain such a certificate, their certificates
+    /// being issued to another organizational unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the operator.
+    ///
+    /// # Errors
+    ///
+    /// * The certificate could not be generated or signed.
+    pub fn issue_admin_certificate(&self, name: &str) -> Result<(String, String)> {
+        let mut dn = DistinguishedName::new();
+        dn.push(DnType::OrganizationName, "Orka");
+        dn.push(DnType::OrganizationalUnitName, ADMIN_ORGANIZATIONAL_UNIT);
+        dn.push(DnType::CommonName, name);
+
+        let mut params = CertificateParams::default();
+        params.alg = self.ca.get_params().alg;
+        params.distinguished_name = dn;
+        params.is_ca = IsCa::NoCa;
+        params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        params.not_before = OffsetDateTime::now_utc() - Duration::days(1);
+        params.not_after = OffsetDateTime::now_utc() + Duration::days(ADMIN_CERT_VALIDITY_DAYS);
+
+        let cert = Certificate::from_params(params)
+            .with_context(|| "Unable to generate the admin certificate")?;
+
+        let cert_data = cert
+            .serialize_pem_with_signer(&self.ca)
+            .with_context(|| "Unable to sign the admin certificate")?;
+
+        Ok((cert_data, cert.serialize_private_key_pem()))
+    }
+
+    /// Get the raw data of the X509 certificate of the certificate authority.
+    pub fn ca_cert_data(&self) -> &str {
+        &self.ca_cert_data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use x509_parser::pem::parse_x509_pem;
+
+    use super::*;
+    use crate::tls::config::{CertificateOptions, KeyAlgorithm, SanMismatchPolicy, TlsConfig};
+
+    /// Create a certificate issuer with a new certificate authority.
+    fn issuer(name: &str) -> CertificateIssuer {
+        let dir = std::env::temp_dir().join(format!(
+            "orka-scheduler-issuer-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+
+        let options = CertificateOptions::new(
+            &[],
+            &[],
+            KeyAlgorithm::EcdsaP256,
+            Duration::from_secs(86400),
+            SanMismatchPolicy::Warn,
+        );
+        let config = TlsConfig::new(&dir, true, options);
+        config.prepare_directory().unwrap();
+
+        let mut tls_manager = TlsManager::new(config);
+        tls_manager.populate_secrets().unwrap();
+
+        let issuer = CertificateIssuer::new(&tls_manager).unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        issuer
+    }
+
+    #[test]
+    fn drops_the_extensions_requested_by_the_agent() {
+        let issuer = issuer("extensions");
+
+        let mut params = CertificateParams::new(vec![
+            "scheduler.example.com".to_string(),
+            "localhost".to_string(),
+        ]);
+        params.distinguished_name.push(DnType::CommonName, "admin");
+        let csr_data = Certificate::from_params(params)
+            .unwrap()
+            .serialize_request_pem()
+            .unwrap();
+
+        let cert_data = issuer.issue_agent_certificate("node-1", &csr_data).unwrap();
+
+        let (_, pem) = parse_x509_pem(cert_data.as_bytes()).unwrap();
+        let cert = pem.parse_x509().unwrap();
+
+        let common_name = cert.subject().iter_common_name().next().unwrap();
+        assert_eq!(common_name.as_str().unwrap(), "node-1");
+        assert!(cert.subject_alternative_name().unwrap().is_none());
+        assert!(!cert.is_ca());
+    }
+
+    #[test]
+    fn issues_admin_certificates_to_the_admin_unit() {
+        let issuer = issuer("admin");
+
+        let (cert_data, key_data) = issuer.issue_admin_certificate("alice").unwrap();
+
+        let (_, pem) = parse_x509_pem(cert_data.as_bytes()).unwrap();
+        let cert = pem.parse_x509().unwrap();
+
+        let unit = cert.subject().iter_organizational_unit().next().unwrap();
+        assert_eq!(unit.as_str().unwrap(), ADMIN_ORGANIZATIONAL_UNIT);
+        let common_name = cert.subject().iter_common_name().next().unwrap();
+        assert_eq!(common_name.as_str().unwrap(), "alice");
+
+        let key_pair = rcgen::KeyPair::from_pem(&key_data).unwrap();
+        assert_eq!(
+            cert.public_key().subject_public_key.data.as_ref(),
+            key_pair.public_key_raw()
+        );
+    }
+}
@@ -1,13 +1,23 @@
 //! TLS management functions.
 
-use std::{fs::File, io::Write};
-
-use anyhow::{Context, Result};
-use rcgen::{Certificate, CertificateParams, DistinguishedName, DnType};
+use std::{
+    fs::{self, File},
+    io::{ErrorKind, Write},
+    net::IpAddr,
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use rcgen::{
+    BasicConstraints, Certificate, CertificateParams, DistinguishedName, DnType,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, KeyUsagePurpose, SanType,
+};
 use time::{Duration, OffsetDateTime};
 use tracing::{event, Level};
+use x509_parser::{extensions::GeneralName, pem::parse_x509_pem};
 
-use super::config::TlsConfig;
+use super::config::{CertificateOptions, KeyAlgorithm, SanMismatchPolicy, TlsConfig};
 
 /// Manager for TLS secrets.
 pub struct TlsManager {
@@ -19,6 +29,12 @@ pub struct TlsManager {
 
     /// Private key data.
     key_data: Option<String>,
+
+    /// X509 certificate data of the certificate authority.
+    ca_cert_data: Option<String>,
+
+    /// Private key data of the certificate authority.
+    ca_key_data: Option<String>,
 }
 
 impl TlsManager {
@@ -32,22 +48,43 @@ impl TlsManager {
             config,
             cert_data: None,
             key_data: None,
+            ca_cert_data: None,
+            ca_key_data: None,
         }
     }
 
-    /// Populate the certificate and private key data by reading them from the files specified in
-    /// the config file, or generating them if these files do not exist and the configuration
-    /// allows it.
+    /// Populate the certificate authority, certificate and private key data by reading them from
+    /// the files specified in the config file, or generating them if these files do not exist and
+    /// the configuration allows it. Generated certificates are signed by the certificate
+    /// authority.
     ///
     /// # Errors
     ///
     /// * The TLS secrets could not be read from the disk.
+    /// * The TLS certificate lacks some subject alternative names, must be regenerated and TLS
+    ///   secret generation is disabled.
     /// * The TLS secrets could not be generated.
     /// * The TLS secrets could not be written to the disk.
     pub fn populate_secrets(&mut self) -> Result<()> {
+        self.populate_ca_secrets()
+            .with_context(|| "Unable to provide the certificate authority for TLS")?;
+
+        let tls_paths = self.config.paths();
+
         // First, try to read the certificate and private key from the disk
-        match self.read_secrets() {
-            Ok(_) => return Ok(()),
+        match read_pem_pair(tls_paths.cert_file(), tls_paths.private_key_file()) {
+            Ok((cert_data, key_data)) => {
+                self.warn_if_not_issued_by_ca(&cert_data);
+
+                if !self.should_regenerate(&cert_data)? {
+                    self.cert_data = Some(cert_data);
+                    self.key_data = Some(key_data);
+                    return Ok(());
+                }
+
+                backup_pem_pair(tls_paths.cert_file(), tls_paths.private_key_file())
+                    .with_context(|| "Unable to back up the previous TLS secrets")?;
+            }
             Err(e) => {
                 event!(Level::DEBUG, "Failed to read TLS secrets from disk");
 
@@ -64,188 +101,904 @@ impl TlsManager {
             "Generating certificate and private key for TLS"
         );
 
-        self.generate_secrets()
+        let (cert_data, key_data) = self
+            .generate_secrets()
             .with_context(|| "Unable to generate the TLS secrets")?;
 
-        self.write_secrets()
-            .with_context(|| "Unable to write the TLS secrets to the disk")?;
+        write_pem_pair(
+            tls_paths.cert_file(),
+            &cert_data,
+            tls_paths.private_key_file(),
+            &key_data,
+        )
+        .with_context(|| "Unable to write the TLS secrets to the disk")?;
+
+        // Everything went correctly, commit the data that was generated
+        self.cert_data = Some(cert_data);
+        self.key_data = Some(key_data);
 
         Ok(())
     }
 
-    /// Read the certificate and private key data from the disk. The file paths are specified in
-    /// the configuration.
+    /// Populate the certificate authority data by reading it from the disk, or generating it if
+    /// the files do not exist and the configuration allows it. The certificate and private key
+    /// of the scheduler are left untouched.
     ///
     /// # Errors
     ///
-    /// * The certificate or private key file could not be read.
-    fn read_secrets(&mut self) -> Result<()> {
+    /// * The certificate authority could not be read from the disk.
+    /// * The certificate authority could not be generated.
+    /// * The certificate authority could not be written to the disk.
+    pub fn populate_ca_secrets(&mut self) -> Result<()> {
         let tls_paths = self.config.paths();
-        let cert_file_path = tls_paths.cert_file();
-        let private_key_file_path = tls_paths.private_key_file();
 
-        event!(
-            Level::DEBUG,
-            path = %cert_file_path.display(),
-            "Reading certificate file for TLS"
-        );
-        let cert_data = std::fs::read_to_string(cert_file_path).with_context(|| {
-            format!(
-                "Unable to read certificate file for TLS: {}",
-                cert_file_path.display()
-            )
-        })?;
+        match read_pem_pair(tls_paths.ca_cert_file(), tls_paths.ca_private_key_file()) {
+            Ok((cert_data, key_data)) => {
+                self.ca_cert_data = Some(cert_data);
+                self.ca_key_data = Some(key_data);
+                return Ok(());
+            }
+            Err(e) => {
+                event!(
+                    Level::DEBUG,
+                    "Failed to read the certificate authority from disk"
+                );
+
+                if !self.config.can_generate_secrets() {
+                    return Err(e)
+                        .with_context(|| "Unable to read the certificate authority from the disk");
+                }
+            }
+        }
+
+        event!(Level::INFO, "Generating certificate authority for TLS");
+
+        let key_algorithm = self.config.certificate_options().key_algorithm();
+        let (cert_data, key_data) = generate_ca_secrets(key_algorithm)
+            .with_context(|| "Unable to generate the certificate authority")?;
+
+        write_pem_pair(
+            tls_paths.ca_cert_file(),
+            &cert_data,
+            tls_paths.ca_private_key_file(),
+            &key_data,
+        )
+        .with_context(|| "Unable to write the certificate authority to the disk")?;
+
+        self.ca_cert_data = Some(cert_data);
+        self.ca_key_data = Some(key_data);
+
+        Ok(())
+    }
+
+    /// Log a warning if a certificate was not issued by the certificate authority, as the node
+    /// agents trusting the authority would then be unable to verify it.
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_data` - The certificate to check, in PEM format.
+    fn warn_if_not_issued_by_ca(&self, cert_data: &str) {
+        let Some(ca_cert_data) = &self.ca_cert_data else {
+            return;
+        };
+
+        match is_issued_by(cert_data, ca_cert_data) {
+            Ok(true) => (),
+            Ok(false) => event!(
+                Level::WARN,
+                path = %self.config.paths().cert_file().display(),
+                "The TLS certificate was not issued by the scheduler certificate authority, node agents will not be able to verify it"
+            ),
+            Err(err) => event!(
+                Level::WARN,
+                error = %err,
+                "Unable to check the issuer of the TLS certificate"
+            ),
+        }
+    }
+
+    /// Check whether an existing certificate should be replaced because it lacks some of the
+    /// requested subject alternative names. Depending on the configured policy, a warning is
+    /// logged instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_data` - The certificate to check, in PEM format.
+    ///
+    /// # Errors
+    ///
+    /// * The certificate must be regenerated but TLS secret generation is disabled.
+    fn should_regenerate(&self, cert_data: &str) -> Result<bool> {
+        let options = self.config.certificate_options();
+        let path = self.config.paths().cert_file().display();
+
+        let missing = match missing_subject_alt_names(cert_data, options) {
+            Ok(missing) if missing.is_empty() => return Ok(false),
+            Ok(missing) => missing.join(", "),
+            Err(err) => {
+                event!(
+                    Level::WARN,
+                    error = %err,
+                    "Unable to check the subject alternative names of the TLS certificate"
+                );
+                return Ok(false);
+            }
+        };
+
+        if options.san_mismatch_policy() == SanMismatchPolicy::Regenerate {
+            if !self.config.can_generate_secrets() {
+                bail!(
+                    "The TLS certificate {} lacks the subject alternative names {} and cannot be regenerated because TLS secret generation is disabled",
+                    path,
+                    missing
+                );
+            }
+
+            event!(
+                Level::INFO,
+                %path,
+                missing,
+                "The TLS certificate lacks some subject alternative names, regenerating it"
+            );
+            return Ok(true);
+        }
 
         event!(
-            Level::DEBUG,
-            path = %private_key_file_path.display(),
-            "Reading key file for TLS"
+            Level::WARN,
+            %path,
+            missing,
+            "The TLS certificate lacks some subject alternative names, node agents using them will not be able to verify it"
         );
-        let key_data = std::fs::read_to_string(private_key_file_path).with_context(|| {
-            format!(
-                "Unable to read private key file for TLS: {}",
-                private_key_file_path.display()
-            )
-        })?;
+        Ok(false)
+    }
 
-        // Everything went correctly, commit the data that was read
+    /// Read the certificate and private key from the disk again, for example after they were
+    /// replaced by an operator. The data held by the manager is left untouched.
+    ///
+    /// # Errors
+    ///
+    /// * The certificate or private key file could not be read.
+    /// * The private key does not match the certificate, for example because only one of the
+    ///   files was replaced yet.
+    pub fn read_secrets(&self) -> Result<(String, String)> {
+        let tls_paths = self.config.paths();
+
+        let (cert_data, key_data) =
+            read_pem_pair(tls_paths.cert_file(), tls_paths.private_key_file())?;
+
+        if !key_matches_certificate(&cert_data, &key_data)? {
+            bail!("The private key does not match the certificate");
+        }
+
+        Ok((cert_data, key_data))
+    }
+
+    /// Replace the certificate and private key data held by the manager.
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_data` - The new certificate data.
+    /// * `key_data` - The new private key data.
+    pub fn set_secrets(&mut self, cert_data: String, key_data: String) {
         self.cert_data = Some(cert_data);
         self.key_data = Some(key_data);
+    }
+
+    /// Check whether the certificate can be renewed automatically, which is only the case for
+    /// certificates issued by the certificate authority when secret generation is allowed.
+    pub fn can_renew(&self) -> bool {
+        if !self.config.can_generate_secrets() {
+            return false;
+        }
+
+        match (&self.cert_data, &self.ca_cert_data) {
+            (Some(cert_data), Some(ca_cert_data)) => {
+                is_issued_by(cert_data, ca_cert_data).unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+
+    /// Check whether the certificate expires within a given period.
+    ///
+    /// # Arguments
+    ///
+    /// * `period` - The period to check.
+    ///
+    /// # Errors
+    ///
+    /// * The certificate data does not exist or could not be parsed.
+    pub fn expires_within(&self, period: std::time::Duration) -> Result<bool> {
+        let cert_data = self
+            .cert_data
+            .as_ref()
+            .with_context(|| "The certificate data is missing")?;
+
+        let (_, cert_pem) =
+            parse_x509_pem(cert_data.as_bytes()).with_context(|| "Invalid certificate PEM data")?;
+        let cert = cert_pem
+            .parse_x509()
+            .with_context(|| "Unable to parse the certificate")?;
+
+        let not_after = cert.validity().not_after.to_datetime();
+        let period = Duration::try_from(period).unwrap_or(Duration::MAX);
+
+        Ok(OffsetDateTime::now_utc().saturating_add(period) >= not_after)
+    }
+
+    /// Generate a new certificate and private key signed by the certificate authority, and
+    /// replace the previous ones on the disk.
+    ///
+    /// # Errors
+    ///
+    /// * The TLS secrets could not be generated.
+    /// * The TLS secrets could not be written to the disk.
+    pub fn renew_secrets(&mut self) -> Result<()> {
+        let tls_paths = self.config.paths();
+
+        let (cert_data, key_data) = self
+            .generate_secrets()
+            .with_context(|| "Unable to generate the TLS secrets")?;
+
+        // Write the key first, readers of the files check that both of them match. Only the
+        // owner can read the private key.
+        replace_file(tls_paths.private_key_file(), &key_data, 0o600)
+            .and_then(|_| replace_file(tls_paths.cert_file(), &cert_data, 0o644))
+            .with_context(|| "Unable to write the TLS secrets to the disk")?;
+
+        self.set_secrets(cert_data, key_data);
 
         Ok(())
     }
 
-    /// Generate a new private key and a new certificate.
+    /// Load the certificate authority so that it can sign certificates.
     ///
     /// # Errors
     ///
-    /// * The self-signed certificate could not be generated.
-    /// * The self-signed certificate could not be serialized.
-    fn generate_secrets(&mut self) -> Result<()> {
-        let subject_alt_names = ["localhost".to_string()];
-        let mut cert_params = CertificateParams::new(subject_alt_names);
+    /// * The certificate authority data does not exist.
+    /// * The certificate authority data could not be parsed.
+    pub fn certificate_authority(&self) -> Result<Certificate> {
+        let ca_cert_data = self
+            .ca_cert_data
+            .as_ref()
+            .with_context(|| "The certificate authority data is missing")?;
+
+        let ca_key_data = self
+            .ca_key_data
+            .as_ref()
+            .with_context(|| "The certificate authority private key data is missing")?;
+
+        let key_pair = KeyPair::from_pem(ca_key_data)
+            .with_context(|| "Unable to parse the certificate authority private key")?;
+
+        let ca_params = CertificateParams::from_ca_cert_pem(ca_cert_data, key_pair)
+            .with_context(|| "Unable to parse the certificate authority certificate")?;
+
+        Certificate::from_params(ca_params)
+            .with_context(|| "Unable to load the certificate authority")
+    }
+
+    /// Generate a new private key and a new certificate signed by the certificate authority.
+    ///
+    /// # Errors
+    ///
+    /// * The certificate authority could not be loaded.
+    /// * The certificate could not be generated.
+    /// * The certificate could not be serialized.
+    fn generate_secrets(&self) -> Result<(String, String)> {
+        let options = self.config.certificate_options();
+
+        let mut cert_params = CertificateParams::new(options.dns_names().to_vec());
+        cert_params.subject_alt_names.extend(
+            options
+                .ip_addresses()
+                .iter()
+                .map(|address| SanType::IpAddress(*address)),
+        );
+        cert_params.alg = options.key_algorithm().signature_algorithm();
 
         let mut dn = DistinguishedName::new();
         dn.push(DnType::OrganizationName, "Orka");
         dn.push(DnType::OrganizationalUnitName, "Scheduler");
         dn.push(DnType::CommonName, "Orka Scheduler TLS certificate");
         cert_params.distinguished_name = dn;
+        cert_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+
+        let validity = Duration::try_from(options.validity())
+            .with_context(|| "The certificate validity period is too long")?;
 
         cert_params.not_before = OffsetDateTime::now_utc() - Duration::days(1);
-        cert_params.not_after = OffsetDateTime::now_utc() + Duration::days(365 * 10);
+        cert_params.not_after = OffsetDateTime::now_utc()
+            .checked_add(validity)
+            .with_context(|| "The certificate validity period is too long")?;
+
+        let ca = self.certificate_authority()?;
 
         let cert = Certificate::from_params(cert_params)
-            .with_context(|| "Unable to generate the self-signed certificate")?;
+            .with_context(|| "Unable to generate the certificate")?;
 
         let cert_data = cert
-            .serialize_pem()
-            .with_context(|| "Unable to serialize the self-signed certificate")?;
+            .serialize_pem_with_signer(&ca)
+            .with_context(|| "Unable to serialize the certificate")?;
 
         let key_data = cert.serialize_private_key_pem();
 
-        // Everything went correctly, commit the data that was read
-        self.cert_data = Some(cert_data);
-        self.key_data = Some(key_data);
+        Ok((cert_data, key_data))
+    }
 
-        Ok(())
+    /// Get the raw data of the X509 certificate.
+    pub fn cert_data(&self) -> Option<&String> {
+        self.cert_data.as_ref()
     }
 
-    /// Write the certificate and private key data to the disk. The file paths are specified in the
-    /// configuration.
-    ///
-    /// # Errors
-    ///
-    /// * The certificate or private key data does not exist.
-    /// * The certificate or private key file could not be opened (for example, not enough
-    ///   permissions or the file already exists).
-    /// * The certificate or private key file could not be written to.
-    /// * The certificate or private key file could not be synced to the disk.
-    fn write_secrets(&mut self) -> Result<()> {
-        // Make sure the secrets are here
-        let cert_data = self
-            .cert_data
-            .as_ref()
-            .with_context(|| "No certificate data was provided to write to the disk")?;
+    /// Get the raw data of the private key.
+    pub fn key_data(&self) -> Option<&String> {
+        self.key_data.as_ref()
+    }
 
-        let key_data = self
-            .key_data
-            .as_ref()
-            .with_context(|| "No private key data was provided to write to the disk")?;
+    /// Get the raw data of the X509 certificate of the certificate authority.
+    pub fn ca_cert_data(&self) -> Option<&String> {
+        self.ca_cert_data.as_ref()
+    }
+}
 
-        // Intentionally prevent overwriting files, because we don't want users to loose data that
-        // they might not have saved
-        let mut file_opts = File::options();
-        file_opts.read(true).write(true).create_new(true);
+/// Generate a new private key and a new self-signed certificate for the certificate authority.
+///
+/// # Arguments
+///
+/// * `key_algorithm` - The algorithm of the generated private key.
+///
+/// # Errors
+///
+/// * The self-signed certificate could not be generated.
+/// * The self-signed certificate could not be serialized.
+fn generate_ca_secrets(key_algorithm: KeyAlgorithm) -> Result<(String, String)> {
+    let mut ca_params = CertificateParams::new(Vec::new());
+    ca_params.alg = key_algorithm.signature_algorithm();
+
+    let mut dn = DistinguishedName::new();
+    dn.push(DnType::OrganizationName, "Orka");
+    dn.push(DnType::OrganizationalUnitName, "Scheduler");
+    dn.push(DnType::CommonName, "Orka Scheduler certificate authority");
+    ca_params.distinguished_name = dn;
+
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    ca_params.key_usages = vec![
+        KeyUsagePurpose::KeyCertSign,
+        KeyUsagePurpose::CrlSign,
+        KeyUsagePurpose::DigitalSignature,
+    ];
+
+    ca_params.not_before = OffsetDateTime::now_utc() - Duration::days(1);
+    ca_params.not_after = OffsetDateTime::now_utc() + Duration::days(365 * 10);
+
+    let ca = Certificate::from_params(ca_params)
+        .with_context(|| "Unable to generate the self-signed certificate authority")?;
+
+    let cert_data = ca
+        .serialize_pem()
+        .with_context(|| "Unable to serialize the self-signed certificate authority")?;
+
+    let key_data = ca.serialize_private_key_pem();
+
+    Ok((cert_data, key_data))
+}
 
-        let tls_paths = self.config.paths();
-        let cert_file_path = tls_paths.cert_file();
-        let private_key_file_path = tls_paths.private_key_file();
+/// Check whether a certificate was signed by a certificate authority.
+///
+/// # Arguments
+///
+/// * `cert_data` - The certificate to check, in PEM format.
+/// * `ca_cert_data` - The certificate of the certificate authority, in PEM format.
+///
+/// # Errors
+///
+/// * One of the certificates could not be parsed.
+fn is_issued_by(cert_data: &str, ca_cert_data: &str) -> Result<bool> {
+    let (_, cert_pem) =
+        parse_x509_pem(cert_data.as_bytes()).with_context(|| "Invalid certificate PEM data")?;
+    let cert = cert_pem
+        .parse_x509()
+        .with_context(|| "Unable to parse the certificate")?;
+
+    let (_, ca_pem) = parse_x509_pem(ca_cert_data.as_bytes())
+        .with_context(|| "Invalid certificate authority PEM data")?;
+    let ca = ca_pem
+        .parse_x509()
+        .with_context(|| "Unable to parse the certificate authority")?;
+
+    Ok(cert.verify_signature(Some(ca.public_key())).is_ok())
+}
 
-        event!(
-            Level::DEBUG,
-            path = %cert_file_path.display(),
-            "Writing certificate file for TLS"
-        );
-        let mut cert_file = file_opts.open(cert_file_path).with_context(|| {
-            format!(
-                "Unable to open certificate file for TLS: {}",
-                cert_file_path.display()
-            )
-        })?;
+/// List the requested subject alternative names that a certificate does not contain.
+///
+/// # Arguments
+///
+/// * `cert_data` - The certificate to check, in PEM format.
+/// * `options` - The parameters holding the requested subject alternative names.
+///
+/// # Errors
+///
+/// * The certificate could not be parsed.
+fn missing_subject_alt_names(cert_data: &str, options: &CertificateOptions) -> Result<Vec<String>> {
+    let (_, cert_pem) =
+        parse_x509_pem(cert_data.as_bytes()).with_context(|| "Invalid certificate PEM data")?;
+    let cert = cert_pem
+        .parse_x509()
+        .with_context(|| "Unable to parse the certificate")?;
+
+    let mut dns_names = Vec::new();
+    let mut ip_addresses = Vec::new();
+
+    let extension = cert
+        .subject_alternative_name()
+        .with_context(|| "Invalid subject alternative name extension")?;
+
+    for name in extension.iter().flat_map(|ext| &ext.value.general_names) {
+        match name {
+            GeneralName::DNSName(name) => dns_names.push(name.to_string()),
+            GeneralName::IPAddress(bytes) => {
+                let address = match bytes.len() {
+                    4 => <[u8; 4]>::try_from(*bytes).ok().map(IpAddr::from),
+                    16 => <[u8; 16]>::try_from(*bytes).ok().map(IpAddr::from),
+                    _ => None,
+                };
+                ip_addresses.extend(address);
+            }
+            _ => (),
+        }
+    }
 
-        cert_file.write_all(cert_data.as_bytes()).with_context(|| {
-            format!(
-                "Unable to write certificate file for TLS: {}",
-                cert_file_path.display()
-            )
-        })?;
+    let missing_dns_names = options
+        .dns_names()
+        .iter()
+        .filter(|name| !dns_names.contains(name))
+        .map(|name| format!("DNS:{}", name));
+
+    let missing_ip_addresses = options
+        .ip_addresses()
+        .iter()
+        .filter(|address| !ip_addresses.contains(address))
+        .map(|address| format!("IP:{}", address));
+
+    Ok(missing_dns_names.chain(missing_ip_addresses).collect())
+}
+
+/// Check whether a private key is the one of a certificate.
+///
+/// # Arguments
+///
+/// * `cert_data` - The certificate, in PEM format.
+/// * `key_data` - The private key, in PEM format.
+///
+/// # Errors
+///
+/// * The certificate or private key could not be parsed.
+fn key_matches_certificate(cert_data: &str, key_data: &str) -> Result<bool> {
+    let (_, cert_pem) =
+        parse_x509_pem(cert_data.as_bytes()).with_context(|| "Invalid certificate PEM data")?;
+    let cert = cert_pem
+        .parse_x509()
+        .with_context(|| "Unable to parse the certificate")?;
+
+    let key_pair =
+        KeyPair::from_pem(key_data).with_context(|| "Unable to parse the private key")?;
+
+    Ok(cert.public_key().subject_public_key.data.as_ref() == key_pair.public_key_raw())
+}
+
+/// Atomically replace the content of a file, by writing a temporary file next to it and renaming
+/// it over the previous one.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file.
+/// * `data` - The new content of the file.
+/// * `mode` - The permissions of the new file, set before any data is written to it.
+///
+/// # Errors
+///
+/// * The temporary file could not be written or synced to the disk.
+/// * The temporary file could not be renamed.
+fn replace_file(path: &Path, data: &str, mode: u32) -> Result<()> {
+    let mut tmp_path = PathBuf::from(path);
+    tmp_path.as_mut_os_string().push(".tmp");
+
+    event!(
+        Level::DEBUG,
+        path = %path.display(),
+        "Replacing file for TLS"
+    );
+
+    // A leftover temporary file would keep its permissions, so always start from a new one
+    match fs::remove_file(&tmp_path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+    .with_context(|| format!("Unable to remove file for TLS: {}", tmp_path.display()))?;
+
+    let mut file = File::options()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(&tmp_path)
+        .with_context(|| format!("Unable to open file for TLS: {}", tmp_path.display()))?;
+
+    file.write_all(data.as_bytes())
+        .and_then(|_| file.sync_data())
+        .with_context(|| format!("Unable to write file for TLS: {}", tmp_path.display()))?;
+
+    fs::rename(&tmp_path, path)
+        .with_context(|| format!("Unable to replace file for TLS: {}", path.display()))
+}
+
+/// Move a certificate and its private key out of the way by appending `.bak` to their names,
+/// replacing any previous backup.
+///
+/// # Arguments
+///
+/// * `cert_file_path` - The path to the certificate file.
+/// * `private_key_file_path` - The path to the private key file.
+///
+/// # Errors
+///
+/// * The certificate or private key file could not be renamed.
+fn backup_pem_pair(cert_file_path: &Path, private_key_file_path: &Path) -> Result<()> {
+    for path in [cert_file_path, private_key_file_path] {
+        let mut backup_path = PathBuf::from(path);
+        backup_path.as_mut_os_string().push(".bak");
 
         event!(
-            Level::DEBUG,
-            path = %private_key_file_path.display(),
-            "Writing key file for TLS"
+            Level::INFO,
+            path = %path.display(),
+            backup_path = %backup_path.display(),
+            "Backing up TLS file"
         );
-        let mut private_key_file = file_opts.open(private_key_file_path).with_context(|| {
+        fs::rename(path, &backup_path)
+            .with_context(|| format!("Unable to back up TLS file: {}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Read a certificate and its private key from the disk.
+///
+/// # Arguments
+///
+/// * `cert_file_path` - The path to the certificate file.
+/// * `private_key_file_path` - The path to the private key file.
+///
+/// # Errors
+///
+/// * The certificate or private key file could not be read.
+fn read_pem_pair(cert_file_path: &Path, private_key_file_path: &Path) -> Result<(String, String)> {
+    event!(
+        Level::DEBUG,
+        path = %cert_file_path.display(),
+        "Reading certificate file for TLS"
+    );
+    let cert_data = std::fs::read_to_string(cert_file_path).with_context(|| {
+        format!(
+            "Unable to read certificate file for TLS: {}",
+            cert_file_path.display()
+        )
+    })?;
+
+    event!(
+        Level::DEBUG,
+        path = %private_key_file_path.display(),
+        "Reading key file for TLS"
+    );
+    let key_data = std::fs::read_to_string(private_key_file_path).with_context(|| {
+        format!(
+            "Unable to read private key file for TLS: {}",
+            private_key_file_path.display()
+        )
+    })?;
+
+    Ok((cert_data, key_data))
+}
+
+/// Write a certificate and its private key to the disk, without overwriting existing files. Only
+/// the owner can read the private key.
+///
+/// # Arguments
+///
+/// * `cert_file_path` - The path to the certificate file.
+/// * `cert_data` - The certificate data.
+/// * `private_key_file_path` - The path to the private key file.
+/// * `key_data` - The private key data.
+///
+/// # Errors
+///
+/// * The certificate or private key file could not be opened (for example, not enough
+///   permissions or the file already exists).
+/// * The certificate or private key file could not be written to.
+/// * The certificate or private key file could not be synced to the disk.
+pub fn write_pem_pair(
+    cert_file_path: &Path,
+    cert_data: &str,
+    private_key_file_path: &Path,
+    key_data: &str,
+) -> Result<()> {
+    // Intentionally prevent overwriting files, because we don't want users to loose data that
+    // they might not have saved
+    let mut file_opts = File::options();
+    file_opts.read(true).write(true).create_new(true);
+
+    event!(
+        Level::DEBUG,
+        path = %cert_file_path.display(),
+        "Writing certificate file for TLS"
+    );
+    let mut cert_file = file_opts.open(cert_file_path).with_context(|| {
+        format!(
+            "Unable to open certificate file for TLS: {}",
+            cert_file_path.display()
+        )
+    })?;
+
+    cert_file.write_all(cert_data.as_bytes()).with_context(|| {
+        format!(
+            "Unable to write certificate file for TLS: {}",
+            cert_file_path.display()
+        )
+    })?;
+
+    event!(
+        Level::DEBUG,
+        path = %private_key_file_path.display(),
+        "Writing key file for TLS"
+    );
+    // The private key is a secret, so only the owner can read it
+    let mut private_key_file = file_opts
+        .mode(0o600)
+        .open(private_key_file_path)
+        .with_context(|| {
             format!(
                 "Unable to open private key file for TLS: {}",
                 private_key_file_path.display()
             )
         })?;
 
-        private_key_file
-            .write_all(key_data.as_bytes())
-            .with_context(|| {
-                format!(
-                    "Unable to write private key file for TLS: {}",
-                    private_key_file_path.display()
-                )
-            })?;
-
-        // Make sure the data has reached the disk
-        cert_file.sync_data().with_context(|| {
-            format!(
-                "Unable to sync certificate file to disk for TLS: {}",
-                cert_file_path.display()
-            )
-        })?;
-
-        private_key_file.sync_data().with_context(|| {
+    private_key_file
+        .write_all(key_data.as_bytes())
+        .with_context(|| {
             format!(
-                "Unable to sync private key file to disk for TLS: {}",
+                "Unable to write private key file for TLS: {}",
                 private_key_file_path.display()
             )
         })?;
 
-        Ok(())
+    // Make sure the data has reached the disk
+    cert_file.sync_data().with_context(|| {
+        format!(
+            "Unable to sync certificate file to disk for TLS: {}",
+            cert_file_path.display()
+        )
+    })?;
+
+    private_key_file.sync_data().with_context(|| {
+        format!(
+            "Unable to sync private key file to disk for TLS: {}",
+            private_key_file_path.display()
+        )
+    })?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::*;
+
+    /// Create an empty TLS directory for a test.
+    fn tls_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "orka-scheduler-tls-{}-{}",
+            name,
+            std::process::id()
+        ));
+
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
     }
 
-    /// Get the raw data of the X509 certificate.
-    pub fn cert_data(&self) -> Option<&String> {
-        self.cert_data.as_ref()
+    /// Create a TLS manager storing its secrets in a directory, and populate its secrets.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The TLS directory.
+    /// * `dns_names` - The DNS names requested for the scheduler certificate.
+    /// * `can_generate_secrets` - Whether the secrets can be generated.
+    /// * `policy` - What to do when the existing certificate lacks some names.
+    fn populated_manager(
+        dir: &Path,
+        dns_names: &[&str],
+        can_generate_secrets: bool,
+        policy: SanMismatchPolicy,
+    ) -> Result<TlsManager> {
+        let dns_names: Vec<_> = dns_names.iter().map(|name| name.to_string()).collect();
+        let options = CertificateOptions::new(
+            &dns_names,
+            &[],
+            KeyAlgorithm::EcdsaP256,
+            std::time::Duration::from_secs(30 * 86400),
+            policy,
+        );
+
+        let mut manager = TlsManager::new(TlsConfig::new(dir, can_generate_secrets, options));
+        manager.populate_secrets()?;
+        Ok(manager)
     }
 
-    /// Get the raw data of the private key.
-    pub fn key_data(&self) -> Option<&String> {
-        self.key_data.as_ref()
+    #[test]
+    fn regenerates_a_certificate_missing_a_requested_name() {
+        let dir = tls_dir("san-regenerate");
+
+        let previous = populated_manager(&dir, &[], true, SanMismatchPolicy::Regenerate).unwrap();
+        let previous = previous.cert_data().unwrap().clone();
+
+        let manager = populated_manager(
+            &dir,
+            &["scheduler.example.com"],
+            true,
+            SanMismatchPolicy::Regenerate,
+        )
+        .unwrap();
+        let cert_data = manager.cert_data().unwrap();
+
+        assert_ne!(cert_data, &previous);
+        assert!(
+            missing_subject_alt_names(cert_data, manager.config.certificate_options())
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            fs::read_to_string(dir.join("scheduler.pem.bak")).unwrap(),
+            previous
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn fails_when_a_missing_name_cannot_be_regenerated() {
+        let dir = tls_dir("san-disabled");
+
+        populated_manager(&dir, &[], true, SanMismatchPolicy::Regenerate).unwrap();
+
+        let err = populated_manager(
+            &dir,
+            &["scheduler.example.com"],
+            false,
+            SanMismatchPolicy::Regenerate,
+        )
+        .err()
+        .unwrap();
+
+        assert!(format!("{:#}", err).contains("DNS:scheduler.example.com"));
+        assert!(format!("{:#}", err).contains("generation is disabled"));
+
+        // The warning policy keeps the certificate
+        assert!(populated_manager(
+            &dir,
+            &["scheduler.example.com"],
+            false,
+            SanMismatchPolicy::Warn
+        )
+        .is_ok());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn keeps_a_certificate_with_extra_names() {
+        let dir = tls_dir("san-extra");
+
+        let previous = populated_manager(
+            &dir,
+            &["a.example.com", "b.example.com"],
+            true,
+            SanMismatchPolicy::Regenerate,
+        )
+        .unwrap();
+
+        let manager = populated_manager(
+            &dir,
+            &["a.example.com"],
+            true,
+            SanMismatchPolicy::Regenerate,
+        )
+        .unwrap();
+
+        assert_eq!(manager.cert_data(), previous.cert_data());
+        assert!(!dir.join("scheduler.pem.bak").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn checks_the_expiry_window() {
+        let dir = tls_dir("expiry");
+        let day = std::time::Duration::from_secs(86400);
+
+        // The certificate is valid for 30 days
+        let manager = populated_manager(&dir, &[], true, SanMismatchPolicy::Warn).unwrap();
+
+        assert!(manager.expires_within(31 * day).unwrap());
+        assert!(!manager.expires_within(29 * day).unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn renews_secrets_with_a_private_key_readable_by_the_owner_only() {
+        let dir = tls_dir("renew");
+
+        let mut manager = populated_manager(&dir, &[], true, SanMismatchPolicy::Warn).unwrap();
+        let previous = manager.cert_data().unwrap().clone();
+
+        // A leftover temporary file must not leak its permissions to the new key
+        fs::write(dir.join("scheduler.key.tmp"), "stale").unwrap();
+        fs::set_permissions(
+            dir.join("scheduler.key.tmp"),
+            fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+
+        assert!(manager.can_renew());
+        manager.renew_secrets().unwrap();
+
+        let (cert_data, key_data) = manager.read_secrets().unwrap();
+        assert_ne!(cert_data, previous);
+        assert_eq!(Some(&cert_data), manager.cert_data());
+        assert_eq!(Some(&key_data), manager.key_data());
+
+        let mode = fs::metadata(dir.join("scheduler.key"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_private_key_not_matching_the_certificate() {
+        let dir = tls_dir("mismatch");
+        let other_dir = tls_dir("mismatch-other");
+
+        let manager = populated_manager(&dir, &[], true, SanMismatchPolicy::Warn).unwrap();
+        let other = populated_manager(&other_dir, &[], true, SanMismatchPolicy::Warn).unwrap();
+
+        let cert_data = manager.cert_data().unwrap();
+        assert!(key_matches_certificate(cert_data, manager.key_data().unwrap()).unwrap());
+        assert!(!key_matches_certificate(cert_data, other.key_data().unwrap()).unwrap());
+
+        // Only the key was replaced yet
+        fs::copy(other_dir.join("scheduler.key"), dir.join("scheduler.key")).unwrap();
+        assert!(manager.read_secrets().is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+        fs::remove_dir_all(other_dir).unwrap();
+    }
+
+    #[test]
+    fn writes_private_keys_readable_by_the_owner_only() {
+        let dir = tls_dir("key-mode");
+        let cert_path = dir.join("scheduler.pem");
+        let key_path = dir.join("scheduler.key");
+
+        write_pem_pair(&cert_path, "certificate", &key_path, "key").unwrap();
+
+        let mode = fs::metadata(&key_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(write_pem_pair(&cert_path, "certificate", &key_path, "key").is_err());
+
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -1,4 +1,12 @@
 //! TLS management.
 
+pub mod acceptor;
+pub mod commands;
 pub mod config;
+pub mod errors;
+pub mod guard;
+pub mod identity;
+pub mod issuer;
 pub mod manager;
+pub mod resolver;
+pub mod rotation;
@@ -0,0 +1,112 @@
+//! Resolution of the certificate presented by the gRPC server, which can be swapped at runtime.
+
+use std::{
+    io::BufReader,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::{bail, Context, Result};
+use tokio_rustls::rustls::{
+    server::{ClientHello, ResolvesServerCert},
+    sign::{self, CertifiedKey},
+    Certificate, PrivateKey,
+};
+
+/// Certificate resolver always presenting the current identity of the scheduler. Replacing the
+/// identity only affects the handshakes that happen afterwards, established connections keep
+/// the identity they negotiated.
+pub struct ReloadableCertResolver {
+    /// The identity presented during new handshakes.
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ReloadableCertResolver {
+    /// Create a resolver presenting an identity.
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_data` - The certificate chain, in PEM format.
+    /// * `key_data` - The private key, in PEM format.
+    ///
+    /// # Errors
+    ///
+    /// * The certificate or private key could not be parsed.
+    pub fn new(cert_data: &str, key_data: &str) -> Result<Self> {
+        Ok(Self {
+            current: RwLock::new(Arc::new(certified_key(cert_data, key_data)?)),
+        })
+    }
+
+    /// Replace the identity presented during new handshakes.
+    ///
+    /// # Arguments
+    ///
+    /// * `cert_data` - The certificate chain, in PEM format.
+    /// * `key_data` - The private key, in PEM format.
+    ///
+    /// # Errors
+    ///
+    /// * The certificate or private key could not be parsed, the previous identity is kept.
+    pub fn update(&self, cert_data: &str, key_data: &str) -> Result<()> {
+        let certified_key = Arc::new(certified_key(cert_data, key_data)?);
+
+        // The identity is always replaced as a whole, so a poisoned lock holds a usable value
+        let mut current = self
+            .current
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        *current = certified_key;
+
+        Ok(())
+    }
+}
+
+impl ResolvesServerCert for ReloadableCertResolver {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let current = self
+            .current
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        Some(Arc::clone(&current))
+    }
+}
+
+/// Parse a certificate chain and its private key into an identity usable by the TLS server.
+///
+/// # Arguments
+///
+/// * `cert_data` - The certificate chain, in PEM format.
+/// * `key_data` - The private key, in PEM format.
+///
+/// # Errors
+///
+/// * The certificate chain is empty or could not be parsed.
+/// * The private key is missing, could not be parsed or uses an unsupported algorithm.
+fn certified_key(cert_data: &str, key_data: &str) -> Result<CertifiedKey> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(cert_data.as_bytes()))
+        .with_context(|| "Unable to parse the certificate")?;
+
+    if certs.is_empty() {
+        bail!("No certificate was found");
+    }
+
+    let key = rustls_pemfile::read_all(&mut BufReader::new(key_data.as_bytes()))
+        .with_context(|| "Unable to parse the private key")?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(key),
+            _ => None,
+        })
+        .with_context(|| "No private key was found")?;
+
+    let signing_key = sign::any_supported_type(&PrivateKey(key))
+        .with_context(|| "The private key is not supported")?;
+
+    Ok(CertifiedKey::new(
+        certs.into_iter().map(Certificate).collect(),
+        signing_key,
+    ))
+}
@@ -0,0 +1,261 @@
+//! Rotation of the TLS identity of the scheduler while it is running.
+
+use std::{future, sync::Arc, time::Duration};
+
+use tokio::{
+    signal::unix::{signal, Signal, SignalKind},
+    time::MissedTickBehavior,
+};
+use tracing::{event, Level};
+
+use super::{manager::TlsManager, resolver::ReloadableCertResolver};
+
+/// Options of the rotation of the TLS identity.
+#[derive(Debug, Clone, Copy)]
+pub struct RotationOptions {
+    /// Interval at which the certificate and private key files are checked for changes.
+    pub reload_interval: Duration,
+
+    /// How long before its expiration a generated certificate is renewed, if automatic renewal
+    /// is enabled.
+    pub renew_before: Option<Duration>,
+}
+
+/// Background task keeping the identity presented by the gRPC server up to date.
+///
+/// The certificate and private key files are reloaded when their content changes or when the
+/// process receives `SIGHUP`. Generated certificates can also be renewed automatically when they
+/// are close to their expiration. Only new handshakes use the new identity, established
+/// connections are left untouched.
+pub struct CertificateRotator {
+    /// The TLS manager holding the current secrets.
+    tls_manager: TlsManager,
+
+    /// The resolver providing the identity to the gRPC server.
+    resolver: Arc<ReloadableCertResolver>,
+
+    /// Options of the rotation.
+    options: RotationOptions,
+}
+
+impl CertificateRotator {
+    /// Create a new `CertificateRotator`.
+    ///
+    /// # Arguments
+    ///
+    /// * `tls_manager` - The TLS manager holding the current secrets.
+    /// * `resolver` - The resolver providing the identity to the gRPC server.
+    /// * `options` - Options of the rotation.
+    pub fn new(
+        tls_manager: TlsManager,
+        resolver: Arc<ReloadableCertResolver>,
+        options: RotationOptions,
+    ) -> Self {
+        Self {
+            tls_manager,
+            resolver,
+            options,
+        }
+    }
+
+    /// Watch the TLS secrets until the process exits.
+    pub async fn run(mut self) {
+        let mut interval = tokio::time::interval(self.options.reload_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        let mut hangup = signal(SignalKind::hangup())
+            .map_err(|err| {
+                event!(
+                    Level::WARN,
+                    error = %err,
+                    "Unable to listen for SIGHUP, TLS secrets will only be reloaded when they change"
+                );
+            })
+            .ok();
+
+        if self.options.renew_before.is_some() && !self.tls_manager.can_renew() {
+            event!(
+                Level::WARN,
+                "The TLS certificate was not generated by the scheduler, it will not be renewed automatically"
+            );
+        }
+
+        loop {
+            let forced = tokio::select! {
+                _ = interval.tick() => false,
+                _ = Self::hangup(&mut hangup) => true,
+            };
+
+            if forced {
+                event!(Level::INFO, "Received SIGHUP, reloading the TLS secrets");
+            }
+
+            let renewed = self.renew_if_expiring();
+            self.reload(forced || renewed);
+        }
+    }
+
+    /// Wait for the next `SIGHUP`, or forever if the signal cannot be received.
+    ///
+    /// # Arguments
+    ///
+    /// * `hangup` - The `SIGHUP` listener, if it could be registered.
+    async fn hangup(hangup: &mut Option<Signal>) {
+        match hangup {
+            Some(hangup) => {
+                hangup.recv().await;
+            }
+            None => future::pending().await,
+        }
+    }
+
+    /// Reload the certificate and private key from the disk if they changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `forced` - Whether to reload them even if they did not change.
+    fn reload(&mut self, forced: bool) {
+        let (cert_data, key_data) = match self.tls_manager.read_secrets() {
+            Ok(secrets) => secrets,
+            Err(err) => {
+                event!(
+                    Level::WARN,
+                    error = %err,
+                    "Unable to reload the TLS secrets, keeping the previous ones"
+                );
+                return;
+            }
+        };
+
+        let changed = self.tls_manager.cert_data() != Some(&cert_data)
+            || self.tls_manager.key_data() != Some(&key_data);
+
+        if !changed && !forced {
+            return;
+        }
+
+        if let Err(err) = self.resolver.update(&cert_data, &key_data) {
+            event!(
+                Level::WARN,
+                error = %err,
+                "Unable to load the new TLS secrets, keeping the previous ones"
+            );
+            return;
+        }
+
+        self.tls_manager.set_secrets(cert_data, key_data);
+        event!(Level::INFO, "Reloaded the TLS secrets");
+    }
+
+    /// Renew the certificate if automatic renewal is enabled and it is about to expire,
+    /// returning whether it was renewed.
+    fn renew_if_expiring(&mut self) -> bool {
+        let Some(renew_before) = self.options.renew_before else {
+            return false;
+        };
+
+        if !self.tls_manager.can_renew() {
+            return false;
+        }
+
+        match self.tls_manager.expires_within(renew_before) {
+            Ok(true) => (),
+            Ok(false) => return false,
+            Err(err) => {
+                event!(
+                    Level::WARN,
+                    error = %err,
+                    "Unable to check the expiration of the TLS certificate"
+                );
+                return false;
+            }
+        }
+
+        event!(
+            Level::INFO,
+            "The TLS certificate is about to expire, renewing it"
+        );
+
+        match self.tls_manager.renew_secrets() {
+            Ok(()) => true,
+            Err(err) => {
+                event!(
+                    Level::ERROR,
+                    error = ?err,
+                    "Unable to renew the TLS certificate"
+                );
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::tls::config::{CertificateOptions, KeyAlgorithm, SanMismatchPolicy, TlsConfig};
+
+    /// Number of seconds in a day.
+    const DAY: u64 = 86400;
+
+    /// Create a rotator for a certificate valid for 30 days.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name of the test, used for its TLS directory.
+    /// * `renew_before` - How long before its expiration the certificate is renewed.
+    fn rotator(name: &str, renew_before: Duration) -> CertificateRotator {
+        let dir = std::env::temp_dir().join(format!(
+            "orka-scheduler-rotation-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let options = CertificateOptions::new(
+            &[],
+            &[],
+            KeyAlgorithm::EcdsaP256,
+            Duration::from_secs(30 * DAY),
+            SanMismatchPolicy::Warn,
+        );
+        let mut tls_manager = TlsManager::new(TlsConfig::new(&dir, true, options));
+        tls_manager.populate_secrets().unwrap();
+
+        let resolver = ReloadableCertResolver::new(
+            tls_manager.cert_data().unwrap(),
+            tls_manager.key_data().unwrap(),
+        )
+        .unwrap();
+
+        CertificateRotator::new(
+            tls_manager,
+            Arc::new(resolver),
+            RotationOptions {
+                reload_interval: Duration::from_secs(60),
+                renew_before: Some(renew_before),
+            },
+        )
+    }
+
+    #[test]
+    fn renews_when_within_threshold() {
+        let mut rotator = rotator("renew", Duration::from_secs(31 * DAY));
+        let previous = rotator.tls_manager.cert_data().unwrap().clone();
+
+        assert!(rotator.renew_if_expiring());
+        assert_ne!(rotator.tls_manager.cert_data().unwrap(), &previous);
+    }
+
+    #[test]
+    fn keeps_when_not_within_threshold() {
+        let mut rotator = rotator("keep", Duration::from_secs(29 * DAY));
+        let previous = rotator.tls_manager.cert_data().unwrap().clone();
+
+        assert!(!rotator.renew_if_expiring());
+        assert_eq!(rotator.tls_manager.cert_data().unwrap(), &previous);
+    }
+}